@@ -1,11 +1,18 @@
+use std::sync::Arc;
+
 use anyhow::anyhow;
 use serenity::all::{
     ActionRowComponent, Builder, ComponentInteraction, Context, CreateActionRow, CreateInputText,
-    CreateInteractionResponse, CreateModal, EditMessage, InputTextStyle, ModalInteraction,
+    CreateInteractionResponse, CreateModal, InputTextStyle, ModalInteraction,
 };
 
 use crate::{
-    chat::{ChatMessage, context::MessageIdentifier, engine::EngineGuard},
+    bot::Outbound,
+    chat::{
+        ChatMessage,
+        context::{ConversationScope, MessageIdentifier},
+        engine::EngineGuard,
+    },
     utils::misc::{self, ButtonStates},
 };
 
@@ -47,6 +54,8 @@ impl Handler {
             user,
             message,
             data,
+            guild_id,
+            channel_id,
             ..
         } = interaction;
 
@@ -68,32 +77,54 @@ impl Handler {
             .flatten()
             .ok_or_else(|| anyhow!("could not find content to edit"))?;
 
-        let data = &self.data;
+        let scope = ConversationScope::resolve(
+            &ctx,
+            &self.data.channel_kinds,
+            channel_id,
+            guild_id,
+            user.id,
+        )
+        .await?;
+
+        CreateInteractionResponse::Acknowledge
+            .execute(&ctx.http, (id, &token))
+            .await?;
 
-        let guard = EngineGuard::lock(&data, user.id).await?;
+        self.edit_response(
+            scope,
+            (message.id, message.channel_id).into(),
+            content,
+            ctx.http.clone(),
+        )
+        .await
+    }
+
+    /// Replaces a response with what the user rewrote it to, as another version of it.
+    pub async fn edit_response(
+        &self,
+        scope: ConversationScope,
+        identifier: MessageIdentifier,
+        content: String,
+        outbound: Arc<dyn Outbound>,
+    ) -> anyhow::Result<()> {
+        let guard = EngineGuard::lock(&self.data, scope).await?;
         let mut engine = guard.engine().await.write().await;
 
-        let (_, identifier, _) = match engine
-            .find_full_mut(&(message.id, message.channel_id).into())
-        {
+        let (_, found, _) = match engine.find_full_mut(&identifier) {
             Some(messages) => messages,
             None => {
                 log::warn!(
                     "No conversation thread found for edited message id: {:?}, is this our fault?",
-                    message.id
+                    identifier.message()
                 );
                 return Err(anyhow!("message not found in engine"));
             }
         };
-        let channel = identifier.channel();
-        let messages = identifier.messages();
-
-        CreateInteractionResponse::Acknowledge
-            .execute(&ctx.http, (id, &token))
-            .await?;
+        let channel = found.channel();
+        let messages = found.messages();
 
         let out: anyhow::Result<(ChatMessage, MessageIdentifier)> = async {
-            misc::delete_message_batch(channel, &ctx.http, messages).await?;
+            misc::delete_message_batch(channel, &outbound, messages).await?;
 
             let messages = misc::chunk_message(
                 &content,
@@ -103,8 +134,8 @@ impl Handler {
                 },
             )?;
 
-            let ids = misc::send_message_batch(channel, &ctx.http, messages).await?;
-            let last_id = ids.last().ok_or(anyhow::anyhow!("no message ids"))?.clone();
+            let ids = misc::send_message_batch(channel, &outbound, messages).await?;
+            let last_id = *ids.last().ok_or(anyhow::anyhow!("no message ids"))?;
 
             Ok((
                 ChatMessage::assistant(content),
@@ -113,42 +144,21 @@ impl Handler {
         }
         .await;
 
-        match out {
-            Ok((response, new_identifier)) => {
-                let identifier = (message.id, message.channel_id).into();
-                let messages = engine
-                    .find_mut(&identifier)
-                    .ok_or(anyhow::anyhow!("message not found in engine"))?;
-
-                messages.push(response); // pushes and selects
+        let (response, new_identifier) = out?;
+        let response = response.stamped(engine.now());
 
-                let message = ctx
-                    .http
-                    .get_message(new_identifier.channel(), new_identifier.message())
-                    .await;
+        let messages = engine
+            .find_mut(&identifier)
+            .ok_or(anyhow::anyhow!("message not found in engine"))?;
 
-                engine.swap_identifiers(&identifier, new_identifier)?;
+        messages.push(response); // pushes and selects
 
-                if let Ok(mut message) = message {
-                    tokio::spawn({
-                        let mut recv = self.data.msg_channel.0.subscribe();
-                        async move {
-                            let _ = recv.recv().await;
+        let last_id = new_identifier.message();
+        engine.swap_identifiers(&identifier, new_identifier)?;
+        engine.autosave().await;
 
-                            let _ = message
-                                .edit(&ctx.http, EditMessage::new().components(vec![]))
-                                .await;
+        Self::expire_buttons(&self.data, outbound, channel, last_id);
 
-                            drop(recv);
-                        }
-                    });
-
-                    Ok(())
-                } else {
-                    Err(anyhow::anyhow!("could not fetch discord message"))
-                }
-            }
-            Err(why) => Err(why),
-        }
+        Ok(())
     }
 }
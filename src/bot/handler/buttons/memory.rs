@@ -0,0 +1,80 @@
+use anyhow::anyhow;
+use serenity::all::{
+    ActionRowComponent, Context, CreateInteractionResponse, CreateInteractionResponseMessage,
+    ModalInteraction,
+};
+
+use crate::{
+    bot::handler::events::commands,
+    chat::{context::ConversationScope, engine::EngineGuard},
+};
+
+use super::super::Handler;
+
+impl Handler {
+    pub async fn memory_edit_modal(
+        &self,
+        interaction: ModalInteraction,
+        ctx: Context,
+    ) -> anyhow::Result<()> {
+        let id = interaction
+            .data
+            .custom_id
+            .strip_prefix("memory_edit_")
+            .and_then(|id| id.parse::<u64>().ok())
+            .ok_or_else(|| anyhow!("could not find the memory to edit"))?;
+
+        let content = interaction
+            .data
+            .components
+            .iter()
+            .flat_map(|row| &row.components)
+            .find_map(|component| match component {
+                ActionRowComponent::InputText(text) => text.value.clone(),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow!("could not find content to edit"))?;
+
+        let scope = ConversationScope::resolve(
+            &ctx,
+            &self.data.channel_kinds,
+            interaction.channel_id,
+            interaction.guild_id,
+            interaction.user.id,
+        )
+        .await?;
+
+        let owner = commands::memory_owner(
+            scope,
+            interaction.user.id,
+            interaction
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions),
+        );
+
+        let guard = EngineGuard::lock(&self.data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        engine
+            .client
+            .get_memory(id)
+            .await?
+            .filter(|memory| commands::owns_memory(owner, memory))
+            .ok_or_else(|| anyhow!("memory {id} not found"))?;
+        engine.client.edit_memory(id, &content).await?;
+
+        interaction
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("updated memory `{id}`."))
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+
+        Ok(())
+    }
+}
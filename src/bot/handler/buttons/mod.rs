@@ -1,12 +1,17 @@
+use std::sync::Arc;
+
 use serenity::all::{
-    ActionRowComponent, ButtonKind, Context, CreateActionRow, CreateButton, EditMessage, Http,
-    Message,
+    ActionRowComponent, ButtonKind, ChannelId, CreateActionRow, CreateButton, EditMessage, Http,
+    Message, MessageId,
 };
 
+use crate::bot::{Outbound, handler::framework::InnerData};
+
 use super::Handler;
 
 mod delete;
 mod edit;
+mod memory;
 mod next;
 mod prev;
 mod regen;
@@ -14,8 +19,8 @@ mod regen;
 impl Handler {
     pub async fn disable_buttons(
         &self,
-        message: &mut Message,
-        ctx: &Context,
+        message: &Message,
+        outbound: &dyn Outbound,
     ) -> anyhow::Result<()> {
         let buttons = CreateActionRow::Buttons(
             message
@@ -50,13 +55,34 @@ impl Handler {
                 .collect::<Vec<_>>(),
         );
 
-        message
-            .edit(&ctx.http, EditMessage::new().components(vec![buttons]))
+        outbound
+            .edit_message(
+                message.channel_id,
+                message.id,
+                EditMessage::new().components(vec![buttons]),
+            )
             .await?;
 
         Ok(())
     }
 
+    /// Removes the buttons of a response once anything else happens in any conversation.
+    pub fn expire_buttons(
+        data: &InnerData,
+        outbound: Arc<dyn Outbound>,
+        channel: ChannelId,
+        message: MessageId,
+    ) {
+        let mut recv = data.msg_channel.0.subscribe();
+        tokio::spawn(async move {
+            let _ = recv.recv().await;
+
+            let _ = outbound
+                .edit_message(channel, message, EditMessage::new().components(vec![]))
+                .await;
+        });
+    }
+
     #[allow(unused)]
     pub async fn enable_buttons(
         mut message: Message,
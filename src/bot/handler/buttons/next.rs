@@ -1,8 +1,14 @@
+use std::sync::Arc;
+
 use anyhow::bail;
-use serenity::all::{ComponentInteraction, Context, EditMessage};
+use serenity::all::{ComponentInteraction, Context};
 
 use crate::{
-    chat::engine::EngineGuard,
+    bot::{Outbound, Typing},
+    chat::{
+        context::{ConversationScope, MessageIdentifier},
+        engine::EngineGuard,
+    },
     utils::misc::{self, ButtonStates, RegenOrNext},
 };
 
@@ -10,11 +16,35 @@ use super::super::Handler;
 
 impl Handler {
     pub async fn next(&self, component: ComponentInteraction, ctx: Context) -> anyhow::Result<()> {
-        let guard = EngineGuard::lock(&self.data, component.user.id).await?;
+        let scope = ConversationScope::resolve(
+            &ctx,
+            &self.data.channel_kinds,
+            component.channel_id,
+            component.guild_id,
+            component.user.id,
+        )
+        .await?;
+
+        self.next_response(
+            scope,
+            (component.message.id, component.message.channel_id).into(),
+            ctx.http.clone(),
+        )
+        .await
+    }
+
+    /// Shows the next version of a response, replacing it on Discord.
+    pub async fn next_response(
+        &self,
+        scope: ConversationScope,
+        identifier: MessageIdentifier,
+        outbound: Arc<dyn Outbound>,
+    ) -> anyhow::Result<()> {
+        let guard = EngineGuard::lock(&self.data, scope).await?;
         let mut engine = guard.engine().await.write().await;
 
-        let (_, identifier, message) = engine
-            .find_full_mut(&(component.message.id, component.message.channel_id).into())
+        let (_, found, message) = engine
+            .find_full_mut(&identifier)
             .ok_or(anyhow::anyhow!("message not found in engine"))?;
 
         if !message.forward {
@@ -23,8 +53,8 @@ impl Handler {
 
         let forward = message.forward();
 
-        let channel = identifier.channel();
-        let messages = identifier.messages();
+        let channel = found.channel();
+        let messages = found.messages();
         let content = forward.content();
         let button_states = ButtonStates {
             prev_disabled: false, // went forward, so obviously not disabled
@@ -34,37 +64,21 @@ impl Handler {
             },
         };
 
-        let typing = ctx.http.start_typing(channel);
+        let typing = Typing::start(outbound.clone(), channel);
 
         let result: anyhow::Result<()> = async {
             let content = content.ok_or(anyhow::anyhow!("Message does not have a content"))?;
 
-            misc::delete_message_batch(channel, &ctx.http, messages).await?;
+            misc::delete_message_batch(channel, &outbound, messages).await?;
 
             let messages = misc::chunk_message(&content, button_states)?;
 
-            let ids = misc::send_message_batch(channel, &ctx.http, messages).await?;
-            let last_id = ids.last().ok_or(anyhow::anyhow!("no message ids"))?.clone();
-
-            let mut message = ctx.http.get_message(channel, last_id).await?;
-
-            engine.swap_identifiers(
-                &(component.message.id, component.message.channel_id).into(),
-                (last_id, channel, ids),
-            )?;
-
-            tokio::spawn({
-                let mut recv = self.data.msg_channel.0.subscribe();
-                async move {
-                    let _ = recv.recv().await;
+            let ids = misc::send_message_batch(channel, &outbound, messages).await?;
+            let last_id = *ids.last().ok_or(anyhow::anyhow!("no message ids"))?;
 
-                    let _ = message
-                        .edit(&ctx.http, EditMessage::new().components(vec![]))
-                        .await;
+            engine.swap_identifiers(&identifier, (last_id, channel, ids))?;
 
-                    drop(recv);
-                }
-            });
+            Self::expire_buttons(&self.data, outbound.clone(), channel, last_id);
 
             Ok(())
         }
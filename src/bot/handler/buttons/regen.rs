@@ -1,56 +1,100 @@
-use serenity::all::{ComponentInteraction, Context, EditMessage};
+use std::sync::Arc;
+
+use serenity::all::{ComponentInteraction, Context};
+use tokio::sync::mpsc;
 
 use crate::{
+    bot::{Outbound, Typing},
     chat::{
         ChatMessage,
-        context::MessageIdentifier,
+        context::{ConversationScope, MessageIdentifier},
         engine::{ContextType, EngineGuard},
     },
-    utils::misc::{self, ButtonStates},
+    utils::{
+        misc::{self, ButtonStates},
+        stream::MessageStreamer,
+    },
 };
 
 use super::super::Handler;
 
 impl Handler {
     pub async fn regen(&self, component: ComponentInteraction, ctx: Context) -> anyhow::Result<()> {
-        let guard = EngineGuard::lock(&self.data, component.user.id).await?;
+        let scope = ConversationScope::resolve(
+            &ctx,
+            &self.data.channel_kinds,
+            component.channel_id,
+            component.guild_id,
+            component.user.id,
+        )
+        .await?;
+
+        self.regen_response(
+            scope,
+            (component.message.id, component.message.channel_id).into(),
+            ctx.http.clone(),
+        )
+        .await
+    }
+
+    /// Generates another version of a response, replacing it on Discord.
+    pub async fn regen_response(
+        &self,
+        scope: ConversationScope,
+        identifier: MessageIdentifier,
+        outbound: Arc<dyn Outbound>,
+    ) -> anyhow::Result<()> {
+        let guard = EngineGuard::lock(&self.data, scope).await?;
         let mut engine = guard.engine().await.write().await;
 
         // uses this to find the error before other things
-        let (_, identifier, _) = engine
-            .find_full_mut(&(component.message.id, component.message.channel_id).into())
+        let (_, found, _) = engine
+            .find_full_mut(&identifier)
             .ok_or(anyhow::anyhow!("Message not found in engine"))?;
-        let channel = identifier.channel();
-        let messages = identifier.messages();
+        let channel = found.channel();
+        let messages = found.messages();
 
-        let typing = ctx.http.start_typing(channel);
+        let typing = Typing::start(outbound.clone(), channel);
 
         let out: anyhow::Result<(ChatMessage, MessageIdentifier)> = async {
-            let response = engine
-                .user_prompt(
+            let (deltas, events) = mpsc::unbounded_channel();
+            let mut streamer = MessageStreamer::new(channel, outbound.clone());
+
+            let (response, _) = tokio::join!(
+                engine.user_prompt_stream(
                     None,
-                    Some(ContextType::Regen(
-                        (component.message.id, component.message.channel_id).into(),
-                    )),
-                )
-                .await?;
+                    None,
+                    vec![],
+                    Some(ContextType::Regen(identifier.clone())),
+                    Some(deltas),
+                ),
+                streamer.consume(events),
+            );
+
+            let response = match response {
+                Ok(response) => response,
+                Err(why) => {
+                    streamer.discard().await;
+                    return Err(why);
+                }
+            };
 
             let content = response
                 .content()
                 .ok_or(anyhow::anyhow!("Message does not have a content"))?;
 
-            misc::delete_message_batch(channel, &ctx.http, messages).await?;
-
-            let messages = misc::chunk_message(
-                &content,
-                ButtonStates {
-                    prev_disabled: false,
-                    regen_or_next: misc::RegenOrNext::Regen,
-                },
-            )?;
+            misc::delete_message_batch(channel, &outbound, messages).await?;
 
-            let ids = misc::send_message_batch(channel, &ctx.http, messages).await?;
-            let last_id = ids.last().ok_or(anyhow::anyhow!("no message ids"))?.clone();
+            let ids = streamer
+                .finish(
+                    &content,
+                    ButtonStates {
+                        prev_disabled: false,
+                        regen_or_next: misc::RegenOrNext::Regen,
+                    },
+                )
+                .await?;
+            let last_id = *ids.last().ok_or(anyhow::anyhow!("no message ids"))?;
 
             Ok((response, (last_id, channel, ids).into()))
         }
@@ -58,42 +102,21 @@ impl Handler {
 
         typing.stop();
 
-        match out {
-            Ok((message, new_identifier)) => {
-                let identifier = (component.message.id, component.message.channel_id).into();
-                let messages = engine
-                    .find_mut(&identifier)
-                    .ok_or(anyhow::anyhow!("message not found in engine"))?;
+        let (message, new_identifier) = out?;
+        let message = message.stamped(engine.now());
 
-                messages.push(message); // pushes and selects
+        let messages = engine
+            .find_mut(&identifier)
+            .ok_or(anyhow::anyhow!("message not found in engine"))?;
 
-                let message = ctx
-                    .http
-                    .get_message(new_identifier.channel(), new_identifier.message())
-                    .await;
+        messages.push(message); // pushes and selects
 
-                engine.swap_identifiers(&identifier, new_identifier)?;
+        let last_id = new_identifier.message();
+        engine.swap_identifiers(&identifier, new_identifier)?;
+        engine.autosave().await;
 
-                if let Ok(mut message) = message {
-                    tokio::spawn({
-                        let mut recv = self.data.msg_channel.0.subscribe();
-                        async move {
-                            let _ = recv.recv().await;
+        Self::expire_buttons(&self.data, outbound, channel, last_id);
 
-                            let _ = message
-                                .edit(&ctx.http, EditMessage::new().components(vec![]))
-                                .await;
-
-                            drop(recv);
-                        }
-                    });
-
-                    Ok(())
-                } else {
-                    Err(anyhow::anyhow!("could not fetch discord message"))
-                }
-            }
-            Err(why) => Err(why),
-        }
+        Ok(())
     }
 }
@@ -5,6 +5,7 @@ use tokio::sync::RwLock;
 use crate::bot::handler::events::HandlerResult;
 use crate::bot::handler::framework::Context;
 use crate::chat;
+use crate::chat::context::ConversationScope;
 use crate::utils::macros::config;
 
 /// Clears the current context window and reloads the engine
@@ -14,21 +15,35 @@ pub async fn clear(ctx: Context<'_>) -> HandlerResult<()> {
     let mut user_map = data.user_map.write().await;
 
     let result: anyhow::Result<()> = async {
+        let scope = super::scope(ctx).await?;
+
         let new_engine = {
             let config = config!(data);
-            let mut new_engine = chat::engine::ChatEngine::new(config, ctx.author().id).await?;
-            new_engine.clear_context();
+            let mut new_engine = chat::engine::ChatEngine::new(
+                config,
+                &data.models,
+                &data.env,
+                data.reminders.clone(),
+                data.activities.clone(),
+                data.store().await,
+                scope,
+            )
+            .await?;
+            new_engine.clear_context().await?;
             RwLock::new(new_engine)
         };
 
-        let author = ctx.author();
-
-        user_map.remove(&author.id);
-        user_map.insert(author.id, new_engine);
+        if let Some(old_engine) = user_map.remove(&scope) {
+            data.untrack(scope, old_engine.into_inner().user_message_ids());
+        }
+        user_map.insert(scope, new_engine);
+        chat::engine::touch(&data, scope);
 
-        let mut freewill_map = data.freewill_map.write().await;
-        if let Some(handle) = freewill_map.remove(&author.id) {
-            handle.abort();
+        if let ConversationScope::Direct(user) = scope {
+            let mut freewill_map = data.freewill_map.write().await;
+            if let Some(handle) = freewill_map.remove(&user) {
+                handle.abort();
+            }
         }
 
         ctx.send(
@@ -2,7 +2,11 @@ use std::fmt::Display;
 
 use poise::CreateReply;
 
-use crate::bot::handler::{events::HandlerResult, framework::Context};
+use crate::{
+    bot::handler::{events::HandlerResult, framework::Context},
+    chat::engine,
+    config::{diff::ConfigChanges, validate::mask},
+};
 
 #[derive(Debug, poise::ChoiceParameter)]
 pub enum KeyChoice {
@@ -36,6 +40,8 @@ pub enum KeyChoice {
     #[name = "Vector Size"]
     VectorSize,
     // Vector DB
+    #[name = "Memory Backend"]
+    MemoryBackend,
     #[name = "QDrant Host"]
     QdrantHost,
     #[name = "QDrant Port"]
@@ -51,6 +57,17 @@ pub enum KeyChoice {
     SimilarityThreshold,
 }
 
+impl KeyChoice {
+    /// Where the secret behind the key is in the config, if it is one.
+    fn secret_path(&self) -> Option<&'static str> {
+        match self {
+            Self::ApiKey => Some("llm.completion.api_key"),
+            Self::EmbeddingApiKey => Some("llm.embedding.api_key"),
+            _ => None,
+        }
+    }
+}
+
 impl Display for KeyChoice {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -68,6 +85,7 @@ impl Display for KeyChoice {
             Self::Temperature => write!(f, "Temperature"),
             Self::VectorSize => write!(f, "Vector Size"),
             Self::SimilarityThreshold => write!(f, "Memory Similarity Threshold"),
+            Self::MemoryBackend => write!(f, "Memory Backend"),
             Self::QdrantHost => write!(f, "QDrant Host"),
             Self::QdrantPort => write!(f, "QDrant Port"),
             Self::QdrantHttps => write!(f, "Use HTTPs for QDrant"),
@@ -83,7 +101,8 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
     let result: anyhow::Result<()> = async {
         if let Some(mut value) = value {
             let mut config = data.config.write().await;
-            config.update();
+            let before = config.clone();
+            config.update()?;
 
             if value.to_lowercase() == "null" || value.to_lowercase() == "none" {
                 value = "".to_string();
@@ -91,7 +110,8 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
 
             match key {
                 KeyChoice::ApiKey => {
-                    config.llm.completion.api_key = value.clone();
+                    config.llm.completion.api_key =
+                        config.set_secret("llm.completion.api_key", &value)?;
                 }
                 KeyChoice::Model => {
                     config.llm.completion.model = value.clone();
@@ -110,7 +130,8 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
                     if value.trim().is_empty() {
                         config.llm.embedding.api_key = None;
                     } else {
-                        config.llm.embedding.api_key = Some(value.clone());
+                        config.llm.embedding.api_key =
+                            Some(config.set_secret("llm.embedding.api_key", &value)?);
                     }
                 }
                 KeyChoice::EmbeddingCustomUrl => {
@@ -202,6 +223,18 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
                             })?);
                     }
                 }
+                KeyChoice::MemoryBackend => {
+                    if value.trim().is_empty() {
+                        config.llm.embedding.backend = None;
+                    } else {
+                        config.llm.embedding.backend =
+                            Some(serde_plain::from_str(&value.to_lowercase()).map_err(|_| {
+                                anyhow::anyhow!(
+                                    "Invalid value \"{value}\", please provide either \"qdrant\" or \"local\""
+                                )
+                            })?);
+                    }
+                }
                 KeyChoice::QdrantHost => {
                     config.llm.embedding.qdrant_host = value.clone();
                 }
@@ -255,8 +288,23 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
                 }
             }
 
+            if let Err(why) = config.validate() {
+                *config = before;
+                return Err(why.into());
+            }
+
             config.async_save().await?;
 
+            // live engines pick the change up right away
+            let changes = ConfigChanges::between(&before, &config);
+
+            // never echo a secret back
+            if key.secret_path().is_some() {
+                value = mask(&value);
+            }
+            drop(config);
+            engine::apply_config(&data, changes).await;
+
             if value.trim().is_empty() {
                 ctx.send(
                     CreateReply::default()
@@ -334,6 +382,14 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
                         .map(|similarity_threshold| similarity_threshold.to_string()),
                     false,
                 ),
+                KeyChoice::MemoryBackend => (
+                    config
+                        .llm
+                        .embedding
+                        .backend
+                        .and_then(|backend| serde_plain::to_string(&backend).ok()),
+                    false,
+                ),
                 KeyChoice::QdrantHost => (Some(config.llm.embedding.qdrant_host.clone()), false),
                 KeyChoice::QdrantPort => (
                     config
@@ -371,7 +427,13 @@ pub async fn config(ctx: Context<'_>, key: KeyChoice, value: Option<String>) ->
 
             let value = if let Some(value) = value {
                 Some(if sensitive {
-                    format!("||`{}`||", value)
+                    let source = key
+                        .secret_path()
+                        .and_then(|path| config.secret_ref(path))
+                        .map(|reference| format!(" (from `{reference}`)"))
+                        .unwrap_or_default();
+
+                    format!("`{}`{source}", mask(&value))
                 } else {
                     format!("`{}`", value)
                 })
@@ -0,0 +1,127 @@
+use chrono::Timelike;
+use chrono_tz::Tz;
+use poise::CreateReply;
+use serenity::all::CreateEmbed;
+
+use crate::bot::handler::events::{HandlerResult, freewill::DEFAULT_MAX_UNANSWERED};
+use crate::bot::handler::framework::Context;
+use crate::chat::context::{ActiveHours, UserProfile};
+use crate::utils::macros::config;
+
+/// Turns freewill on or off for whoever invoked the command
+pub async fn freewill_set(ctx: Context<'_>, enabled: bool) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+        let user = ctx.author().id;
+
+        let folder = config
+            .context
+            .save_to_disk_folder
+            .as_deref()
+            .ok_or(anyhow::anyhow!(
+                "freewill settings need context.save_to_disk_folder to be set"
+            ))?;
+
+        let mut profile = UserProfile::load(folder, user).await;
+        profile.freewill = (!enabled).then_some(false);
+        profile.save(folder, user).await?;
+        data.forget_profile(user).await;
+
+        // the next message starts it again
+        if !enabled {
+            let mut freewill_map = data.freewill_map.write().await;
+            if let Some(handle) = freewill_map.remove(&user) {
+                handle.abort();
+            }
+        }
+
+        let content = match enabled {
+            true => "freewill turned on, the bot may reach out to you on its own.",
+            false => "freewill turned off, the bot will only answer you.",
+        };
+
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Shows when the bot may reach out to whoever invoked the command
+pub async fn freewill_status(ctx: Context<'_>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+        let user = ctx.author().id;
+        let freewill = &config.freewill;
+
+        // the same profile and activity freewill goes by, without loading the conversation
+        let profile = data.profile(user).await;
+        let enabled = profile.freewill_enabled();
+        let timezone = profile
+            .timezone
+            .or(config.context.system.timezone)
+            .unwrap_or(Tz::UTC);
+        let activity = data.activities.get(user).unwrap_or_default();
+        let times = activity.user_messages;
+
+        let quiet_hours = match freewill.quiet_hours {
+            Some(quiet_hours) => format!("{:02}:00-{:02}:00", quiet_hours.start, quiet_hours.end),
+            None => "none".to_string(),
+        };
+        let active_hours = match freewill.learn_active_hours.unwrap_or(true) {
+            true => match ActiveHours::learn(&times, timezone) {
+                Some(active_hours) => active_hours.to_string(),
+                None => format!(
+                    "still learning ({}/{} messages)",
+                    times.len(),
+                    ActiveHours::MIN_SAMPLES
+                ),
+            },
+            false => "not learned".to_string(),
+        };
+
+        let embed = CreateEmbed::default()
+            .title("Freewill")
+            .field("Status", if enabled { "on" } else { "off" }, true)
+            .field(
+                "Timezone",
+                format!(
+                    "{timezone} ({:02}:00 now)",
+                    data.env.now().with_timezone(&timezone).hour()
+                ),
+                true,
+            )
+            .field(
+                "Unanswered",
+                format!(
+                    "{}/{}",
+                    activity.unanswered,
+                    freewill.max_unanswered.unwrap_or(DEFAULT_MAX_UNANSWERED)
+                ),
+                true,
+            )
+            .field("Quiet hours", quiet_hours, true)
+            .field("Active hours", active_hours, true);
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
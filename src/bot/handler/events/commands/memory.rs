@@ -0,0 +1,344 @@
+use poise::CreateReply;
+use serenity::all::{
+    CreateActionRow, CreateEmbed, CreateEmbedFooter, CreateInputText, CreateInteractionResponse,
+    CreateModal, InputTextStyle, Permissions, UserId,
+};
+
+use crate::bot::handler::events::HandlerResult;
+use crate::bot::handler::framework::Context;
+use crate::chat::{archive::storage::Memory, context::ConversationScope, engine::EngineGuard};
+
+const PAGE_SIZE: usize = 10;
+const PREVIEW_LENGTH: usize = 300;
+/// Discord rejects embeds with a longer description.
+const DESCRIPTION_LIMIT: usize = 4096;
+
+fn parse_id(id: &str) -> anyhow::Result<u64> {
+    id.trim()
+        .parse::<u64>()
+        .map_err(|_| anyhow::anyhow!("Invalid memory id \"{id}\", please provide a valid number"))
+}
+
+/// Whose memories someone may see and change in a conversation, `None` for everyone's.
+/// Members of a shared conversation are limited to their own unless they can manage
+/// messages.
+pub fn memory_owner(
+    scope: ConversationScope,
+    user: UserId,
+    permissions: Option<Permissions>,
+) -> Option<UserId> {
+    match scope.is_direct() || permissions.is_some_and(|permissions| permissions.manage_messages())
+    {
+        true => None,
+        false => Some(user),
+    }
+}
+
+pub fn owns_memory(owner: Option<UserId>, memory: &Memory) -> bool {
+    owner.is_none_or(|owner| memory.user_id == Some(owner))
+}
+
+/// [memory_owner] for the author of a command. Only interactions carry their
+/// permissions, so prefix commands are always limited to the author's own memories.
+async fn author_owner(ctx: Context<'_>, scope: ConversationScope) -> Option<UserId> {
+    let permissions = ctx
+        .author_member()
+        .await
+        .and_then(|member| member.permissions);
+
+    memory_owner(scope, ctx.author().id, permissions)
+}
+
+fn format_memory(memory: &Memory, content: String, score: Option<f32>) -> String {
+    let content = match content.char_indices().nth(PREVIEW_LENGTH) {
+        Some((index, _)) => format!("{}…", &content[..index]),
+        None => content,
+    };
+
+    let score = match score {
+        Some(score) => format!(" • score `{score:.3}`"),
+        None => String::new(),
+    };
+
+    format!(
+        "**`{}`** • <t:{}:R>{}\n{}\n",
+        memory.id,
+        memory.date.timestamp(),
+        score,
+        content
+    )
+}
+
+/// Joins formatted memories into an embed description, leaving out whichever do not fit
+/// and saying how many were.
+fn join_fitting(entries: Vec<String>) -> String {
+    let total = entries.len();
+    let mut description = String::new();
+    let mut length = 0;
+
+    for (shown, entry) in entries.into_iter().enumerate() {
+        let more = format!("…and {} more", total - shown);
+        let entry_length = entry.chars().count() + 1;
+
+        if length + entry_length + more.chars().count() > DESCRIPTION_LIMIT {
+            description.push_str(&more);
+            break;
+        }
+
+        description.push_str(&entry);
+        description.push('\n');
+        length += entry_length;
+    }
+
+    description
+}
+
+/// Lists the conversation's long term memories, newest first
+pub async fn memory_list(ctx: Context<'_>, page: Option<u32>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let scope = super::scope(ctx).await?;
+        let owner = author_owner(ctx, scope).await;
+
+        let guard = EngineGuard::lock(&data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        let memories = engine
+            .client
+            .list_memories()
+            .await?
+            .into_iter()
+            .filter(|memory| owns_memory(owner, memory))
+            .collect::<Vec<_>>();
+
+        if memories.is_empty() {
+            ctx.send(
+                CreateReply::default()
+                    .content("no memories stored yet.")
+                    .ephemeral(true),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        let pages = memories.len().div_ceil(PAGE_SIZE);
+        let page = (page.unwrap_or(1) as usize).clamp(1, pages);
+
+        let description = join_fitting(
+            memories
+                .iter()
+                .skip((page - 1) * PAGE_SIZE)
+                .take(PAGE_SIZE)
+                .map(|memory| {
+                    format_memory(
+                        memory,
+                        engine.client.fill_placeholders(&memory.content),
+                        None,
+                    )
+                })
+                .collect(),
+        );
+
+        let embed = CreateEmbed::default()
+            .title("Long term memories")
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!(
+                "page {page}/{pages} • {} memories",
+                memories.len()
+            )));
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Searches the conversation's long term memories, showing their similarity scores
+pub async fn memory_search(
+    ctx: Context<'_>,
+    query: String,
+    limit: Option<u32>,
+) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let scope = super::scope(ctx).await?;
+        let owner = author_owner(ctx, scope).await;
+
+        let guard = EngineGuard::lock(&data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        let results = engine
+            .client
+            .search_memories(&query, limit.unwrap_or(PAGE_SIZE as u32) as u64)
+            .await?
+            .into_iter()
+            .filter(|(memory, _)| owns_memory(owner, memory))
+            .collect::<Vec<_>>();
+
+        let description = match results.is_empty() {
+            true => "no matching memories found.".to_string(),
+            false => join_fitting(
+                results
+                    .iter()
+                    .map(|(memory, score)| {
+                        format_memory(
+                            memory,
+                            engine.client.fill_placeholders(&memory.content),
+                            Some(*score),
+                        )
+                    })
+                    .collect(),
+            ),
+        };
+
+        let embed = CreateEmbed::default()
+            .title(format!("Memories matching \"{query}\""))
+            .description(description);
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Opens a modal to rewrite a memory, which is re-embedded once submitted
+pub async fn memory_edit(ctx: Context<'_>, id: String) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let id = parse_id(&id)?;
+
+        let poise::Context::Application(app_ctx) = ctx else {
+            anyhow::bail!("editing memories is only available as a slash command");
+        };
+
+        let scope = super::scope(ctx).await?;
+        let owner = author_owner(ctx, scope).await;
+
+        let guard = EngineGuard::lock(&data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        let memory = engine
+            .client
+            .get_memory(id)
+            .await?
+            .filter(|memory| owns_memory(owner, memory))
+            .ok_or(anyhow::anyhow!("memory {id} not found"))?;
+
+        let modal = CreateModal::new(format!("memory_edit_{id}"), "Edit Memory").components(vec![
+            CreateActionRow::InputText(
+                CreateInputText::new(InputTextStyle::Paragraph, "Memory", "memory")
+                    .placeholder("Edit this memory here")
+                    .value(engine.client.fill_placeholders(&memory.content))
+                    .required(true)
+                    .min_length(1),
+            ),
+        ]);
+
+        app_ctx
+            .interaction
+            .create_response(ctx.http(), CreateInteractionResponse::Modal(modal))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Deletes a single memory
+pub async fn memory_forget(ctx: Context<'_>, id: String) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let id = parse_id(&id)?;
+
+        let scope = super::scope(ctx).await?;
+        let owner = author_owner(ctx, scope).await;
+
+        let guard = EngineGuard::lock(&data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        engine
+            .client
+            .get_memory(id)
+            .await?
+            .filter(|memory| owns_memory(owner, memory))
+            .ok_or(anyhow::anyhow!("memory {id} not found"))?;
+        engine.client.forget_memory(id).await?;
+
+        ctx.send(
+            CreateReply::default()
+                .content(format!("forgot memory `{id}`."))
+                .ephemeral(true),
+        )
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Deletes every memory of the conversation
+pub async fn memory_forget_all(ctx: Context<'_>, confirm: bool) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        if !confirm {
+            anyhow::bail!("refusing to forget all memories without confirmation");
+        }
+
+        let scope = super::scope(ctx).await?;
+        if author_owner(ctx, scope).await.is_some() {
+            anyhow::bail!(
+                "forgetting every memory of a shared conversation takes the Manage Messages permission"
+            );
+        }
+
+        let guard = EngineGuard::lock(&data, scope).await?;
+        let engine = guard.engine().await.read().await;
+
+        engine.client.forget_all_memories().await?;
+
+        ctx.send(
+            CreateReply::default()
+                .content("forgot all memories.")
+                .ephemeral(true),
+        )
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
@@ -1,7 +1,32 @@
 mod clear;
 mod config;
+mod freewill;
+mod memory;
+mod persona;
+mod profile;
 mod reload;
+mod reminder;
 
 pub use clear::*;
 pub use config::*;
+pub use freewill::*;
+pub use memory::*;
+pub use persona::*;
+pub use profile::*;
 pub use reload::*;
+pub use reminder::*;
+
+use crate::bot::handler::framework::Context;
+use crate::chat::context::ConversationScope;
+
+/// Resolves the conversation a command was invoked from.
+async fn scope(ctx: Context<'_>) -> anyhow::Result<ConversationScope> {
+    ConversationScope::resolve(
+        ctx.serenity_context(),
+        &ctx.data().channel_kinds,
+        ctx.channel_id(),
+        ctx.guild_id(),
+        ctx.author().id,
+    )
+    .await
+}
@@ -0,0 +1,127 @@
+use poise::CreateReply;
+use serenity::all::CreateEmbed;
+
+use crate::bot::handler::events::HandlerResult;
+use crate::bot::handler::framework::Context;
+use crate::chat::engine::EngineGuard;
+use crate::utils::macros::config;
+
+/// Name the default persona (`context.system`) goes by in commands.
+pub const DEFAULT_PERSONA: &str = "default";
+
+/// Lists the configured personas, marking the conversation's active one
+pub async fn persona_list(ctx: Context<'_>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+
+        let guard = EngineGuard::lock(&data, super::scope(ctx).await?).await?;
+        let engine = guard.engine().await.read().await;
+        let active = engine.persona().unwrap_or(DEFAULT_PERSONA);
+
+        let description = std::iter::once(DEFAULT_PERSONA.to_string())
+            .chain(config.persona_names())
+            .map(|name| {
+                let persona = config
+                    .with_persona((name != DEFAULT_PERSONA).then_some(name.as_str()))
+                    .map(|config| config.context.system.chatbot_name)
+                    .unwrap_or_default();
+
+                match name == active {
+                    true => format!("**`{name}`** • {persona} (active)"),
+                    false => format!("`{name}` • {persona}"),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let embed = CreateEmbed::default()
+            .title("Personas")
+            .description(description);
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Switches the conversation over to another persona, keeping the context window
+pub async fn persona_switch(ctx: Context<'_>, name: String) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+        let persona = (name != DEFAULT_PERSONA).then_some(name.clone());
+
+        let guard = EngineGuard::lock(&data, super::scope(ctx).await?).await?;
+        let mut engine = guard.engine().await.write().await;
+
+        engine.switch_persona(config, &data.models, persona).await?;
+
+        ctx.send(
+            CreateReply::default()
+                .content(format!(
+                    "switched to `{name}`, you are now talking to {}.",
+                    engine.config.system.chatbot_name
+                ))
+                .ephemeral(true),
+        )
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Shows the conversation's active persona
+pub async fn persona_show(ctx: Context<'_>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+
+        let guard = EngineGuard::lock(&data, super::scope(ctx).await?).await?;
+        let engine = guard.engine().await.read().await;
+
+        let name = engine.persona().unwrap_or(DEFAULT_PERSONA);
+        let persona_config = config.with_persona(engine.persona())?;
+        let llm = &persona_config.llm.completion;
+        let system = &engine.config.system;
+
+        let mut embed = CreateEmbed::default()
+            .title(format!("{} (`{name}`)", system.chatbot_name))
+            .description(&system.about)
+            .field("Model", &llm.model, true);
+
+        if let Some(temperature) = llm.temperature {
+            embed = embed.field("Temperature", temperature.to_string(), true);
+        }
+        if let Some(tone) = &system.tone {
+            embed = embed.field("Tone", tone, false);
+        }
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
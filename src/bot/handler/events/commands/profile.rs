@@ -0,0 +1,180 @@
+use chrono_tz::Tz;
+use poise::CreateReply;
+use serenity::all::CreateEmbed;
+use tokio::sync::RwLock;
+
+use crate::bot::handler::events::HandlerResult;
+use crate::bot::handler::framework::Context;
+use crate::chat::{
+    context::{ConversationScope, UserProfile},
+    engine::ChatEngine,
+};
+use crate::utils::macros::config;
+
+/// Shows the profile of whoever invoked the command
+pub async fn profile_show(ctx: Context<'_>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+        let folder = config
+            .context
+            .save_to_disk_folder
+            .as_deref()
+            .ok_or(anyhow::anyhow!(
+                "profiles need context.save_to_disk_folder to be set"
+            ))?;
+
+        let profile = UserProfile::load(folder, ctx.author().id).await;
+        let system = &config.context.system;
+        let unset = |value: &str| format!("{value} (default)");
+
+        let embed = CreateEmbed::default()
+            .title("Your profile")
+            .field(
+                "Name",
+                profile
+                    .user_name
+                    .unwrap_or_else(|| unset(&system.user_name)),
+                true,
+            )
+            .field(
+                "Timezone",
+                profile
+                    .timezone
+                    .or(system.timezone)
+                    .map(|timezone| timezone.to_string())
+                    .unwrap_or_else(|| unset("UTC")),
+                true,
+            )
+            .field(
+                "About",
+                profile
+                    .user_about
+                    .or(system.user_about.clone())
+                    .unwrap_or_else(|| unset("nothing")),
+                false,
+            );
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Updates the given fields of the invoker's profile, `None` leaving them untouched
+pub async fn profile_set(
+    ctx: Context<'_>,
+    name: Option<String>,
+    about: Option<String>,
+    timezone: Option<String>,
+) -> HandlerResult<()> {
+    let result: anyhow::Result<()> = async {
+        let timezone = timezone
+            .map(|timezone| {
+                timezone.trim().parse::<Tz>().map_err(|_| {
+                    anyhow::anyhow!(
+                        "Unknown timezone \"{timezone}\", try something like \"America/New_York\""
+                    )
+                })
+            })
+            .transpose()?;
+
+        update_profile(ctx, |profile| {
+            if let Some(name) = name {
+                profile.user_name = Some(name);
+            }
+            if let Some(about) = about {
+                profile.user_about = Some(about);
+            }
+            if let Some(timezone) = timezone {
+                profile.timezone = Some(timezone);
+            }
+        })
+        .await?;
+
+        ctx.send(
+            CreateReply::default()
+                .content("profile updated.")
+                .ephemeral(true),
+        )
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Resets the invoker's profile back to the configured defaults
+pub async fn profile_reset(ctx: Context<'_>) -> HandlerResult<()> {
+    let result: anyhow::Result<()> = async {
+        // freewill has a command of its own
+        update_profile(ctx, |profile| {
+            *profile = UserProfile {
+                freewill: profile.freewill,
+                ..Default::default()
+            }
+        })
+        .await?;
+
+        ctx.send(
+            CreateReply::default()
+                .content("profile reset to the defaults.")
+                .ephemeral(true),
+        )
+        .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Saves the edited profile and reloads the invoker's direct conversation so it's
+/// picked up right away.
+async fn update_profile(
+    ctx: Context<'_>,
+    edit: impl FnOnce(&mut UserProfile),
+) -> anyhow::Result<()> {
+    let data = ctx.data();
+    let config = config!(&data);
+    let user = ctx.author().id;
+
+    let folder = config
+        .context
+        .save_to_disk_folder
+        .as_deref()
+        .ok_or(anyhow::anyhow!(
+            "profiles need context.save_to_disk_folder to be set"
+        ))?;
+
+    let mut profile = UserProfile::load(folder, user).await;
+    edit(&mut profile);
+    profile.save(folder, user).await?;
+    data.forget_profile(user).await;
+
+    let scope = ConversationScope::Direct(user);
+    let mut user_map = data.user_map.write().await;
+    if let Some(engine) = user_map.remove(&scope) {
+        let engine = ChatEngine::reload(engine.into_inner(), config, &data.models).await?;
+        user_map.insert(scope, RwLock::new(engine));
+    }
+
+    Ok(())
+}
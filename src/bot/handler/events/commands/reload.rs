@@ -16,12 +16,26 @@ pub async fn reload(ctx: Context<'_>) -> HandlerResult<()> {
     let mut user_map = data.user_map.write().await;
 
     let result: anyhow::Result<()> = async {
-        let author = ctx.author();
-        let engine = match user_map.remove(&author.id) {
-            Some(engine) => chat::engine::ChatEngine::reload(engine.into_inner(), config).await,
-            None => chat::engine::ChatEngine::new(config, ctx.author().id).await,
+        let scope = super::scope(ctx).await?;
+        let engine = match user_map.remove(&scope) {
+            Some(engine) => {
+                chat::engine::ChatEngine::reload(engine.into_inner(), config, &data.models).await
+            }
+            None => {
+                chat::engine::ChatEngine::new(
+                    config,
+                    &data.models,
+                    &data.env,
+                    data.reminders.clone(),
+                    data.activities.clone(),
+                    data.store().await,
+                    scope,
+                )
+                .await
+            }
         }?;
-        user_map.insert(author.id, RwLock::new(engine));
+        user_map.insert(scope, RwLock::new(engine));
+        chat::engine::touch(data, scope);
 
         ctx.send(
             CreateReply::default()
@@ -0,0 +1,144 @@
+use poise::CreateReply;
+use serenity::all::{CreateEmbed, CreateEmbedFooter};
+
+use crate::bot::handler::events::HandlerResult;
+use crate::bot::handler::framework::Context;
+use crate::chat::{
+    context::{ConversationScope, UserProfile},
+    reminder::{self, MAX_PENDING, Reminder},
+};
+use crate::utils::macros::config;
+
+fn format_reminder(reminder: &Reminder) -> String {
+    let asked_by = match reminder.user {
+        Some(user) => format!(" • for <@{user}>"),
+        None => String::new(),
+    };
+
+    format!(
+        "**`{}`** • <t:{}:f> (<t:{}:R>){}\n{}\n",
+        reminder.id,
+        reminder.due.timestamp(),
+        reminder.due.timestamp(),
+        asked_by,
+        reminder.note
+    )
+}
+
+/// Lists the conversation's pending reminders, soonest first
+pub async fn reminder_list(ctx: Context<'_>) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let reminders = data.reminders.list(super::scope(ctx).await?)?;
+
+        if reminders.is_empty() {
+            ctx.send(
+                CreateReply::default()
+                    .content("no reminders pending.")
+                    .ephemeral(true),
+            )
+            .await?;
+
+            return Ok(());
+        }
+
+        let description = reminders
+            .iter()
+            .map(format_reminder)
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let embed = CreateEmbed::default()
+            .title("Reminders")
+            .description(description)
+            .footer(CreateEmbedFooter::new(format!(
+                "{}/{MAX_PENDING} reminders",
+                reminders.len()
+            )));
+
+        ctx.send(CreateReply::default().embed(embed).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Schedules a reminder for whoever invoked the command, `when` being in their timezone
+pub async fn reminder_add(ctx: Context<'_>, when: String, note: String) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let config = config!(&data);
+        let user = ctx.author().id;
+
+        let timezone = UserProfile::timezone_of(
+            config.context.save_to_disk_folder.as_deref(),
+            user,
+            config.context.system.timezone,
+        )
+        .await;
+
+        let due = reminder::parse_when(&when, data.reminders.now(), timezone)?;
+        let scope = super::scope(ctx).await?;
+        let reminder = data.reminders.add(scope, Some(user), due, note).await?;
+
+        let content = match scope {
+            ConversationScope::Direct(_) => format!(
+                "reminder `{}` set, the bot will bring it up <t:{}:R>.",
+                reminder.id,
+                reminder.due.timestamp()
+            ),
+            _ => format!(
+                "reminder `{}` set, the bot will bring it up in this channel <t:{}:R>.",
+                reminder.id,
+                reminder.due.timestamp()
+            ),
+        };
+
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
+
+/// Cancels one of the conversation's reminders, only whoever asked for it can
+pub async fn reminder_cancel(ctx: Context<'_>, id: u64) -> HandlerResult<()> {
+    let data = ctx.data().clone();
+
+    let result: anyhow::Result<()> = async {
+        let scope = super::scope(ctx).await?;
+        let content = match data
+            .reminders
+            .cancel(scope, id, Some(ctx.author().id))
+            .await?
+        {
+            Some(_) => format!("reminder `{id}` cancelled."),
+            None => format!("there is no reminder `{id}`."),
+        };
+
+        ctx.send(CreateReply::default().content(content).ephemeral(true))
+            .await?;
+
+        Ok(())
+    }
+    .await;
+
+    match result {
+        Ok(_) => HandlerResult::ok(()),
+        Err(why) => HandlerResult::err(why, ctx),
+    }
+}
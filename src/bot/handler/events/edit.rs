@@ -1,8 +1,12 @@
 use anyhow::anyhow;
-use serenity::all::{Context, Message, MessageUpdateEvent};
+use serenity::all::{ChannelId, Context, GuildId, Message, MessageId, MessageUpdateEvent, UserId};
 
 use crate::{
-    chat::{ChatMessage, context::UserPrompt, engine::EngineGuard},
+    chat::{
+        ChatMessage,
+        context::{ConversationScope, Speaker, UserPrompt},
+        engine::EngineGuard,
+    },
     utils,
 };
 
@@ -32,7 +36,18 @@ impl Handler {
             return HandlerResult::ok(());
         };
 
-        let guard = match EngineGuard::lock(&self.data, author.id).await {
+        let Some(scope) = self
+            .edited_scope(event.id, event.channel_id, event.guild_id, author.id)
+            .await
+        else {
+            log::trace!(
+                "ignoring the edit of {}, it is not part of any conversation",
+                event.id
+            );
+            return HandlerResult::ok(());
+        };
+
+        let guard = match EngineGuard::lock(&self.data, scope).await {
             Ok(guard) => guard,
             Err(why) => {
                 return HandlerResult::err(
@@ -48,16 +63,33 @@ impl Handler {
 
         let mut engine = guard.engine().await.write().await;
 
+        // saved conversations have to be loaded to tell
+        if engine.find((event.id, event.channel_id)).is_none() {
+            log::trace!(
+                "ignoring the edit of {}, {scope} does not have it",
+                event.id
+            );
+            return HandlerResult::ok(());
+        }
+
         let user_prompt = match async {
             let mut user_prompt = UserPrompt {
                 content: Some(new_content),
-                current_time: engine.config.system.get_time(),
+                current_time: engine.config.system.get_time(engine.now()),
                 relevant_memories: vec![],
                 time_since: utils::time_to_string(engine.time_since_last()),
                 system_note: None,
+                speaker: Some(engine.with_profile(Speaker::from(&author)).await),
                 freewill: false,
+                reminder: false,
+                // editing a message cannot change what was attached to it
+                attachments: engine
+                    .find((event.id, event.channel_id))
+                    .map(|messages| messages.selected().attachments.clone())
+                    .unwrap_or_default(),
             };
             engine.client.rag_recall(&mut user_prompt).await?;
+            engine.fit_memories(&mut user_prompt);
 
             Ok::<UserPrompt, anyhow::Error>(user_prompt)
         }
@@ -78,13 +110,10 @@ impl Handler {
         };
 
         // user message
+        let now = engine.now();
         let messages = match engine.find_mut(&(event.id, event.channel_id).into()) {
             Some(messages) => messages,
             None => {
-                log::warn!(
-                    "No conversation thread found for edited message id: {:?}, is this our fault?",
-                    event.id
-                );
                 return HandlerResult::err(
                     anyhow!("message not found in engine"),
                     (
@@ -97,26 +126,38 @@ impl Handler {
         };
 
         // push the new message and select it
-        // messages.push(ChatMessage::user(new_content));
-        if let Err(why) = async {
-            messages.push(TryInto::<ChatMessage>::try_into(user_prompt)?);
-
-            Ok::<(), anyhow::Error>(())
-        }
-        .await
-        {
-            log::error!("failed to push message: {why:?}");
-
-            return HandlerResult::err(
-                why,
-                (
-                    ctx.http,
-                    event.channel_id,
-                    event.message_reference.flatten(),
-                ),
-            );
-        }
+        messages.push(ChatMessage::from(user_prompt).stamped(now));
 
         HandlerResult::ok(())
     }
+
+    /// The conversation an edited message may be part of, without looking its channel up.
+    /// A loaded conversation only has it if it is tracked, a saved one has to be loaded to
+    /// tell.
+    async fn edited_scope(
+        &self,
+        message: MessageId,
+        channel: ChannelId,
+        guild: Option<GuildId>,
+        author: UserId,
+    ) -> Option<ConversationScope> {
+        let candidates = match guild {
+            None => vec![ConversationScope::Direct(author)],
+            Some(_) => vec![
+                ConversationScope::Channel(channel),
+                ConversationScope::Thread(channel),
+            ],
+        };
+
+        let tracked = self.data.is_tracked(message);
+        let user_map = self.data.user_map.read().await;
+        let saved_scopes = self.data.saved_scopes.read().await;
+
+        candidates
+            .into_iter()
+            .find(|scope| match user_map.contains_key(scope) {
+                true => tracked,
+                false => saved_scopes.contains(scope),
+            })
+    }
 }
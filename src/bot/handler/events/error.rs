@@ -6,7 +6,7 @@ use serenity::all::{
     MessageReference,
 };
 
-use crate::bot::handler::framework::Context;
+use crate::{bot::handler::framework::Context, chat::client::ProviderFailure};
 
 use super::super::Handler;
 
@@ -18,10 +18,17 @@ impl Handler {
 
         log::error!("handling error:\n\n{error:?}\n");
 
-        let embed = CreateEmbed::default()
+        let mut embed = CreateEmbed::default()
             .color(0xFF6961)
             .title("Chatbot encountered an error")
             .description(format!("```{}```", error.to_string()));
+
+        if let Some(failure) = error.downcast_ref::<ProviderFailure>() {
+            embed = embed
+                .description(format!("```{}```", error.root_cause()))
+                .field("Provider", &failure.provider, true)
+                .field("Failure", failure.kind.to_string(), true);
+        }
         let button = CreateButton::new("delete_error")
             .label("")
             .emoji('🗑')
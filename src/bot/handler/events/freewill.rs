@@ -1,12 +1,20 @@
 use std::sync::Arc;
 
-use rand::Rng;
-use serenity::all::{ChannelId, EditMessage, Http, MessageId, UserId};
-use tokio::{task::JoinHandle, time};
+use std::time::Duration;
+
+use chrono::Timelike;
+use chrono_tz::Tz;
+use serenity::all::{ChannelId, MessageId, UserId};
+use tokio::task::JoinHandle;
 
 use crate::{
-    bot::handler::framework::InnerData,
-    chat::engine::{ChatEngine, ContextType, EngineGuard},
+    bot::{Outbound, handler::framework::InnerData},
+    chat::{
+        ChatMessage,
+        context::{ActiveHours, ConversationScope},
+        engine::{ChatEngine, ContextType, EngineGuard},
+    },
+    config::structure::FreewillConfig,
     utils::{
         macros::config,
         misc::{self, ButtonStates},
@@ -15,20 +23,35 @@ use crate::{
 
 use super::super::Handler;
 
+/// How many freewill messages in a row can go unanswered, unless configured otherwise.
+pub const DEFAULT_MAX_UNANSWERED: usize = 1;
+
+/// What the freewill loop of a user does next.
+pub enum FreewillDecision {
+    /// Reach out now
+    Freewill,
+    /// Check again later
+    Wait,
+    /// Stop until the user sends a message again
+    Stop,
+}
+
 impl Handler {
-    pub async fn freewill_dispatch(&self, user: UserId, channel: ChannelId, http: Arc<Http>) {
+    /// Starts the freewill task of a user unless it is already running, it reaches out in
+    /// the channel their conversation last took place in.
+    pub async fn freewill_dispatch(&self, user: UserId, outbound: Arc<dyn Outbound>) {
+        if !Self::freewill_enabled(&self.data, user).await {
+            log::trace!("freewill is turned off for {user}");
+            return;
+        }
+
         let mut freewill_map = self.data.freewill_map.write().await;
         freewill_map
             .entry(user)
             .and_modify(|handle| {
                 if handle.is_finished() {
                     log::info!("freewill was finished, dispatching again");
-                    *handle = Self::freewill_spawn(
-                        self.data.clone(),
-                        user,
-                        channel.clone(),
-                        http.clone(),
-                    );
+                    *handle = Self::freewill_spawn(self.data.clone(), user, outbound.clone());
                 } else {
                     // freewill is already running
                     log::trace!("freewill is already running");
@@ -37,15 +60,19 @@ impl Handler {
             .or_insert_with(|| {
                 log::info!("freewill is not running, dispatching");
 
-                Self::freewill_spawn(self.data.clone(), user, channel, http)
+                Self::freewill_spawn(self.data.clone(), user, outbound)
             });
     }
 
+    /// Whether the user did not turn freewill off, which needs profiles to be saved.
+    pub async fn freewill_enabled(data: &InnerData, user: UserId) -> bool {
+        data.profile(user).await.freewill_enabled()
+    }
+
     pub fn freewill_spawn(
         data: Arc<InnerData>,
         user: UserId,
-        channel: ChannelId,
-        http: Arc<Http>,
+        outbound: Arc<dyn Outbound>,
     ) -> JoinHandle<()> {
         tokio::spawn({
             async move {
@@ -54,18 +81,21 @@ impl Handler {
                     // let min = 0; debbuging stuff
                     let max = 120;
                     // let max = 5; debbuging stuff
-                    let interval = time::Duration::from_secs(rand::random_range(min..max));
-
-                    tokio::time::sleep(interval).await;
-
-                    if Self::should_freewill(data.clone(), user).await {
-                        let did_freewill =
-                            Self::freewill(data.clone(), user, channel.clone(), http.clone()).await;
-                        log::info!("freewill done");
-                        if did_freewill {
-                            return;
-                        } else {
-                            log::warn!("freewill failed, will retry later once called again");
+                    let interval = Duration::from_secs(data.env.rng.random_range(min..max));
+
+                    data.env.clock.sleep(interval).await;
+
+                    match Self::should_freewill(data.clone(), user).await {
+                        FreewillDecision::Wait => (),
+                        FreewillDecision::Stop => return,
+                        FreewillDecision::Freewill => {
+                            let did_freewill =
+                                Self::freewill(data.clone(), user, outbound.clone()).await;
+                            log::info!("freewill done");
+                            if !did_freewill {
+                                log::warn!("freewill failed, will retry later once called again");
+                                return;
+                            }
                         }
                     };
                 }
@@ -73,26 +103,26 @@ impl Handler {
         })
     }
 
-    pub async fn freewill(
-        data: Arc<InnerData>,
-        user: UserId,
-        channel: ChannelId,
-        http: Arc<Http>,
-    ) -> bool {
+    pub async fn freewill(data: Arc<InnerData>, user: UserId, outbound: Arc<dyn Outbound>) -> bool {
         log::debug!("attempting to freewill");
-        let guard = if let Ok(engine) = EngineGuard::lock(&data, user).await {
-            engine
-        } else {
-            return false;
-        };
+        let guard =
+            if let Ok(engine) = EngineGuard::lock(&data, ConversationScope::Direct(user)).await {
+                engine
+            } else {
+                return false;
+            };
 
         let mut engine = guard.engine().await.write().await;
+        let Some(channel) = engine.channel() else {
+            log::error!("no channel to reach {user} in");
+            return false;
+        };
 
         let out: anyhow::Result<MessageId> = async {
             Self::freewill_memory_store(&engine).await?;
 
             let mut response = engine
-                .user_prompt(None, Some(ContextType::Freewill))
+                .user_prompt(None, None, Some(ContextType::Freewill))
                 .await?;
             response.freewill = true;
 
@@ -100,50 +130,12 @@ impl Handler {
                 log::trace!("freewill response:\n{}", content);
             }
 
-            let messages = misc::chunk_message(
-                &response
-                    .content()
-                    .ok_or(anyhow::anyhow!("message does not have a content"))?,
-                ButtonStates {
-                    prev_disabled: true,
-                    regen_or_next: misc::RegenOrNext::Regen,
-                },
-            )?;
-
-            let ids = misc::send_message_batch(channel, &http, messages).await?;
-            let last_id = ids.last().ok_or(anyhow::anyhow!("no message ids"))?.clone();
-
-            engine.add_message(response, (last_id, channel, ids));
-
-            Ok(last_id)
+            Self::send_unprompted(&data, &mut engine, channel, response, outbound).await
         }
         .await;
 
         match out {
-            Ok(msg_id) => {
-                let message = http.get_message(channel, msg_id).await;
-
-                if let Ok(mut message) = message {
-                    let _ = data.msg_channel.0.send("freewill".to_string());
-                    let mut recv = data.msg_channel.0.subscribe();
-                    tokio::spawn({
-                        async move {
-                            let _ = recv.recv().await;
-
-                            let _ = message
-                                .edit(&http, EditMessage::new().components(vec![]))
-                                .await;
-
-                            drop(recv);
-                        }
-                    });
-
-                    true
-                } else {
-                    log::error!("could not fetch discord message");
-                    false
-                }
-            }
+            Ok(_) => true,
             Err(why) => {
                 log::error!("Error sending message: {why:?}");
                 return false;
@@ -151,30 +143,106 @@ impl Handler {
         }
     }
 
-    pub async fn should_freewill(data: Arc<InnerData>, user: UserId) -> bool {
-        let guard = if let Ok(engine) = EngineGuard::lock(&data, user).await {
-            engine
-        } else {
-            return false;
-        };
+    /// Sends a response the bot came up with on its own and adds it to the conversation,
+    /// its buttons going away once anything else happens.
+    pub async fn send_unprompted(
+        data: &InnerData,
+        engine: &mut ChatEngine,
+        channel: ChannelId,
+        response: ChatMessage,
+        outbound: Arc<dyn Outbound>,
+    ) -> anyhow::Result<MessageId> {
+        let messages = misc::chunk_message(
+            &response
+                .content()
+                .ok_or(anyhow::anyhow!("message does not have a content"))?,
+            ButtonStates {
+                prev_disabled: true,
+                regen_or_next: misc::RegenOrNext::Regen,
+            },
+        )?;
+
+        let ids = misc::send_message_batch(channel, &outbound, messages).await?;
+        let last_id = *ids.last().ok_or(anyhow::anyhow!("no message ids"))?;
+
+        engine.add_message(response, (last_id, channel, ids));
+        engine.autosave().await;
+
+        // the buttons of whatever was answered before go away
+        let _ = data.msg_channel.0.send("freewill".to_string());
+        Self::expire_buttons(data, outbound, channel, last_id);
+
+        Ok(last_id)
+    }
 
-        let engine = guard.engine().await.read().await;
+    /// Decides whether to reach out now, waiting through quiet and inactive hours and
+    /// stopping once the user stopped answering or turned freewill off. It goes by the
+    /// recorded activity of the conversation, which is only loaded to reach out.
+    pub async fn should_freewill(data: Arc<InnerData>, user: UserId) -> FreewillDecision {
+        if !Self::freewill_enabled(&data, user).await {
+            return FreewillDecision::Stop;
+        }
 
-        let time_since_last = engine.time_since_last().num_seconds() as f64;
+        let Some(activity) = data.activities.get(user) else {
+            log::debug!("{user} has no conversation to pick up, stopping");
+            return FreewillDecision::Stop;
+        };
+        let Some(last_message) = activity.last_message else {
+            log::debug!("{user}'s conversation is empty, stopping");
+            return FreewillDecision::Stop;
+        };
 
         let config = config!(data);
-        let mut rng = rand::rng();
-        let threshold = exponential_probability(
-            time_since_last,
-            0,
-            config.freewill.min_time_secs,
-            config.freewill.max_time_secs,
-            config.freewill.steepness,
-        );
+        let freewill = &config.freewill;
 
-        let bool = rng.random_bool(threshold);
+        let max_unanswered = freewill.max_unanswered.unwrap_or(DEFAULT_MAX_UNANSWERED);
+        if activity.unanswered >= max_unanswered {
+            log::info!("{user} left {max_unanswered} freewill messages unanswered, stopping");
+            return FreewillDecision::Stop;
+        }
+
+        let timezone = data
+            .profile(user)
+            .await
+            .timezone
+            .or(config.context.system.timezone)
+            .unwrap_or(Tz::UTC);
+        let now = data.env.now();
+        let hour = now.with_timezone(&timezone).hour();
+
+        if freewill
+            .quiet_hours
+            .is_some_and(|quiet_hours| quiet_hours.contains(hour))
+        {
+            log::trace!("quiet hours for {user}, waiting");
+            return FreewillDecision::Wait;
+        }
+
+        if freewill.learn_active_hours.unwrap_or(true)
+            && ActiveHours::learn(&activity.user_messages, timezone)
+                .is_some_and(|active_hours| !active_hours.contains(hour))
+        {
+            log::trace!("{user} is usually not around at {hour}:00, waiting");
+            return FreewillDecision::Wait;
+        }
 
-        bool
+        let threshold = Self::freewill_chance(freewill, now - last_message);
+
+        match data.env.rng.random_bool(threshold) {
+            true => FreewillDecision::Freewill,
+            false => FreewillDecision::Wait,
+        }
+    }
+
+    /// How likely reaching out is once the conversation went quiet for `time_since_last`.
+    pub fn freewill_chance(freewill: &FreewillConfig, time_since_last: chrono::Duration) -> f64 {
+        exponential_probability(
+            time_since_last.num_seconds() as f64,
+            0,
+            freewill.min_time_secs,
+            freewill.max_time_secs,
+            freewill.steepness,
+        )
     }
 
     pub async fn freewill_memory_store(engine: &ChatEngine) -> anyhow::Result<()> {
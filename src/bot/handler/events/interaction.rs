@@ -25,10 +25,10 @@ impl Handler {
     }
 
     async fn on_component(&self, ctx: Context, interaction: Interaction) -> HandlerResult<()> {
-        if let Some(mut component) = interaction.into_message_component() {
+        if let Some(component) = interaction.into_message_component() {
             let result = match component.data.custom_id.as_str() {
                 id @ ("regen" | "prev" | "next") => {
-                    if let Err(why) = self.disable_buttons(&mut *component.message, &ctx).await {
+                    if let Err(why) = self.disable_buttons(&component.message, &ctx.http).await {
                         log::error!("error editing message: {why:?}");
                         return HandlerResult::err(why, (ctx.http, *component.message));
                     };
@@ -72,6 +72,9 @@ impl Handler {
                 custom_id if custom_id.starts_with("edit_") => {
                     self.edit_modal(modal.clone(), ctx.clone()).await
                 }
+                custom_id if custom_id.starts_with("memory_edit_") => {
+                    self.memory_edit_modal(modal.clone(), ctx.clone()).await
+                }
                 _ => {
                     log::warn!("unknown custom_id \"{:?}\", ignoring", modal.data.custom_id);
                     Ok(())
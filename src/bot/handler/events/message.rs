@@ -1,8 +1,15 @@
-use serenity::all::{ChannelId, Context, EditMessage, Message, MessageId};
+use std::sync::Arc;
+
+use serenity::all::{ChannelId, Context, Message, MessageId};
+use tokio::sync::mpsc;
 
 use crate::{
-    chat::engine::{ContextType, EngineGuard},
-    utils::misc::ButtonStates,
+    bot::{Outbound, Typing},
+    chat::{
+        context::{Attachment, ConversationScope, Speaker},
+        engine::{ContextType, EngineGuard},
+    },
+    utils::{attachments, macros::config, misc::ButtonStates, stream::MessageStreamer},
 };
 
 use super::{super::Handler, error::HandlerResult};
@@ -16,70 +23,166 @@ impl Handler {
             self.data.msg_channel.0.send(msg.content.clone()).unwrap();
         }
 
-        self.freewill_dispatch(msg.author.id, msg.channel_id, ctx.http.clone())
-            .await;
+        let scope = match ConversationScope::resolve(
+            &ctx,
+            &self.data.channel_kinds,
+            msg.channel_id,
+            msg.guild_id,
+            msg.author.id,
+        )
+        .await
+        {
+            Ok(scope) => scope,
+            Err(why) => return HandlerResult::err(why, (ctx.http, msg)),
+        };
+
+        let content = match scope.is_direct() {
+            true => msg.content.clone(),
+            false => msg.content_safe(&ctx.cache),
+        };
+        let config = config!(self.data);
+        let speaker = Speaker::from(&msg.author);
+
+        if !scope.is_direct() && !self.is_triggered(&ctx, &msg).await {
+            // only keep track of the conversation if the bot is already part of it
+            let known = self.data.user_map.read().await.contains_key(&scope)
+                || self.data.saved_scopes.read().await.contains(&scope);
+            if known {
+                let guard = match EngineGuard::lock(&self.data, scope).await {
+                    Ok(guard) => guard,
+                    Err(why) => return HandlerResult::err(why, (ctx.http, msg)),
+                };
+
+                let mut engine = guard.engine().await.write().await;
+                let speaker = engine.with_profile(speaker).await;
+                engine.observe_message(content, speaker, (msg.id, msg.channel_id));
+                self.data.track([msg.id]);
+            }
+
+            return HandlerResult::ok(());
+        }
+
+        if let ConversationScope::Direct(user) = scope {
+            self.freewill_dispatch(user, ctx.http.clone()).await;
+        }
+
+        let attachments = attachments::collect(
+            &msg,
+            &config.discord.attachments.clone().unwrap_or_default(),
+            config.llm.completion.vision.unwrap_or(false),
+        )
+        .await;
 
-        let typing = ctx.http.start_typing(msg.channel_id);
+        match self
+            .reply(
+                scope,
+                content,
+                speaker,
+                attachments,
+                (msg.id, msg.channel_id),
+                ctx.http.clone(),
+            )
+            .await
+        {
+            Ok(_) => HandlerResult::ok(()),
+            Err(why) => HandlerResult::err(why, (ctx.http, msg)),
+        }
+    }
 
-        let result: anyhow::Result<(MessageId, ChannelId)> = async {
-            let guard = EngineGuard::lock(&self.data, msg.author.id).await?;
+    /// Answers a message of the conversation, streaming the response into its channel.
+    /// Returns the id of the last message the response spans.
+    pub async fn reply(
+        &self,
+        scope: ConversationScope,
+        content: String,
+        speaker: Speaker,
+        attachments: Vec<Attachment>,
+        (message, channel): (MessageId, ChannelId),
+        outbound: Arc<dyn Outbound>,
+    ) -> anyhow::Result<MessageId> {
+        let typing = Typing::start(outbound.clone(), channel);
+
+        let result: anyhow::Result<MessageId> = async {
+            let guard = EngineGuard::lock(&self.data, scope).await?;
             let mut engine = guard.engine().await.write().await;
+            let speaker = engine.with_profile(speaker).await;
 
-            let response = engine
-                .user_prompt(
-                    Some((msg.content.clone(), (msg.id, msg.channel_id).into())),
+            let (deltas, events) = mpsc::unbounded_channel();
+            let mut streamer = MessageStreamer::new(channel, outbound.clone());
+
+            let (response, _) = tokio::join!(
+                engine.user_prompt_stream(
+                    Some((content, (message, channel).into())),
+                    Some(speaker),
+                    attachments,
                     Some(ContextType::User),
+                    Some(deltas),
+                ),
+                streamer.consume(events),
+            );
+
+            let response = match response {
+                Ok(response) => response,
+                Err(why) => {
+                    streamer.discard().await;
+                    return Err(why);
+                }
+            };
+            self.data.track([message]);
+
+            let ids = streamer
+                .finish(
+                    &response
+                        .content()
+                        .ok_or(anyhow::anyhow!("message does not have a content"))?,
+                    ButtonStates {
+                        prev_disabled: true,
+                        regen_or_next: misc::RegenOrNext::Regen,
+                    },
                 )
                 .await?;
+            let last_id = *ids.last().ok_or(anyhow::anyhow!("no message ids"))?;
 
-            let messages = misc::chunk_message(
-                &response
-                    .content()
-                    .ok_or(anyhow::anyhow!("message does not have a content"))?,
-                ButtonStates {
-                    prev_disabled: true,
-                    regen_or_next: misc::RegenOrNext::Regen,
-                },
-            )?;
-
-            let ids = misc::send_message_batch(msg.channel_id, &ctx.http, messages).await?;
-            let last_id = ids.last().ok_or(anyhow::anyhow!("no message ids"))?.clone();
+            engine.add_message(response, (last_id, channel, ids));
+            engine.autosave().await;
 
-            engine.add_message(response, (last_id, msg.channel_id, ids));
-
-            Ok((last_id, msg.channel_id))
+            Ok(last_id)
         }
         .await;
 
         typing.stop();
 
-        match result {
-            Ok((msg_id, chan_id)) => {
-                let message = ctx.http.get_message(chan_id, msg_id).await;
-
-                if let Ok(mut message) = message {
-                    let mut recv = self.data.msg_channel.0.subscribe();
-                    tokio::spawn({
-                        async move {
-                            let _ = recv.recv().await;
-
-                            let _ = message
-                                .edit(&ctx.http, EditMessage::new().components(vec![]))
-                                .await;
-
-                            drop(recv);
-                        }
-                    });
-
-                    HandlerResult::ok(())
-                } else {
-                    HandlerResult::err(
-                        anyhow::anyhow!("could not fetch discord message"),
-                        (ctx.http, msg),
-                    )
-                }
-            }
-            Err(why) => HandlerResult::err(why, (ctx.http, msg)),
+        let last_id = result?;
+        Self::expire_buttons(&self.data, outbound, channel, last_id);
+
+        Ok(last_id)
+    }
+
+    /// Whether a guild message should be answered, according to the trigger
+    /// rules of its channel.
+    async fn is_triggered(&self, ctx: &Context, msg: &Message) -> bool {
+        let config = config!(self.data);
+        let triggers = config.discord.triggers_for(msg.channel_id.get());
+
+        if !triggers.enabled.unwrap_or(true) {
+            return false;
         }
+
+        let bot_id = ctx.cache.current_user().id;
+
+        let mentioned =
+            triggers.mention.unwrap_or(true) && msg.mentions.iter().any(|user| user.id == bot_id);
+        let replied = triggers.reply.unwrap_or(true)
+            && msg
+                .referenced_message
+                .as_ref()
+                .is_some_and(|reference| reference.author.id == bot_id);
+        let keyword = triggers
+            .keywords
+            .unwrap_or_default()
+            .iter()
+            .any(|keyword| msg.content.to_lowercase().contains(&keyword.to_lowercase()));
+
+        mentioned || replied || keyword
     }
 }
@@ -4,5 +4,6 @@ mod error;
 mod freewill;
 mod interaction;
 mod message;
+mod reminder;
 
 pub use error::HandlerResult;
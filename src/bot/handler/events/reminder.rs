@@ -0,0 +1,94 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use tokio::task::JoinHandle;
+
+use crate::{
+    bot::{Outbound, handler::framework::InnerData},
+    chat::{
+        context::ConversationScope,
+        engine::{ContextType, EngineGuard},
+        reminder::Reminder,
+    },
+};
+
+use super::super::Handler;
+
+/// How often due reminders are checked for.
+const REMINDER_INTERVAL: Duration = Duration::from_secs(15);
+
+impl Handler {
+    /// Starts delivering reminders once they are due, unless it already is.
+    pub async fn reminder_dispatch(&self, outbound: Arc<dyn Outbound>) {
+        let mut task = self.data.reminder_task.write().await;
+        if task.as_ref().is_some_and(|handle| !handle.is_finished()) {
+            log::trace!("reminders are already running");
+            return;
+        }
+
+        *task = Some(Self::reminder_spawn(self.data.clone(), outbound));
+    }
+
+    pub fn reminder_spawn(data: Arc<InnerData>, outbound: Arc<dyn Outbound>) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                data.env.clock.sleep(REMINDER_INTERVAL).await;
+
+                let due = match data.reminders.due() {
+                    Ok(due) => due,
+                    Err(why) => {
+                        log::error!("failed to get due reminders: {why:?}");
+                        continue;
+                    }
+                };
+
+                // every reminder waits on its own engine, a busy one does not hold up the rest
+                for reminder in due {
+                    tokio::spawn(Self::remind(data.clone(), reminder, outbound.clone()));
+                }
+            }
+        })
+    }
+
+    /// Brings up a reminder in its conversation.
+    pub async fn remind(data: Arc<InnerData>, reminder: Reminder, outbound: Arc<dyn Outbound>) {
+        let scope = reminder.scope;
+        log::info!("reminder {} is due in {scope}", reminder.id);
+
+        let out: anyhow::Result<()> = async {
+            let guard = EngineGuard::lock(&data, scope).await?;
+            let mut engine = guard.engine().await.write().await;
+
+            let channel = match scope {
+                ConversationScope::Direct(_) => engine.channel(),
+                ConversationScope::Channel(channel) | ConversationScope::Thread(channel) => {
+                    Some(channel)
+                }
+            }
+            .ok_or(anyhow!("no channel to remind {scope} in"))?;
+
+            let mut response = engine
+                .user_prompt(None, None, Some(ContextType::Reminder(reminder.clone())))
+                .await?;
+            response.reminder = true;
+
+            Self::send_unprompted(&data, &mut engine, channel, response, outbound).await?;
+
+            Ok(())
+        }
+        .await;
+
+        // it stays pending until it was brought up
+        let out = match out {
+            Ok(()) => data.reminders.delivered(reminder.id).await,
+            Err(why) => {
+                log::error!("failed to deliver reminder {}: {why:?}", reminder.id);
+                data.reminders.failed(reminder.id).await
+            }
+        };
+
+        if let Err(why) = out {
+            log::error!("failed to update reminder {}: {why:?}", reminder.id);
+        }
+    }
+}
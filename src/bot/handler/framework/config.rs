@@ -5,7 +5,7 @@ use crate::bot::handler::{
 };
 
 /// Rewrite keys of the LLM config
-#[poise::command(slash_command, prefix_command)]
+#[poise::command(slash_command, prefix_command, owners_only)]
 pub(super) async fn config(
     ctx: Context<'_>,
     #[description = "Config property"] key: commands::KeyChoice,
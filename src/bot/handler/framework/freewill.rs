@@ -0,0 +1,46 @@
+use super::{Context, Error};
+use crate::bot::handler::{
+    Handler,
+    events::{HandlerResult, commands},
+};
+
+/// Control whether the bot reaches out to you on its own
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("on", "off", "status"),
+    subcommand_required
+)]
+pub(super) async fn freewill(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Lets the bot reach out to you on its own
+#[poise::command(slash_command, prefix_command)]
+async fn on(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::freewill_set(ctx, true).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Stops the bot from reaching out to you on its own
+#[poise::command(slash_command, prefix_command)]
+async fn off(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::freewill_set(ctx, false).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Shows when the bot may reach out to you
+#[poise::command(slash_command, prefix_command)]
+async fn status(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::freewill_status(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
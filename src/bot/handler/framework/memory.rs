@@ -0,0 +1,87 @@
+use super::{Context, Error};
+use crate::bot::handler::{
+    Handler,
+    events::{HandlerResult, commands},
+};
+
+/// Inspect and manage the long term memories the bot has about you
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("list", "search", "edit", "forget", "forget_all"),
+    subcommand_required
+)]
+pub(super) async fn memory(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Lists your long term memories, newest first
+#[poise::command(slash_command, prefix_command)]
+async fn list(
+    ctx: Context<'_>,
+    #[description = "Page to show (starting at 1)"]
+    #[min = 1]
+    page: Option<u32>,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::memory_list(ctx, page).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Searches your long term memories, showing how similar each one is
+#[poise::command(slash_command, prefix_command)]
+async fn search(
+    ctx: Context<'_>,
+    #[description = "What to search for"] query: String,
+    #[description = "Maximum amount of memories to show"]
+    #[min = 1]
+    #[max = 25]
+    limit: Option<u32>,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::memory_search(ctx, query, limit).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Rewrites one of your long term memories
+#[poise::command(slash_command)]
+async fn edit(
+    ctx: Context<'_>,
+    #[description = "Id of the memory (see /memory list)"] id: String,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::memory_edit(ctx, id).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Forgets one of your long term memories
+#[poise::command(slash_command, prefix_command)]
+async fn forget(
+    ctx: Context<'_>,
+    #[description = "Id of the memory (see /memory list)"] id: String,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::memory_forget(ctx, id).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Forgets all of your long term memories
+#[poise::command(slash_command, prefix_command, rename = "forget-all")]
+async fn forget_all(
+    ctx: Context<'_>,
+    #[description = "Confirm that every memory should be forgotten"] confirm: bool,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::memory_forget_all(ctx, confirm).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
@@ -1,6 +1,10 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use serenity::all::{Framework, UserId};
+use serenity::all::{Framework, MessageId, UserId};
 
 use tokio::{
     sync::{
@@ -10,37 +14,171 @@ use tokio::{
     task::JoinHandle,
 };
 
-use crate::{chat::engine::ChatEngine, config::store::ChatBotConfig};
+use crate::{
+    chat::{
+        client::ModelRegistry,
+        context::{
+            Activities, ChannelKinds, ConversationScope, ConversationStore, UserProfile, open_store,
+        },
+        engine::ChatEngine,
+        reminder::Reminders,
+    },
+    config::store::ChatBotConfig,
+    utils::{Environment, macros::config},
+};
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Context<'a> = poise::Context<'a, Data, Error>;
 
 mod clear;
 mod config;
+mod freewill;
+mod memory;
+mod persona;
+mod profile;
 mod reload;
+mod reminder;
 
 pub struct InnerData {
     pub config: RwLock<ChatBotConfig>,
-    pub user_map: RwLock<HashMap<UserId, RwLock<ChatEngine>>>,
+    pub user_map: RwLock<HashMap<ConversationScope, RwLock<ChatEngine>>>,
+    /// When each resident engine was last used, to evict the idle ones
+    pub last_used: Mutex<HashMap<ConversationScope, Instant>>,
+    /// Scopes with a saved conversation, loaded on their first use
+    pub saved_scopes: RwLock<HashSet<ConversationScope>>,
+    /// Messages of the users in conversations loaded so far, edits of any other message
+    /// of a loaded conversation are ignored without locking it
+    pub tracked: Mutex<HashSet<MessageId>>,
+    /// Whether the channels of loaded guild conversations are threads
+    pub channel_kinds: ChannelKinds,
+    /// Clients and storage shared by every engine
+    pub models: ModelRegistry,
     pub freewill_map: RwLock<HashMap<UserId, JoinHandle<()>>>,
     pub context: RwLock<Option<Arc<serenity::client::Context>>>,
     pub msg_channel: (Sender<String>, Receiver<String>),
+    /// Reminders of every conversation, delivered by `reminder_task`
+    pub reminders: Arc<Reminders>,
+    pub reminder_task: RwLock<Option<JoinHandle<()>>>,
+    /// What freewill decides on for every direct conversation, loaded or not
+    pub activities: Arc<Activities>,
+    /// Profiles read so far, so that freewill does not read them on every decision
+    pub profiles: RwLock<HashMap<UserId, UserProfile>>,
+    /// Where every conversation is saved, `None` if they are not
+    pub store: RwLock<Option<Arc<dyn ConversationStore>>>,
+    /// The clock and random numbers engines and freewill run on
+    pub env: Environment,
 }
 pub type Data = Arc<InnerData>;
 
+impl InnerData {
+    pub fn new(config: ChatBotConfig, env: Environment) -> Self {
+        let reminders = Reminders::load(
+            config.context.save_to_disk_folder.as_deref(),
+            env.clock.clone(),
+        );
+        let activities = Activities::load(config.context.save_to_disk_folder.as_deref());
+        let store = open_store(&config.context)
+            .map_err(|why| log::error!("Failed to open conversation store: {why:?}"))
+            .ok()
+            .flatten();
+
+        Self {
+            config: RwLock::new(config),
+            user_map: RwLock::new(HashMap::new()),
+            last_used: Mutex::new(HashMap::new()),
+            saved_scopes: RwLock::new(HashSet::new()),
+            tracked: Mutex::new(HashSet::new()),
+            channel_kinds: ChannelKinds::default(),
+            models: ModelRegistry::new(env.clone()),
+            freewill_map: RwLock::new(HashMap::new()),
+            msg_channel: tokio::sync::broadcast::channel(100),
+            context: RwLock::new(None),
+            reminders: Arc::new(reminders),
+            reminder_task: RwLock::new(None),
+            activities: Arc::new(activities),
+            profiles: RwLock::new(HashMap::new()),
+            store: RwLock::new(store),
+            env,
+        }
+    }
+}
+
+impl InnerData {
+    /// Keeps track of messages of the users, so that editing them edits the prompt.
+    pub fn track(&self, messages: impl IntoIterator<Item = MessageId>) {
+        if let Ok(mut tracked) = self.tracked.lock() {
+            tracked.extend(messages);
+        }
+    }
+
+    /// Forgets what was kept about a conversation that is no longer loaded, its tracked
+    /// `messages` and the kind of its channel.
+    pub fn untrack(&self, scope: ConversationScope, messages: impl IntoIterator<Item = MessageId>) {
+        if let Ok(mut tracked) = self.tracked.lock() {
+            for message in messages {
+                tracked.remove(&message);
+            }
+        }
+
+        match scope {
+            ConversationScope::Channel(channel) | ConversationScope::Thread(channel) => {
+                self.channel_kinds.forget(channel)
+            }
+            ConversationScope::Direct(_) => (),
+        }
+    }
+
+    /// The profile of a user, read once and kept until [InnerData::forget_profile].
+    pub async fn profile(&self, user: UserId) -> UserProfile {
+        if let Some(profile) = self.profiles.read().await.get(&user) {
+            return profile.clone();
+        }
+
+        let profile = match config!(self).context.save_to_disk_folder {
+            Some(folder) => UserProfile::load(&folder, user).await,
+            None => UserProfile::default(),
+        };
+        self.profiles.write().await.insert(user, profile.clone());
+
+        profile
+    }
+
+    /// Drops the kept profile of a user, here and in every loaded conversation, after it
+    /// changed.
+    pub async fn forget_profile(&self, user: UserId) {
+        self.profiles.write().await.remove(&user);
+        for engine in self.user_map.read().await.values() {
+            engine.write().await.forget_profile(user);
+        }
+    }
+
+    pub async fn store(&self) -> Option<Arc<dyn ConversationStore>> {
+        self.store.read().await.clone()
+    }
+
+    pub fn is_tracked(&self, message: MessageId) -> bool {
+        self.tracked
+            .lock()
+            .is_ok_and(|tracked| tracked.contains(&message))
+    }
+}
+
 pub async fn framework(config: ChatBotConfig) -> (impl Framework + 'static, Data) {
-    let data = Arc::new(InnerData {
-        config: RwLock::new(config),
-        user_map: RwLock::new(HashMap::new()),
-        freewill_map: RwLock::new(HashMap::new()),
-        msg_channel: tokio::sync::broadcast::channel(100),
-        context: RwLock::new(None),
-    });
+    let data = Arc::new(InnerData::new(config, Environment::default()));
 
     (
         poise::Framework::builder()
             .options(poise::FrameworkOptions {
-                commands: vec![clear::clear(), reload::reload(), config::config()],
+                commands: vec![
+                    clear::clear(),
+                    reload::reload(),
+                    config::config(),
+                    freewill::freewill(),
+                    memory::memory(),
+                    persona::persona(),
+                    profile::profile(),
+                    reminder::reminder(),
+                ],
                 ..Default::default()
             })
             .setup({
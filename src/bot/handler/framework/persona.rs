@@ -0,0 +1,62 @@
+use super::{Context, Error};
+use crate::bot::handler::{
+    Handler,
+    events::{HandlerResult, commands},
+};
+use crate::utils::macros::config;
+
+/// Inspect and switch the persona the bot talks to you as
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("list", "switch", "show"),
+    subcommand_required
+)]
+pub(super) async fn persona(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+async fn autocomplete_persona(ctx: Context<'_>, partial: &str) -> impl Iterator<Item = String> {
+    let config = config!(ctx.data());
+
+    std::iter::once(commands::DEFAULT_PERSONA.to_string())
+        .chain(config.persona_names())
+        .filter(|name| name.starts_with(partial))
+        .collect::<Vec<_>>()
+        .into_iter()
+}
+
+/// Lists every persona available
+#[poise::command(slash_command, prefix_command)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::persona_list(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Switches to another persona, keeping the conversation going
+#[poise::command(slash_command, prefix_command)]
+async fn switch(
+    ctx: Context<'_>,
+    #[description = "Name of the persona (see /persona list)"]
+    #[autocomplete = "autocomplete_persona"]
+    name: String,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::persona_switch(ctx, name).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Shows the persona you are currently talking to
+#[poise::command(slash_command, prefix_command)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::persona_show(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
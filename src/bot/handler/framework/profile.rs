@@ -0,0 +1,51 @@
+use super::{Context, Error};
+use crate::bot::handler::{
+    Handler,
+    events::{HandlerResult, commands},
+};
+
+/// Tell the bot how to call you, about yourself and your timezone
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("show", "set", "reset"),
+    subcommand_required
+)]
+pub(super) async fn profile(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Shows your profile
+#[poise::command(slash_command, prefix_command)]
+async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::profile_show(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Updates your profile, leaving out whatever you don't want to change
+#[poise::command(slash_command, prefix_command)]
+async fn set(
+    ctx: Context<'_>,
+    #[description = "What the bot should call you"] name: Option<String>,
+    #[description = "A few words about yourself"] about: Option<String>,
+    #[description = "Your timezone, like \"America/New_York\""] timezone: Option<String>,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::profile_set(ctx, name, about, timezone).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Resets your profile back to the defaults
+#[poise::command(slash_command, prefix_command)]
+async fn reset(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::profile_reset(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,56 @@
+use super::{Context, Error};
+use crate::bot::handler::{
+    Handler,
+    events::{HandlerResult, commands},
+};
+
+/// Inspect and manage the reminders of this conversation
+#[poise::command(
+    slash_command,
+    prefix_command,
+    subcommands("list", "add", "cancel"),
+    subcommand_required
+)]
+pub(super) async fn reminder(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Lists the pending reminders, soonest first
+#[poise::command(slash_command, prefix_command)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::reminder_list(ctx).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Has the bot remind you of something later
+#[poise::command(slash_command, prefix_command)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "In a while (30m, 2h, 1d12h) or at a time in your timezone (18:30, 2025-06-01 18:30)"]
+    when: String,
+    #[description = "What to be reminded of"] note: String,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::reminder_add(ctx, when, note).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
+
+/// Cancels one of your reminders
+#[poise::command(slash_command, prefix_command)]
+async fn cancel(
+    ctx: Context<'_>,
+    #[description = "Id of the reminder (see /reminder list)"]
+    #[min = 1]
+    id: u64,
+) -> Result<(), Error> {
+    if let HandlerResult::Err(why) = commands::reminder_cancel(ctx, id).await {
+        Handler::on_error(why).await;
+    }
+
+    Ok(())
+}
@@ -1,4 +1,5 @@
 use std::{
+    path::Path,
     process::exit,
     sync::{Arc, mpsc},
     time::Duration,
@@ -10,10 +11,20 @@ use serenity::{
     all::{Context, EventHandler, Interaction, Message, MessageUpdateEvent, Ready, UserId},
     async_trait,
 };
-use tokio::sync::RwLock;
 use tokio::task::JoinHandle;
 
-use crate::{chat::engine::ChatEngine, utils::macros::config};
+use crate::{
+    chat::{
+        context::{Activity, ConversationScope, ConversationStore, autosave_interval},
+        engine,
+    },
+    utils::macros::config,
+};
+
+/// How often idle engines are looked for.
+const EVICTION_INTERVAL: Duration = Duration::from_secs(60);
+/// How often the config file is checked for changes.
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(2);
 
 mod buttons;
 mod events;
@@ -26,6 +37,26 @@ impl Handler {
     pub fn new(data: Data) -> (Arc<Self>, JoinHandle<()>) {
         let handler = Arc::new(Self { data });
 
+        tokio::spawn({
+            let handler = handler.clone();
+            async move { handler.autosave_loop().await }
+        });
+
+        tokio::spawn({
+            let handler = handler.clone();
+            async move {
+                loop {
+                    tokio::time::sleep(EVICTION_INTERVAL).await;
+                    engine::evict_idle(&handler.data).await;
+                }
+            }
+        });
+
+        tokio::spawn({
+            let handler = handler.clone();
+            async move { handler.watch_config().await }
+        });
+
         let handle = tokio::spawn({
             let handler = handler.clone();
             let shutdown_rx = setup_ctrlc_handler();
@@ -123,32 +154,24 @@ impl EventHandler for Handler {
         //     ));
 
         // list contents of directory of saves
-        let config = config!(self.data);
-
         let result: anyhow::Result<()> = async {
-            if let Some(path) = &config.context.save_to_disk_folder {
-                // walk the directory for files matching the pattern "context-*.bin" and extract the
-                // id from the filename
-                let files = std::fs::read_dir(path)?;
-                let regex = regex::Regex::new(r"^context-(\d+)\.bin$")?;
-                for file in files {
-                    let file = file?;
-                    let filename = file.file_name();
-                    if let Some(captures) = regex.captures(&filename.to_string_lossy()) {
-                        let id = captures
-                            .get(1)
-                            .ok_or(anyhow::anyhow!("no id found"))?
-                            .as_str()
-                            .parse::<u64>()?;
-                        log::info!("found saved context with id {id}");
-
-                        let mut user_map = self.data.user_map.write().await;
-                        let user = UserId::new(id);
-                        let engine = ChatEngine::new(config.clone(), user.clone()).await?;
-
-                        user_map.insert(user, RwLock::new(engine));
+            // engines are only loaded once they are used
+            if let Some(store) = self.data.store().await {
+                let scopes = store.scopes().await?;
+                log::info!("found {} saved contexts", scopes.len());
+
+                // freewill picks up where it left off, going by the recorded activity
+                for scope in &scopes {
+                    if let ConversationScope::Direct(user) = *scope {
+                        if self.data.activities.get(user).is_none() {
+                            self.record_saved_activity(store.as_ref(), user).await;
+                        }
+
+                        self.freewill_dispatch(user, ctx.http.clone()).await;
                     }
                 }
+
+                self.data.saved_scopes.write().await.extend(scopes);
             }
 
             Ok(())
@@ -159,6 +182,8 @@ impl EventHandler for Handler {
             log::error!("failed to load saved contexts: {why:?}");
         }
 
+        self.reminder_dispatch(ctx.http.clone()).await;
+
         self.data.context.write().await.replace(Arc::new(ctx));
     }
 
@@ -188,6 +213,114 @@ impl EventHandler for Handler {
 }
 
 impl Handler {
+    /// Records the activity of a conversation saved before activity was, reading it from
+    /// the store rather than loading its engine.
+    async fn record_saved_activity(&self, store: &dyn ConversationStore, user: UserId) {
+        let scope = ConversationScope::Direct(user);
+
+        let result: anyhow::Result<()> = async {
+            if let Some(saved) = store.load(scope).await? {
+                let activity =
+                    Activity::of(saved.messages.values().map(|messages| messages.selected()));
+                self.data.activities.record(user, activity).await?;
+            }
+
+            Ok(())
+        }
+        .await;
+
+        if let Err(why) = result {
+            log::error!("failed to record the activity of {scope}: {why:?}");
+        }
+    }
+
+    /// Periodically saves every loaded context, so that a crash loses as little as possible.
+    async fn autosave_loop(&self) {
+        loop {
+            let interval = autosave_interval(&config!(self.data).context);
+
+            if interval.is_zero() {
+                log::info!("periodic autosave disabled");
+                return;
+            }
+
+            tokio::time::sleep(interval).await;
+
+            // the map is only held for one engine at a time, so that loading another one
+            // does not wait for every save
+            let scopes = self
+                .data
+                .user_map
+                .read()
+                .await
+                .keys()
+                .copied()
+                .collect::<Vec<_>>();
+
+            for scope in scopes {
+                let user_map = self.data.user_map.read().await;
+                // evicted in the meantime, and saved while at it
+                let Some(engine) = user_map.get(&scope) else {
+                    continue;
+                };
+                // busy with a turn, which saves it once it is over
+                let Ok(engine) = engine.try_read() else {
+                    log::trace!("{scope} is busy, not autosaving it");
+                    continue;
+                };
+
+                if let Err(why) = engine.save().await {
+                    log::error!("failed to autosave context for {scope}: {why:?}");
+                }
+            }
+        }
+    }
+
+    /// Reloads the config whenever its file changes, applying it to every live engine.
+    async fn watch_config(&self) {
+        let path = config!(self.data).path;
+        let modified = |path: &Path| {
+            std::fs::metadata(path)
+                .and_then(|meta| meta.modified())
+                .ok()
+        };
+
+        let mut last_modified = modified(&path);
+        loop {
+            tokio::time::sleep(CONFIG_WATCH_INTERVAL).await;
+
+            let current = modified(&path);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+
+            let (changes, token_changed) = {
+                let mut config = self.data.config.write().await;
+                let token = config.discord.token.clone();
+                let changes = config.update();
+
+                (changes, config.discord.token != token)
+            };
+
+            match changes {
+                Ok(changes) if changes.is_empty() => {}
+                Ok(changes) => {
+                    log::info!("config file changed ({changes}), applying it");
+
+                    if token_changed {
+                        log::warn!("discord.token changed, restart the bot for it to take effect");
+                    }
+
+                    engine::apply_config(&self.data, changes).await;
+                }
+                Err(why) => {
+                    log::error!("rejected the config file change, keeping the old config: {why:?}")
+                }
+            }
+        }
+    }
+
     async fn shutdown(&self) -> anyhow::Result<()> {
         log::info!("Shutdown signal received, waiting for locks and shutting down...");
         let user_map = self.data.user_map.write().await;
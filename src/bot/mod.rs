@@ -5,8 +5,10 @@ use tokio::task::JoinHandle;
 
 use crate::config::store::ChatBotConfig;
 pub use handler::Data;
+pub use outbound::{Outbound, Typing};
 
 pub mod handler;
+pub mod outbound;
 
 pub struct ChatBot {
     client: Client,
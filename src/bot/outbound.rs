@@ -0,0 +1,121 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use serenity::all::{ChannelId, CreateMessage, EditMessage, Http, MessageId};
+use tokio::task::JoinHandle;
+
+/// Discord shows the bot typing for 10 seconds, so it is broadcast again a little sooner.
+const TYPING_INTERVAL: Duration = Duration::from_secs(7);
+
+/// What the bot does on Discord on its own, outside of answering an interaction, so that
+/// it can be faked.
+#[async_trait]
+pub trait Outbound: Send + Sync {
+    async fn send_message(
+        &self,
+        channel: ChannelId,
+        message: CreateMessage,
+    ) -> anyhow::Result<MessageId>;
+
+    async fn edit_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+        edit: EditMessage,
+    ) -> anyhow::Result<()>;
+
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> anyhow::Result<()>;
+
+    /// Shows the bot typing in a channel for a few seconds.
+    async fn broadcast_typing(&self, channel: ChannelId) -> anyhow::Result<()>;
+}
+
+#[async_trait]
+impl Outbound for Http {
+    async fn send_message(
+        &self,
+        channel: ChannelId,
+        message: CreateMessage,
+    ) -> anyhow::Result<MessageId> {
+        Ok(channel.send_message(self, message).await?.id)
+    }
+
+    async fn edit_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+        edit: EditMessage,
+    ) -> anyhow::Result<()> {
+        channel.edit_message(self, message, edit).await?;
+
+        Ok(())
+    }
+
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> anyhow::Result<()> {
+        channel.delete_message(self, message).await?;
+
+        Ok(())
+    }
+
+    async fn broadcast_typing(&self, channel: ChannelId) -> anyhow::Result<()> {
+        channel.broadcast_typing(self).await?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<T: Outbound + ?Sized> Outbound for Arc<T> {
+    async fn send_message(
+        &self,
+        channel: ChannelId,
+        message: CreateMessage,
+    ) -> anyhow::Result<MessageId> {
+        (**self).send_message(channel, message).await
+    }
+
+    async fn edit_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+        edit: EditMessage,
+    ) -> anyhow::Result<()> {
+        (**self).edit_message(channel, message, edit).await
+    }
+
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> anyhow::Result<()> {
+        (**self).delete_message(channel, message).await
+    }
+
+    async fn broadcast_typing(&self, channel: ChannelId) -> anyhow::Result<()> {
+        (**self).broadcast_typing(channel).await
+    }
+}
+
+/// Shows the bot typing in a channel until stopped or dropped, like serenity's own typing
+/// but through any [Outbound].
+pub struct Typing(JoinHandle<()>);
+
+impl Typing {
+    pub fn start(outbound: Arc<dyn Outbound>, channel: ChannelId) -> Self {
+        Self(tokio::spawn(async move {
+            loop {
+                if let Err(why) = outbound.broadcast_typing(channel).await {
+                    log::warn!("failed to show typing in {channel}: {why:?}");
+                    return;
+                }
+
+                tokio::time::sleep(TYPING_INTERVAL).await;
+            }
+        }))
+    }
+
+    /// Same as dropping it, spelled out where typing should stop.
+    pub fn stop(self) {}
+}
+
+impl Drop for Typing {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
@@ -0,0 +1,262 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+
+use crate::chat::archive::storage::Memory;
+
+use super::MemoryBackend;
+
+#[derive(Serialize, Deserialize, Clone)]
+struct LocalPoint {
+    memory: Memory,
+    vector: Vec<f32>,
+}
+
+/// Embedded, dependency-free memory backend.
+///
+/// Every namespace lives in a `memories-{namespace}.bin` file under the given
+/// folder and are searched by brute-force cosine similarity. Without a folder
+/// memories are only kept in memory for the lifetime of the process.
+pub struct LocalBackend {
+    folder: Option<PathBuf>,
+    vector_size: u64,
+    collections: RwLock<HashMap<String, Vec<LocalPoint>>>,
+}
+
+impl LocalBackend {
+    pub fn new(folder: Option<PathBuf>, vector_size: u64) -> anyhow::Result<Self> {
+        match &folder {
+            Some(folder) => std::fs::create_dir_all(folder)?,
+            None => log::warn!("no save folder configured, local memories will not be persisted"),
+        }
+
+        Ok(Self {
+            folder,
+            vector_size,
+            collections: RwLock::new(HashMap::new()),
+        })
+    }
+
+    pub fn vector_size(&self) -> u64 {
+        self.vector_size
+    }
+
+    fn path(&self, namespace: &str) -> Option<PathBuf> {
+        self.folder
+            .as_ref()
+            .map(|folder| folder.join(format!("memories-{}.bin", namespace)))
+    }
+
+    /// Loads the user's collection from disk if it isn't cached yet.
+    async fn load(&self, namespace: &str) -> anyhow::Result<()> {
+        if self.collections.read().await.contains_key(namespace) {
+            return Ok(());
+        }
+
+        let points: Vec<LocalPoint> = match self.path(namespace) {
+            Some(path) if tokio::fs::try_exists(&path).await? => {
+                ciborium::from_reader(tokio::fs::read(path).await?.as_slice())?
+            }
+            _ => vec![],
+        };
+
+        self.collections
+            .write()
+            .await
+            .entry(namespace.to_string())
+            .or_insert(points);
+
+        Ok(())
+    }
+
+    /// Writes the user's collection to a temporary file and renames it over
+    /// the old one, so a crash mid-write never leaves a truncated file behind.
+    async fn persist(&self, namespace: &str, points: &[LocalPoint]) -> anyhow::Result<()> {
+        if let Some(path) = self.path(namespace) {
+            let mut bytes = vec![];
+            ciborium::into_writer(points, &mut bytes)?;
+
+            let tmp = path.with_extension("bin.tmp");
+            tokio::fs::write(&tmp, bytes).await?;
+            tokio::fs::rename(tmp, path).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MemoryBackend for LocalBackend {
+    async fn health_check(&self, namespace: &str) -> anyhow::Result<()> {
+        self.load(namespace).await?;
+
+        let collections = self.collections.read().await;
+        let mismatch = collections
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .find(|point| point.vector.len() as u64 != self.vector_size);
+
+        match mismatch {
+            Some(point) => Err(anyhow::anyhow!(
+                "vector size mismatch, expected {} but got {}",
+                self.vector_size,
+                point.vector.len()
+            )),
+            None => Ok(()),
+        }
+    }
+
+    async fn store(
+        &self,
+        memory: Memory,
+        embedding: Vec<f32>,
+        namespace: &str,
+    ) -> anyhow::Result<()> {
+        self.load(namespace).await?;
+
+        let mut collections = self.collections.write().await;
+        let points = collections.entry(namespace.to_string()).or_default();
+
+        let point = LocalPoint {
+            memory,
+            vector: embedding,
+        };
+        match points.iter_mut().find(|p| p.memory.id == point.memory.id) {
+            Some(existing) => *existing = point,
+            None => points.push(point),
+        }
+
+        self.persist(namespace, points).await
+    }
+
+    async fn search(
+        &self,
+        embedding: Vec<f32>,
+        namespace: &str,
+        limit: u64,
+        threshold: f32,
+    ) -> anyhow::Result<Vec<(Memory, f32)>> {
+        self.load(namespace).await?;
+
+        let collections = self.collections.read().await;
+
+        let mut scored = collections
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .map(|point| (cosine_similarity(&embedding, &point.vector), point))
+            .filter(|(score, _)| *score > threshold)
+            .collect::<Vec<_>>();
+
+        scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+        Ok(scored
+            .into_iter()
+            .take(limit as usize)
+            .enumerate()
+            .map(|(i, (score, point))| {
+                log::debug!("memory #{i}:\n{}\nscore: {}", point.memory.content, score);
+
+                (point.memory.clone(), score)
+            })
+            .collect())
+    }
+
+    async fn find_recent(
+        &self,
+        namespace: &str,
+        limit: u32,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Memory>> {
+        self.load(namespace).await?;
+
+        let collections = self.collections.read().await;
+
+        let mut recent = collections
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .filter(|point| point.memory.date >= since)
+            .map(|point| point.memory.clone())
+            .collect::<Vec<_>>();
+        recent.sort_by(|a, b| b.date.cmp(&a.date));
+        recent.truncate(limit as usize);
+
+        Ok(recent)
+    }
+
+    async fn get(&self, id: u64, namespace: &str) -> anyhow::Result<Option<Memory>> {
+        self.load(namespace).await?;
+
+        let collections = self.collections.read().await;
+
+        Ok(collections
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .find(|point| point.memory.id == id)
+            .map(|point| point.memory.clone()))
+    }
+
+    async fn scroll_all(&self, namespace: &str) -> anyhow::Result<Vec<Memory>> {
+        self.load(namespace).await?;
+
+        let collections = self.collections.read().await;
+
+        Ok(collections
+            .get(namespace)
+            .into_iter()
+            .flatten()
+            .map(|point| point.memory.clone())
+            .collect())
+    }
+
+    async fn delete(&self, ids: Vec<u64>, namespace: &str) -> anyhow::Result<()> {
+        self.load(namespace).await?;
+
+        let mut collections = self.collections.write().await;
+        let points = collections.entry(namespace.to_string()).or_default();
+
+        points.retain(|point| !ids.contains(&point.memory.id));
+
+        self.persist(namespace, points).await
+    }
+
+    async fn delete_all(&self, namespace: &str) -> anyhow::Result<()> {
+        self.collections
+            .write()
+            .await
+            .insert(namespace.to_string(), vec![]);
+
+        if let Some(path) = self.path(namespace) {
+            if tokio::fs::try_exists(&path).await? {
+                tokio::fs::remove_file(path).await?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let (dot, norm_a, norm_b) = a
+        .iter()
+        .zip(b)
+        .fold((0.0, 0.0, 0.0), |(dot, norm_a, norm_b), (x, y)| {
+            (dot + x * y, norm_a + x * x, norm_b + y * y)
+        });
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a.sqrt() * norm_b.sqrt())
+    }
+}
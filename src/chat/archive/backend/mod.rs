@@ -0,0 +1,57 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+use super::storage::Memory;
+
+mod local;
+mod qdrant;
+
+pub use local::LocalBackend;
+pub use qdrant::QdrantBackend;
+
+/// A vector store capable of holding the long term memories of every conversation.
+///
+/// Memories are grouped into namespaces (one per conversation scope), and
+/// implementations are expected to create whatever collection/file they need
+/// for a namespace on first use.
+#[async_trait]
+pub trait MemoryBackend: Send + Sync {
+    /// Checks that the backend is reachable and that the user's collection
+    /// matches the configured vector size.
+    async fn health_check(&self, namespace: &str) -> anyhow::Result<()>;
+
+    async fn store(
+        &self,
+        memory: Memory,
+        embedding: Vec<f32>,
+        namespace: &str,
+    ) -> anyhow::Result<()>;
+
+    /// Returns up to `limit` memories scoring above `threshold` together with
+    /// their score, best first.
+    async fn search(
+        &self,
+        embedding: Vec<f32>,
+        namespace: &str,
+        limit: u64,
+        threshold: f32,
+    ) -> anyhow::Result<Vec<(Memory, f32)>>;
+
+    /// Returns up to `limit` of the newest memories stored at or after `since`, newest first.
+    async fn find_recent(
+        &self,
+        namespace: &str,
+        limit: u32,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Memory>>;
+
+    /// Returns the memory with the given id, if there is one.
+    async fn get(&self, id: u64, namespace: &str) -> anyhow::Result<Option<Memory>>;
+
+    /// Returns every memory stored for the user, in no particular order.
+    async fn scroll_all(&self, namespace: &str) -> anyhow::Result<Vec<Memory>>;
+
+    async fn delete(&self, ids: Vec<u64>, namespace: &str) -> anyhow::Result<()>;
+
+    async fn delete_all(&self, namespace: &str) -> anyhow::Result<()>;
+}
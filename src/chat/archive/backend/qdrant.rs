@@ -0,0 +1,297 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use qdrant_client::{
+    Qdrant,
+    qdrant::{
+        Condition, CreateCollectionBuilder, CreateFieldIndexCollectionBuilder, DeletePointsBuilder,
+        Direction, Distance, FieldCondition, FieldType, Filter, GetPointsBuilder, OrderByBuilder,
+        PointId, PointStruct, PointsIdsList, Range, RetrievedPoint, ScrollPointsBuilder,
+        SearchPointsBuilder, UpsertPointsBuilder, VectorParamsBuilder, condition::ConditionOneOf,
+        point_id::PointIdOptions, vectors_config::Config,
+    },
+};
+
+use crate::{chat::archive::storage::Memory, config::structure::LLMEmbeddingConfig};
+
+use super::MemoryBackend;
+
+pub struct QdrantBackend {
+    client: Qdrant,
+    vector_size: u64,
+}
+
+impl QdrantBackend {
+    pub fn new(config: &LLMEmbeddingConfig, vector_size: u64) -> anyhow::Result<Self> {
+        let client = Qdrant::from_url(&format!(
+            "http{}://{}:{}",
+            match config.qdrant_https.unwrap_or(false) {
+                true => "s",
+                false => "",
+            },
+            config.qdrant_host,
+            config.qdrant_port.unwrap_or(6334)
+        ))
+        .skip_compatibility_check()
+        .build()?;
+
+        Ok(Self {
+            client,
+            vector_size,
+        })
+    }
+
+    async fn try_create_collection(&self, namespace: &str) -> anyhow::Result<String> {
+        let collection_name = format!("chatbot_{}", namespace);
+
+        Ok(
+            match self.client.collection_exists(&collection_name).await? {
+                true => collection_name,
+                false => {
+                    self.client
+                        .create_collection(
+                            CreateCollectionBuilder::new(&collection_name).vectors_config(
+                                VectorParamsBuilder::new(self.vector_size, Distance::Cosine),
+                            ),
+                        )
+                        .await?;
+                    collection_name
+                }
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl MemoryBackend for QdrantBackend {
+    async fn health_check(&self, namespace: &str) -> anyhow::Result<()> {
+        self.client.health_check().await?;
+
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        // find_recent orders by date, which takes an index that collections created before
+        // it do not have, creating one that exists already is a no-op
+        self.client
+            .create_field_index(CreateFieldIndexCollectionBuilder::new(
+                &collection_name,
+                "date",
+                FieldType::Integer,
+            ))
+            .await?;
+
+        let collection_info = self.client.collection_info(collection_name).await?;
+
+        let vector_size: u64 = async {
+            if let Config::Params(params) = collection_info
+                .result?
+                .config?
+                .params?
+                .vectors_config?
+                .config?
+            {
+                Some(params.size)
+            } else {
+                None
+            }
+        }
+        .await
+        .ok_or(anyhow::anyhow!("failed to get vector size"))?;
+
+        if vector_size != self.vector_size {
+            Err(anyhow::anyhow!(
+                "vector size mismatch, expected {} but got {}",
+                self.vector_size,
+                vector_size
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn store(
+        &self,
+        memory: Memory,
+        embedding: Vec<f32>,
+        namespace: &str,
+    ) -> anyhow::Result<()> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        let points = vec![PointStruct::new(memory.id, embedding, memory.into())];
+        self.client
+            .upsert_points(UpsertPointsBuilder::new(collection_name, points))
+            .await?;
+
+        Ok(())
+    }
+
+    async fn search(
+        &self,
+        embedding: Vec<f32>,
+        namespace: &str,
+        limit: u64,
+        threshold: f32,
+    ) -> anyhow::Result<Vec<(Memory, f32)>> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        let search_result = self
+            .client
+            .search_points(
+                SearchPointsBuilder::new(collection_name, embedding, limit)
+                    // .filter(Filter::all([Condition::matches("bar", 12)]))
+                    .with_payload(true), // .params(SearchParamsBuilder::default().exact(true)),
+            )
+            .await?;
+
+        Ok(search_result
+            .result
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, point)| {
+                let id = if let PointIdOptions::Num(id) = point.id?.point_id_options? {
+                    id
+                } else {
+                    return None;
+                };
+
+                if point.score > threshold {
+                    log::debug!(
+                        "payload #{i}:\n{}\nscore: {}",
+                        serde_json::to_string_pretty(&point.payload).ok()?,
+                        point.score
+                    );
+
+                    Some((Memory::try_from(id, point.payload)?, point.score))
+                } else {
+                    None
+                }
+            })
+            .collect())
+    }
+
+    async fn find_recent(
+        &self,
+        namespace: &str,
+        limit: u32,
+        since: DateTime<Utc>,
+    ) -> anyhow::Result<Vec<Memory>> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        let lower_bound_ts = since.timestamp_millis();
+
+        // Build a filter: only return points whose "date" field is >= lower_bound_ts.
+        let filter = Filter {
+            must: vec![Condition {
+                condition_one_of: Some(ConditionOneOf::Field(FieldCondition {
+                    key: "date".to_string(),
+                    range: Some(Range {
+                        gt: None,
+                        gte: Some(lower_bound_ts as f64),
+                        lt: None,
+                        lte: None,
+                    }),
+                    ..Default::default()
+                })),
+            }],
+            ..Default::default()
+        };
+
+        // Use scroll_points to get points matching the filter.
+        let scroll_result = self
+            .client
+            .scroll(
+                ScrollPointsBuilder::new(collection_name)
+                    .with_payload(true)
+                    .filter(filter)
+                    .order_by(OrderByBuilder::new("date").direction(Direction::Desc as i32))
+                    .limit(limit),
+            )
+            .await?;
+
+        Ok(scroll_result
+            .result
+            .into_iter()
+            .filter_map(retrieved_to_memory)
+            .collect())
+    }
+
+    async fn get(&self, id: u64, namespace: &str) -> anyhow::Result<Option<Memory>> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        let result = self
+            .client
+            .get_points(
+                GetPointsBuilder::new(collection_name, vec![PointId::from(id)]).with_payload(true),
+            )
+            .await?;
+
+        Ok(result.result.into_iter().find_map(retrieved_to_memory))
+    }
+
+    async fn scroll_all(&self, namespace: &str) -> anyhow::Result<Vec<Memory>> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        let mut memories = Vec::new();
+        let mut offset: Option<PointId> = None;
+
+        loop {
+            let mut builder = ScrollPointsBuilder::new(&collection_name)
+                .with_payload(true)
+                .limit(256);
+            if let Some(offset) = offset.take() {
+                builder = builder.offset(offset);
+            }
+
+            let scroll_result = self.client.scroll(builder).await?;
+
+            memories.extend(
+                scroll_result
+                    .result
+                    .into_iter()
+                    .filter_map(retrieved_to_memory),
+            );
+
+            match scroll_result.next_page_offset {
+                Some(next) => offset = Some(next),
+                None => break,
+            }
+        }
+
+        Ok(memories)
+    }
+
+    async fn delete(&self, ids: Vec<u64>, namespace: &str) -> anyhow::Result<()> {
+        let collection_name = self.try_create_collection(namespace).await?;
+
+        self.client
+            .delete_points(
+                DeletePointsBuilder::new(collection_name)
+                    .points(PointsIdsList {
+                        ids: ids.into_iter().map(PointId::from).collect(),
+                    })
+                    .wait(true),
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn delete_all(&self, namespace: &str) -> anyhow::Result<()> {
+        let collection_name = format!("chatbot_{}", namespace);
+
+        // the collection is lazily recreated on the next access
+        if self.client.collection_exists(&collection_name).await? {
+            self.client.delete_collection(collection_name).await?;
+        }
+
+        Ok(())
+    }
+}
+
+fn retrieved_to_memory(point: RetrievedPoint) -> Option<Memory> {
+    let id = if let PointIdOptions::Num(id) = point.id?.point_id_options? {
+        id
+    } else {
+        return None;
+    };
+
+    Memory::try_from(id, point.payload)
+}
@@ -1,2 +1,3 @@
 /// memory archival module
+pub mod backend;
 pub mod storage;
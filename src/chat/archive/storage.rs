@@ -1,18 +1,16 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, path::Path, sync::Arc};
 
 use chrono::{DateTime, TimeZone, Utc};
-use qdrant_client::{
-    Payload, Qdrant,
-    qdrant::{
-        Condition, CreateCollectionBuilder, Distance, FieldCondition, Filter, PointStruct, Range,
-        ScrollPointsBuilder, SearchPointsBuilder, UpsertPointsBuilder, Value, VectorParamsBuilder,
-        condition::ConditionOneOf, point_id::PointIdOptions, vectors_config::Config,
-    },
-};
+use qdrant_client::{Payload, qdrant::Value};
 use serde::{Deserialize, Serialize};
 use serenity::all::UserId;
 
-use crate::config::structure::LLMConfig;
+use crate::{
+    chat::context::ConversationScope,
+    config::structure::{LLMConfig, MemoryBackendKind},
+};
+
+use super::backend::{LocalBackend, MemoryBackend, QdrantBackend};
 
 #[derive(Debug, Clone, Serialize, Deserialize, Hash, Eq, PartialEq)]
 pub struct Memory {
@@ -20,24 +18,34 @@ pub struct Memory {
     pub content: String,
     // pub topic: String,
     pub date: DateTime<Utc>,
+    /// The user this memory is about, if it can be attributed to a single one.
+    #[serde(default)]
+    pub user_id: Option<UserId>,
 }
 impl Memory {
-    pub fn new(content: String) -> Self {
+    pub fn new(content: String, user_id: Option<UserId>) -> Self {
         Self {
             id: rand::random(),
             content,
             date: Utc::now(),
+            user_id,
         }
     }
     pub fn into(self) -> Payload {
-        Payload::from(HashMap::from([
+        let mut payload = HashMap::from([
             ("content".to_string(), Value::from(self.content)),
             // ("topic".to_string(), Value::from(self.topic)),
             (
                 "date".to_string(),
                 Value::from(self.date.timestamp_millis()),
             ),
-        ]))
+        ]);
+
+        if let Some(user_id) = self.user_id {
+            payload.insert("user_id".to_string(), Value::from(user_id.get() as i64));
+        }
+
+        Payload::from(payload)
     }
     pub fn try_from(id: u64, payload: HashMap<String, Value>) -> Option<Self> {
         Some(Self {
@@ -47,37 +55,89 @@ impl Memory {
             date: Utc
                 .timestamp_millis_opt(payload.get("date")?.as_integer()?)
                 .single()?,
+            user_id: payload
+                .get("user_id")
+                .and_then(|user_id| user_id.as_integer())
+                .filter(|user_id| *user_id > 0)
+                .map(|user_id| UserId::new(user_id as u64)),
         })
     }
 }
 
+/// Where the memories of a conversation are kept, one per scope and persona.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MemoryNamespace(String);
+
+impl MemoryNamespace {
+    /// The default persona keeps the bare scope key, so that memories stored before
+    /// personas existed are still found. Persona names that had to be sanitized get a
+    /// hash of the original name, so that `Night Owl` and `night_owl` stay apart.
+    pub fn new(scope: ConversationScope, persona: Option<&str>) -> Self {
+        match persona {
+            Some(original) => {
+                let mut persona = original
+                    .chars()
+                    .map(|c| match c.is_ascii_alphanumeric() || c == '-' {
+                        true => c.to_ascii_lowercase(),
+                        false => '_',
+                    })
+                    .collect::<String>();
+                if persona != original {
+                    persona = format!("{persona}_{:016x}", fnv1a(original));
+                }
+
+                Self(format!("{}_persona_{persona}", scope.key()))
+            }
+            None => Self(scope.key()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+/// A hash that stays the same across builds, unlike the one of the standard library.
+fn fnv1a(text: &str) -> u64 {
+    text.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 pub struct MemorySettings {
     pub vector_size: u64,
     pub similarity_threshold: f32,
 }
 
 pub struct MemoryStorage {
-    client: Qdrant,
+    backend: Arc<dyn MemoryBackend>,
     settings: MemorySettings,
 }
 
 impl MemoryStorage {
-    pub fn new(config: &LLMConfig, vector_size: u64) -> Self {
-        let client = Qdrant::from_url(&format!(
-            "http{}://{}:{}",
-            match config.embedding.qdrant_https.unwrap_or(false) {
-                true => "s",
-                false => "",
-            },
-            config.embedding.qdrant_host,
-            config.embedding.qdrant_port.unwrap_or(6334)
-        ))
-        .skip_compatibility_check()
-        .build()
-        .unwrap();
+    pub fn new(
+        config: &LLMConfig,
+        save_folder: Option<&Path>,
+        vector_size: u64,
+    ) -> anyhow::Result<Self> {
+        let backend: Arc<dyn MemoryBackend> = match config.embedding.backend.unwrap_or_default() {
+            MemoryBackendKind::Qdrant => {
+                Arc::new(QdrantBackend::new(&config.embedding, vector_size)?)
+            }
+            MemoryBackendKind::Local => Arc::new(Self::local_backend(save_folder, vector_size)?),
+        };
 
+        Ok(Self::with_backend(config, backend, vector_size))
+    }
+
+    /// Storage on a backend that was opened before, for storages that have to share it.
+    pub fn with_backend(
+        config: &LLMConfig,
+        backend: Arc<dyn MemoryBackend>,
+        vector_size: u64,
+    ) -> Self {
         MemoryStorage {
-            client,
+            backend,
             settings: MemorySettings {
                 vector_size,
                 similarity_threshold: config.similarity_threshold.unwrap_or(0.5) as f32,
@@ -85,86 +145,55 @@ impl MemoryStorage {
         }
     }
 
-    pub async fn health_check(&self, user_id: UserId) -> anyhow::Result<()> {
-        self.client.health_check().await?;
-
-        let collection_name = self.try_create_collection(user_id).await?;
-
-        let collection_info = self.client.collection_info(collection_name).await?;
-
-        let vector_size: u64 = async {
-            if let Config::Params(params) = collection_info
-                .result?
-                .config?
-                .params?
-                .vectors_config?
-                .config?
-            {
-                Some(params.size)
-            } else {
-                None
-            }
-        }
-        .await
-        .ok_or(anyhow::anyhow!("failed to get vector size"))?;
-
-        if vector_size != self.settings.vector_size {
-            Err(anyhow::anyhow!(
-                "vector size mismatch, expected {} but got {}",
-                self.settings.vector_size,
-                vector_size
-            ))
-        } else {
-            Ok(())
-        }
+    /// The local backend keeping its files in the `memories` folder of `save_folder`.
+    pub fn local_backend(
+        save_folder: Option<&Path>,
+        vector_size: u64,
+    ) -> anyhow::Result<LocalBackend> {
+        LocalBackend::new(
+            save_folder.map(|folder| folder.join("memories")),
+            vector_size,
+        )
     }
 
-    async fn try_create_collection(&self, user_id: UserId) -> anyhow::Result<String> {
-        let collection_name = format!("chatbot_{}", user_id);
-
-        Ok(
-            match self.client.collection_exists(&collection_name).await? {
-                true => collection_name,
-                false => {
-                    self.client
-                        .create_collection(
-                            CreateCollectionBuilder::new(&collection_name).vectors_config(
-                                VectorParamsBuilder::new(
-                                    self.settings.vector_size,
-                                    Distance::Cosine,
-                                ),
-                            ),
-                        )
-                        .await?;
-                    collection_name
-                }
-            },
-        )
+    pub async fn health_check(&self, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        self.backend.health_check(namespace.as_str()).await
     }
 
     pub async fn store(
         &self,
         memory: Memory,
         embedding: Vec<f32>,
-        user_id: UserId,
+        namespace: &MemoryNamespace,
     ) -> anyhow::Result<()> {
-        let collection_name = self.try_create_collection(user_id).await?;
-
-        let points = vec![PointStruct::new(memory.id, embedding, memory.into())];
-        self.client
-            .upsert_points(UpsertPointsBuilder::new(collection_name, points))
-            .await?;
-
-        Ok(())
+        self.backend
+            .store(memory, embedding, namespace.as_str())
+            .await
     }
 
     pub async fn search(
         &self,
         embedding: Vec<impl Into<f32>>,
-        user_id: UserId,
+        namespace: &MemoryNamespace,
         limit: u64,
         threshold: Option<f32>,
     ) -> anyhow::Result<Vec<Memory>> {
+        Ok(self
+            .search_scored(embedding, namespace, limit, threshold)
+            .await?
+            .into_iter()
+            .map(|(memory, _)| memory)
+            .collect())
+    }
+
+    /// Same as [MemoryStorage::search] but keeps the similarity score of each memory.
+    pub async fn search_scored(
+        &self,
+        embedding: Vec<impl Into<f32>>,
+        namespace: &MemoryNamespace,
+        limit: u64,
+        threshold: Option<f32>,
+    ) -> anyhow::Result<Vec<(Memory, f32)>> {
         let threshold = threshold.unwrap_or(self.settings.similarity_threshold);
 
         let embedding = embedding
@@ -172,95 +201,75 @@ impl MemoryStorage {
             .map(|x| x.into())
             .collect::<Vec<f32>>();
 
-        let collection_name = self.try_create_collection(user_id).await?;
-
-        let search_result = self
-            .client
-            .search_points(
-                SearchPointsBuilder::new(collection_name, embedding, limit)
-                    // .filter(Filter::all([Condition::matches("bar", 12)]))
-                    .with_payload(true), // .params(SearchParamsBuilder::default().exact(true)),
-            )
-            .await?;
-
-        Ok(search_result
-            .result
-            .into_iter()
-            .enumerate()
-            .filter_map(|(i, point)| {
-                let id = if let PointIdOptions::Num(id) = point.id?.point_id_options? {
-                    id
-                } else {
-                    return None;
-                };
-
-                if point.score > threshold {
-                    log::debug!(
-                        "payload #{i}:\n{}\nscore: {}",
-                        serde_json::to_string_pretty(&point.payload).ok()?,
-                        point.score
-                    );
-
-                    Some(Memory::try_from(id, point.payload)?)
-                } else {
-                    None
-                }
-            })
-            .collect())
+        self.backend
+            .search(embedding, namespace.as_str(), limit, threshold)
+            .await
     }
 
     #[allow(unused)]
     pub async fn find_recent(
         &self,
-        user_id: UserId,
+        namespace: &MemoryNamespace,
         limit: u32,
         range: Option<chrono::Duration>,
     ) -> anyhow::Result<Vec<Memory>> {
-        let collection_name = self.try_create_collection(user_id).await?;
-
         let range = range.unwrap_or_else(|| chrono::Duration::days(1));
-        let lower_bound_ts = (Utc::now() - range).timestamp_millis();
-
-        // Build a filter: only return points whose "date" field is >= lower_bound_ts.
-        let filter = Filter {
-            must: vec![Condition {
-                condition_one_of: Some(ConditionOneOf::Field(FieldCondition {
-                    key: "date".to_string(),
-                    range: Some(Range {
-                        gt: None,
-                        gte: Some(lower_bound_ts as f64),
-                        lt: None,
-                        lte: None,
-                    }),
-                    ..Default::default()
-                })),
-            }],
-            ..Default::default()
-        };
 
-        // Use scroll_points to get points matching the filter.
-        let scroll_result = self
-            .client
-            .scroll(
-                ScrollPointsBuilder::new(collection_name)
-                    .with_payload(true)
-                    .filter(filter)
-                    .limit(limit),
-            )
-            .await?;
-
-        Ok(scroll_result
-            .result
-            .into_iter()
-            .filter_map(|point| {
-                let id = if let PointIdOptions::Num(id) = point.id?.point_id_options? {
-                    id
-                } else {
-                    return None;
-                };
-
-                Some(Memory::try_from(id, point.payload)?)
-            })
-            .collect())
+        self.backend
+            .find_recent(namespace.as_str(), limit, Utc::now() - range)
+            .await
+    }
+
+    /// Returns every memory of the user, newest first.
+    pub async fn list(&self, namespace: &MemoryNamespace) -> anyhow::Result<Vec<Memory>> {
+        let mut memories = self.backend.scroll_all(namespace.as_str()).await?;
+        memories.sort_by(|a, b| b.date.cmp(&a.date));
+
+        Ok(memories)
+    }
+
+    pub async fn get(
+        &self,
+        id: u64,
+        namespace: &MemoryNamespace,
+    ) -> anyhow::Result<Option<Memory>> {
+        self.backend.get(id, namespace.as_str()).await
+    }
+
+    /// Replaces the content and embedding of an existing memory, keeping its id and date.
+    pub async fn update(
+        &self,
+        memory: Memory,
+        embedding: Vec<f32>,
+        namespace: &MemoryNamespace,
+    ) -> anyhow::Result<()> {
+        if self.get(memory.id, namespace).await?.is_none() {
+            anyhow::bail!("memory {} not found", memory.id);
+        }
+
+        self.backend
+            .store(memory, embedding, namespace.as_str())
+            .await
+    }
+
+    pub async fn delete(&self, id: u64, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        if self.get(id, namespace).await?.is_none() {
+            anyhow::bail!("memory {id} not found");
+        }
+
+        self.backend.delete(vec![id], namespace.as_str()).await
+    }
+
+    /// Deletes the given memories, without checking that they exist first.
+    pub async fn delete_many(
+        &self,
+        ids: Vec<u64>,
+        namespace: &MemoryNamespace,
+    ) -> anyhow::Result<()> {
+        self.backend.delete(ids, namespace.as_str()).await
+    }
+
+    pub async fn delete_all(&self, namespace: &MemoryNamespace) -> anyhow::Result<()> {
+        self.backend.delete_all(namespace.as_str()).await
     }
 }
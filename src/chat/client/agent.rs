@@ -1,40 +1,61 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, RwLock},
+};
 
 use anyhow::anyhow;
+use chrono_tz::Tz;
+use futures::StreamExt;
 use regex::Regex;
 use rig::{
     OneOrMany,
     completion::{CompletionRequest, ToolDefinition},
     embeddings::Embedding,
     message::{AssistantContent, Message, ToolCall, ToolFunction, ToolResultContent, UserContent},
+    streaming::{StreamingChoice, StreamingResult},
     tool::{Tool, ToolDyn},
 };
-use rig_dyn::{CompletionModel, EmbeddingModel};
 use serde_json::json;
 use serenity::all::UserId;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     chat::{
         ChatMessage,
-        archive::storage::{Memory, MemoryStorage},
-        context::{MessageRole, UserPrompt},
+        archive::storage::{Memory, MemoryNamespace, MemoryStorage},
+        context::{ConversationScope, MessageRole, ToolStep, UserPrompt, without_images},
+        reminder::Reminders,
     },
     config::structure::LLMConfig,
 };
 
-use super::tools;
+use super::{
+    Embedder,
+    provider::ProviderChain,
+    registry::{ModelRegistry, SharedMemory},
+    stream::{StreamEvent, ThinkFilter},
+    tools,
+};
+
+/// How many rounds of tool calls a single response may go through by default.
+const DEFAULT_MAX_TOOL_STEPS: usize = 5;
 
 pub struct CompletionAgentSettings {
-    user_name: String,
-    assistant_name: String,
+    pub user_name: String,
+    pub assistant_name: String,
+    /// The timezone reminders are set in, unless the speaker has one of their own
+    pub timezone: Tz,
 }
 
 pub struct CompletionAgent {
-    completion_model: Arc<Box<dyn CompletionModel>>,
-    embedding_model: Arc<Box<dyn EmbeddingModel>>,
+    providers: Arc<ProviderChain>,
+    embedding_model: Arc<dyn Embedder>,
     memory_storage: Arc<MemoryStorage>,
     tools: HashMap<String, Box<dyn ToolDyn>>,
-    user_id: UserId,
+    scope: ConversationScope,
+    namespace: MemoryNamespace,
+    speaker: Arc<RwLock<Option<UserId>>>,
     config: LLMConfig,
     settings: CompletionAgentSettings,
 }
@@ -42,103 +63,321 @@ pub struct CompletionAgent {
 impl CompletionAgent {
     pub async fn new(
         config: LLMConfig,
-        user_id: UserId,
-        user_name: String,
-        assistant_name: String,
+        registry: &ModelRegistry,
+        scope: ConversationScope,
+        persona: Option<&str>,
+        settings: CompletionAgentSettings,
+        save_folder: Option<&Path>,
+        reminders: Arc<Reminders>,
     ) -> anyhow::Result<Self> {
-        let client = config.completion.provider.client(
-            &config.completion.api_key,
-            config.completion.custom_url.as_deref(),
-        )?;
-        let completion_model = Arc::new(client.completion_model(&config.completion.model).await);
-
-        let embedding_client = config
-            .embedding
-            .provider
-            .map(|provider| {
-                provider.client(
-                    config
-                        .embedding
-                        .api_key
-                        .as_deref()
-                        .unwrap_or(&config.completion.api_key),
-                    config.embedding.custom_url.as_deref(),
-                )
-            })
-            .unwrap_or(Ok(client))?;
-
-        let embedding_model = match config.embedding.vector_size {
-            Some(vector_size) => {
-                let client = embedding_client
-                    .embedding_model_with_ndims(&config.embedding.model, vector_size, None)
-                    .await
-                    .ok_or(anyhow!("failed to create embedding model"))?;
-
-                Arc::new(client)
-            }
-            None => {
-                let client = embedding_client
-                    .embedding_model(&config.embedding.model, None)
-                    .await
-                    .ok_or(anyhow!("failed to create embedding model"))?;
-
-                Arc::new(client)
-            }
-        };
-
-        // test embedding model and obtain true vector size
-        let vector_size = embedding_model.embed_text("a").await?.vec.len() as u64;
+        let providers = registry.providers(&config).await?;
+        let SharedMemory {
+            embedding_model,
+            storage: memory_storage,
+        } = registry.memory(&config, save_folder).await?;
 
-        log::info!("vector size: {}", vector_size);
+        let namespace = MemoryNamespace::new(scope, persona);
+        memory_storage.health_check(&namespace).await?;
 
-        let memory_storage = Arc::new(MemoryStorage::new(&config, vector_size));
-        memory_storage.health_check(user_id).await?;
+        let speaker = Arc::new(RwLock::new(match scope {
+            ConversationScope::Direct(user) => Some(user),
+            _ => None,
+        }));
 
         let recall = tools::MemoryRecall::new(
             embedding_model.clone(),
             memory_storage.clone(),
-            user_id,
-            user_name.clone(),
-            assistant_name.clone(),
+            namespace.clone(),
+            settings.user_name.clone(),
+            settings.assistant_name.clone(),
         );
         let store = tools::MemoryStore::new(
             embedding_model.clone(),
             memory_storage.clone(),
-            user_id,
-            user_name.clone(),
-            assistant_name.clone(),
+            namespace.clone(),
+            speaker.clone(),
+            settings.user_name.clone(),
+            settings.assistant_name.clone(),
+        );
+        let reminder = tools::ReminderScope::new(
+            reminders,
+            scope,
+            speaker.clone(),
+            save_folder.map(Path::to_path_buf),
+            settings.timezone,
         );
 
         let mut tools: HashMap<String, Box<dyn ToolDyn>> = HashMap::new();
         tools.insert(tools::MemoryRecall::NAME.to_string(), Box::new(recall));
         tools.insert(tools::MemoryStore::NAME.to_string(), Box::new(store));
+        tools.insert(
+            tools::ReminderCreate::NAME.to_string(),
+            Box::new(tools::ReminderCreate(reminder.clone())),
+        );
+        tools.insert(
+            tools::ReminderList::NAME.to_string(),
+            Box::new(tools::ReminderList(reminder.clone())),
+        );
+        tools.insert(
+            tools::ReminderCancel::NAME.to_string(),
+            Box::new(tools::ReminderCancel(reminder)),
+        );
 
-        log::info!("engine initialized successfully for {user_id}, health checks passed");
+        log::info!("engine initialized successfully for {scope}, health checks passed");
 
         Ok(Self {
-            completion_model,
+            providers,
             embedding_model,
             memory_storage,
             tools,
-            user_id,
+            scope,
+            namespace,
+            speaker,
             config,
-            settings: CompletionAgentSettings {
-                user_name,
-                assistant_name,
-            },
+            settings,
         })
     }
 
+    /// The LLM settings the client was built with.
+    pub fn config(&self) -> &LLMConfig {
+        &self.config
+    }
+
+    /// Swaps the completion settings, keeping the embedding model, memories and tools.
+    pub async fn update_completion(
+        &mut self,
+        config: LLMConfig,
+        registry: &ModelRegistry,
+    ) -> anyhow::Result<()> {
+        self.providers = registry.providers(&config).await?;
+        self.config = config;
+
+        Ok(())
+    }
+
     pub async fn completion(
         &self,
-        mut prompt: &mut UserPrompt,
-        mut system_prompt: String,
+        prompt: &UserPrompt,
+        system_prompt: String,
+        context: Vec<ChatMessage>,
+    ) -> anyhow::Result<CompletionResult> {
+        self.run(prompt, system_prompt, context, None).await
+    }
+
+    /// Same as [CompletionAgent::completion], but sends the visible text of the response to
+    /// `deltas` as it is generated.
+    ///
+    /// Providers that cannot stream fall back to a regular completion, in which case
+    /// nothing is sent and the whole response is only available once returned.
+    pub async fn completion_stream(
+        &self,
+        prompt: &UserPrompt,
+        system_prompt: String,
+        context: Vec<ChatMessage>,
+        deltas: &UnboundedSender<StreamEvent>,
+    ) -> anyhow::Result<CompletionResult> {
+        let deltas = self
+            .config
+            .completion
+            .stream
+            .unwrap_or(true)
+            .then_some(deltas);
+
+        self.run(prompt, system_prompt, context, deltas).await
+    }
+
+    /// The agent loop, prompting the model and running every tool it calls until it answers
+    /// with text or runs out of steps.
+    async fn run(
+        &self,
+        prompt: &UserPrompt,
+        system_prompt: String,
         context: Vec<ChatMessage>,
+        deltas: Option<&UnboundedSender<StreamEvent>>,
     ) -> anyhow::Result<CompletionResult> {
-        //? traditional RAG
-        self.rag_recall(&mut prompt).await?;
-        // let recalled: Vec<String> = vec![]; // todo testing
+        self.prepare(prompt);
+
+        let max_steps = self.config.max_tool_steps.unwrap_or(DEFAULT_MAX_TOOL_STEPS);
+
+        let mut steps: Vec<ToolStep> = vec![];
+        let mut tools: Vec<String> = vec![];
+
+        loop {
+            let last_step = steps.len() >= max_steps;
+
+            // built once, failing to build it is not the fault of any provider
+            let request = self
+                .build_request(prompt, system_prompt.clone(), &context, &steps, last_step)
+                .await?;
+
+            let contents = self
+                .providers
+                .run(|model| {
+                    let request = request.clone();
+
+                    async move {
+                        let Some(deltas) = deltas else {
+                            return Ok(model.completion(request).await?.into_iter().collect());
+                        };
+
+                        // a previous attempt may have streamed part of a response already
+                        let _ = deltas.send(StreamEvent::Reset);
+
+                        match model.stream(request.clone()).await {
+                            Ok(stream) => self.collect_stream(stream, deltas).await,
+                            Err(why) => {
+                                log::warn!(
+                                    "provider failed to stream, falling back to a completion: {why}"
+                                );
+
+                                Ok(model.completion(request).await?.into_iter().collect())
+                            }
+                        }
+                    }
+                })
+                .await?;
+
+            let (calls, texts): (Vec<_>, Vec<_>) = contents
+                .into_iter()
+                .partition(|content| matches!(content, AssistantContent::ToolCall(_)));
+
+            let calls = calls
+                .into_iter()
+                .filter_map(|content| match content {
+                    AssistantContent::ToolCall(call) => Some(call),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            let text = texts.into_iter().find_map(|content| match content {
+                AssistantContent::Text(text) => Some(text.text),
+                _ => None,
+            });
+
+            match (calls.is_empty() || last_step, text) {
+                (true, Some(text)) => {
+                    let mut message = ChatMessage::assistant(self.clean_response(text)?);
+                    message.tool_steps = steps;
+
+                    return Ok(CompletionResult { message, tools });
+                }
+                (true, None) if last_step => {
+                    anyhow::bail!("ran out of tool steps ({max_steps}) without a response")
+                }
+                (true, None) => anyhow::bail!("response does not have any text"),
+                (false, _) => {
+                    log::info!(
+                        "step {}: calling {}",
+                        steps.len() + 1,
+                        calls
+                            .iter()
+                            .map(|call| call.function.name.as_str())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    );
+
+                    tools.extend(calls.iter().map(|call| call.function.name.clone()));
+                    steps.push(self.run_tools(calls).await?);
+                }
+            }
+        }
+    }
+
+    /// Runs every tool call of a response concurrently, failed calls are reported back to
+    /// the model instead of failing the whole completion.
+    async fn run_tools(&self, calls: Vec<ToolCall>) -> anyhow::Result<ToolStep> {
+        let results = futures::future::join_all(calls.iter().map(async |call| {
+            let name = call.function.name.clone();
+            let result = match self
+                .call_tool(&name, call.function.arguments.to_string())
+                .await
+            {
+                Ok(result) => result,
+                Err(why) => {
+                    log::warn!("tool {name} failed: {why:?}");
+                    format!("error: {why}")
+                }
+            };
+
+            UserContent::tool_result(
+                call.id.clone(),
+                OneOrMany::one(ToolResult::from((name, result)).into()),
+            )
+        }))
+        .await;
+
+        Ok(ToolStep {
+            call: Message::Assistant {
+                content: OneOrMany::many(calls.into_iter().map(AssistantContent::ToolCall))?,
+            },
+            result: Message::User {
+                content: OneOrMany::many(results)?,
+            },
+        })
+    }
+
+    /// Drains a completion stream, forwarding its visible text to `deltas`.
+    async fn collect_stream(
+        &self,
+        mut stream: StreamingResult,
+        deltas: &UnboundedSender<StreamEvent>,
+    ) -> anyhow::Result<Vec<AssistantContent>> {
+        let mut text = String::new();
+        let mut calls = vec![];
+        let mut filter = ThinkFilter::default();
+
+        while let Some(choice) = stream.next().await {
+            match choice? {
+                StreamingChoice::Message(delta) => {
+                    text.push_str(&delta);
+
+                    let mut visible = filter.push(&delta);
+                    if self.config.force_lowercase.unwrap_or(false) {
+                        visible = visible.to_lowercase();
+                    }
+
+                    if !visible.is_empty() {
+                        let _ = deltas.send(StreamEvent::Delta(visible));
+                    }
+                }
+                StreamingChoice::ToolCall(name, id, arguments) => {
+                    calls.push(AssistantContent::ToolCall(ToolCall {
+                        id,
+                        function: ToolFunction { name, arguments },
+                    }));
+                }
+            }
+        }
+
+        if calls.is_empty() {
+            return Ok(vec![AssistantContent::text(text)]);
+        }
+
+        // whatever was said alongside the calls is not the final response
+        let _ = deltas.send(StreamEvent::Reset);
+        if !text.is_empty() {
+            calls.push(AssistantContent::text(text));
+        }
+
+        Ok(calls)
+    }
+
+    fn prepare(&self, prompt: &UserPrompt) {
+        // attribute anything stored during this completion to whoever is being answered
+        if let Some(speaker) = &prompt.speaker {
+            if let Ok(mut current) = self.speaker.write() {
+                *current = Some(speaker.id);
+            }
+        }
+    }
 
+    async fn build_request(
+        &self,
+        prompt: &UserPrompt,
+        mut system_prompt: String,
+        context: &[ChatMessage],
+        steps: &[ToolStep],
+        last_step: bool,
+    ) -> anyhow::Result<CompletionRequest> {
         log::trace!("User prompt: {prompt:?}");
         for (i, message) in context.iter().enumerate() {
             log::trace!("Context {i}: {message:?}");
@@ -146,23 +385,32 @@ impl CompletionAgent {
 
         // let recent = self
         //     .memory_storage
-        //     .find_recent(self.user_id, 5, None)
+        //     .find_recent(self.scope, 5, None)
         //     .await?;
 
         // log::info!("recent memories: {:?}", recent);
 
         //? rag by tool (incentive)
         let use_tools = self.config.use_tools.unwrap_or(true);
-        let tools = if use_tools {
-            system_prompt.push_str("
+        let tools = match (use_tools, last_step) {
+            (false, _) => vec![],
+            // without any tools to call, the model has to answer
+            (true, true) => {
+                system_prompt.push_str("\n## Tool Usage\nYou have used up all of your tool calls for this message, respond to the user now without calling any more tools.\n\n");
+
+                vec![]
+            }
+            (true, false) => {
+                system_prompt.push_str("
 ## Tool Usage
 - Actively try to utilize the memory_store tool to store important information that you'd like to recall later in the long term memory storage, preferably in bullet points. Do not mention the usage of this tool to the user, just use it when needed.
 - Actively try to utilize the memory_recall tool to recall information from previous messages and conversations you are not currently aware of. Do not mention this usage of the tool to the user, just use it when needed. If you believe a memory has already been recalled by the user (as seen in the \"relevant_memories\" section), choose not to recall it again.
+- Utilize the reminder_create tool when the user asks to be reminded of something or you agree to bring something up later, and the reminder_list and reminder_cancel tools to look at or drop pending reminders. You will be prompted once a reminder is due, so do not promise to remember things you did not set a reminder for.
 
 ");
-            self.tool_definitions().await
-        } else {
-            vec![]
+
+                self.tool_definitions().await
+            }
         };
 
         if self.config.completion.reason.unwrap_or(false)
@@ -208,83 +456,86 @@ After this reasoning step, provide your in-character response. This reasoning pr
         }
         log::trace!("additional_params: {:?}", json!(additional_params));
 
-        let request = CompletionRequest {
+        let mut chat_history = context
+            .iter()
+            .cloned()
+            .flat_map(ChatMessage::into_history)
+            .collect::<Vec<_>>();
+
+        // once tools ran, the prompt is followed by the calls and their results, the last of
+        // which takes the place of the prompt
+        let prompt = match steps.is_empty() {
+            true => prompt.clone().try_into()?,
+            false => {
+                chat_history.push(prompt.clone().try_into()?);
+                for step in steps {
+                    chat_history.push(step.call.clone());
+                    chat_history.push(step.result.clone());
+                }
+
+                chat_history
+                    .pop()
+                    .ok_or(anyhow!("tool steps without a result"))?
+            }
+        };
+
+        // images that were sent before vision got turned off
+        let (chat_history, prompt) = match self.config.completion.vision.unwrap_or(false) {
+            true => (chat_history, prompt),
+            false => (
+                chat_history.into_iter().map(without_images).collect(),
+                without_images(prompt),
+            ),
+        };
+
+        Ok(CompletionRequest {
             additional_params: Some(json!(additional_params)),
-            chat_history: context.into_iter().map(|x| x.into()).collect(),
+            chat_history,
             documents: vec![],
             max_tokens: self.config.completion.max_tokens,
             preamble: Some(system_prompt),
             // preamble: None, // todo testing
             temperature: self.config.completion.temperature,
             tools,
-            prompt: prompt.clone().try_into()?,
-        };
-
-        let response = self.completion_model.completion(request).await?;
-
-        match response.first() {
-            rig::message::AssistantContent::Text(mut text) => {
-                log::trace!("Original response:\n{:?}", text.text);
-
-                if self.config.force_lowercase.unwrap_or(false) {
-                    text.text = text.text.to_lowercase();
-                }
-
-                // get rid of CoT
-                let regex = Regex::new(
-                    r"<(?:think|reasoning)>((?:.|\n)*?)<\/(?:think|reasoning)>(?:\n*)?",
-                )?;
-                let matches: Vec<_> = regex.captures_iter(&text.text).collect();
-                for cap in &matches {
-                    if let Some(thought) = cap.get(1) {
-                        log::trace!("Extracted thought process:\n{}", thought.as_str());
-                    }
-                }
-                text.text = regex.replace_all(&text.text, "").to_string();
-
-                // get rid of weird artifacts
-                // 1 or more space before double newline -> double newline
-                let regex = Regex::new(r" +\n\n")?;
-                text.text = regex.replace_all(&text.text, "\n\n").to_string();
-                // 2 or more spaces -> single space
-                let regex = Regex::new(r" {2,}")?;
-                text.text = regex.replace_all(&text.text, " ").to_string();
-                // 3 or more newlines -> 2 newlines
-                let regex = Regex::new(r"\n\n\n+")?;
-                text.text = regex.replace_all(&text.text, "\n\n").to_string();
-                // get rid of "\boxed{TEXT}" if present
-                // if text.text.starts_with("\\boxed{") && text.text.ends_with("}") {
-                //     text.text = text.text[7..text.text.len() - 1].to_string();
-                // }
-
-                Ok(CompletionResult::Message(Message::Assistant {
-                    content: OneOrMany::one(AssistantContent::text(&text.text)),
-                }))
-            }
-            rig::message::AssistantContent::ToolCall(tool_call) => {
-                let tool_call_msg = AssistantContent::ToolCall(tool_call.clone());
+            prompt,
+        })
+    }
 
-                let ToolCall {
-                    id,
-                    function: ToolFunction { name, arguments },
-                } = tool_call;
+    /// Strips the reasoning and formatting artifacts out of a text response.
+    fn clean_response(&self, mut text: String) -> anyhow::Result<String> {
+        log::trace!("Original response:\n{:?}", text);
 
-                let result = self.call_tool(&name, arguments.to_string()).await?;
-                let tool_result: ToolResult = (name, result).into();
+        if self.config.force_lowercase.unwrap_or(false) {
+            text = text.to_lowercase();
+        }
 
-                Ok(CompletionResult::Tool((
-                    Message::Assistant {
-                        content: OneOrMany::one(tool_call_msg),
-                    },
-                    Message::User {
-                        content: OneOrMany::one(UserContent::tool_result(
-                            id,
-                            OneOrMany::one(tool_result.into()),
-                        )),
-                    },
-                )))
+        // get rid of CoT
+        let regex =
+            Regex::new(r"<(?:think|reasoning)>((?:.|\n)*?)<\/(?:think|reasoning)>(?:\n*)?")?;
+        let matches: Vec<_> = regex.captures_iter(&text).collect();
+        for cap in &matches {
+            if let Some(thought) = cap.get(1) {
+                log::trace!("Extracted thought process:\n{}", thought.as_str());
             }
         }
+        text = regex.replace_all(&text, "").to_string();
+
+        // get rid of weird artifacts
+        // 1 or more space before double newline -> double newline
+        let regex = Regex::new(r" +\n\n")?;
+        text = regex.replace_all(&text, "\n\n").to_string();
+        // 2 or more spaces -> single space
+        let regex = Regex::new(r" {2,}")?;
+        text = regex.replace_all(&text, " ").to_string();
+        // 3 or more newlines -> 2 newlines
+        let regex = Regex::new(r"\n\n\n+")?;
+        text = regex.replace_all(&text, "\n\n").to_string();
+        // get rid of "\boxed{TEXT}" if present
+        // if text.starts_with("\\boxed{") && text.ends_with("}") {
+        //     text = text[7..text.len() - 1].to_string();
+        // }
+
+        Ok(text)
     }
 
     async fn tool_definitions(&self) -> Vec<ToolDefinition> {
@@ -313,26 +564,15 @@ After this reasoning step, provide your in-character response. This reasoning pr
 
         log::trace!("RAG query message: {message}");
 
-        let vec = self
-            .embedding_model
-            .embed_text(&message)
-            .await?
-            .vec
-            .into_iter()
-            .map(|x| x as f32)
-            .collect::<Vec<f32>>();
+        let vec = self.embed(message).await?;
 
         // todo change limit here
         let recalled = self
             .memory_storage
-            .search(vec, self.user_id, 5, None)
+            .search(vec, &self.namespace, 5, None)
             .await?
-            .iter_mut()
-            .map(|x| {
-                x.content
-                    .replace("<user>", &self.settings.user_name)
-                    .replace("<assistant>", &self.settings.assistant_name)
-            })
+            .iter()
+            .map(|x| self.fill_placeholders(&x.content))
             .collect::<Vec<_>>();
 
         if !recalled.is_empty() {
@@ -343,6 +583,73 @@ After this reasoning step, provide your in-character response. This reasoning pr
         Ok(())
     }
 
+    async fn embed(&self, text: &str) -> anyhow::Result<Vec<f32>> {
+        Ok(self
+            .embedding_model
+            .embed_text(text)
+            .await?
+            .vec
+            .into_iter()
+            .map(|x| x as f32)
+            .collect::<Vec<f32>>())
+    }
+
+    /// Replaces the `<user>` and `<assistant>` placeholders of a stored memory.
+    pub fn fill_placeholders(&self, content: &str) -> String {
+        content
+            .replace("<user>", &self.settings.user_name)
+            .replace("<assistant>", &self.settings.assistant_name)
+    }
+
+    /// Returns every stored memory of the user, newest first.
+    pub async fn list_memories(&self) -> anyhow::Result<Vec<Memory>> {
+        self.memory_storage.list(&self.namespace).await
+    }
+
+    pub async fn get_memory(&self, id: u64) -> anyhow::Result<Option<Memory>> {
+        self.memory_storage.get(id, &self.namespace).await
+    }
+
+    /// Searches the memories of the user the same way [CompletionAgent::rag_recall] does,
+    /// but without a similarity threshold and keeping the scores.
+    pub async fn search_memories(
+        &self,
+        query: &str,
+        limit: u64,
+    ) -> anyhow::Result<Vec<(Memory, f32)>> {
+        let vec = self.embed(query).await?;
+
+        self.memory_storage
+            .search_scored(vec, &self.namespace, limit, Some(f32::MIN))
+            .await
+    }
+
+    /// Rewrites a memory, re-embedding its new content.
+    pub async fn edit_memory(&self, id: u64, content: &str) -> anyhow::Result<()> {
+        let memory = self
+            .get_memory(id)
+            .await?
+            .ok_or(anyhow!("memory {id} not found"))?;
+
+        let content = content
+            .replace(&self.settings.user_name, "<user>")
+            .replace(&self.settings.assistant_name, "<assistant>");
+
+        let vec = self.embed(&content).await?;
+
+        self.memory_storage
+            .update(Memory { content, ..memory }, vec, &self.namespace)
+            .await
+    }
+
+    pub async fn forget_memory(&self, id: u64) -> anyhow::Result<()> {
+        self.memory_storage.delete(id, &self.namespace).await
+    }
+
+    pub async fn forget_all_memories(&self) -> anyhow::Result<()> {
+        self.memory_storage.delete_all(&self.namespace).await
+    }
+
     pub async fn store(
         &self,
         context: Vec<ChatMessage>,
@@ -359,7 +666,17 @@ After this reasoning step, provide your in-character response. This reasoning pr
         let vec = vec.into_iter().map(|x| x as f32).collect::<Vec<f32>>();
 
         self.memory_storage
-            .store(Memory::new(document), vec, self.user_id)
+            .store(
+                Memory::new(
+                    document,
+                    match self.scope {
+                        ConversationScope::Direct(user) => Some(user),
+                        _ => None,
+                    },
+                ),
+                vec,
+                &self.namespace,
+            )
             .await
     }
 
@@ -422,62 +739,122 @@ User is currently at home
 
 <assistant> is hungry".to_string();
 
-        let prompt = Message::user(
-            context
-                .into_iter()
-                .filter_map(|msg| {
-                    let role = msg.role();
-
-                    let content = match role {
-                        MessageRole::User => {
-                            let user_prompt: UserPrompt = TryFrom::try_from(msg)
-                                .map_err(|why| {
-                                    log::warn!("failed to deserialize user prompt: {why:?}");
-                                })
-                                .ok()?;
-
-                            user_prompt.content.or(user_prompt.system_note)
-                        }
-                        MessageRole::Assistant => msg.content(),
-                    }?;
-
-                    Some(format!(
-                        "{}: {}\n---\n",
-                        match role {
-                            MessageRole::User => user_name,
-                            MessageRole::Assistant => assistant_name,
-                        },
-                        content
-                    ))
-                })
-                .collect::<Vec<String>>()
-                .join("")
-                .trim_end_matches("\n---\n")
-                .replace(user_name, "<user>")
-                .replace(assistant_name, "<assistant>")
-                .to_owned(),
-        );
+        let prompt = Self::transcript(context, user_name, assistant_name)
+            .replace(user_name, "<user>")
+            .replace(assistant_name, "<assistant>");
 
         log::trace!("Summarize prompt:\n{:?}", prompt);
 
-        let request = CompletionRequest {
-            // todo decide if i want this or not
-            // additional_params: Some(json!({
-            //     "top_p": 0.2,
-            //     "frequency_penalty": 0.2,
-            //     "presence_penalty": 0.0,
-            // })),
-            additional_params: None,
-            chat_history: vec![],
-            documents: vec![],
-            max_tokens: Some(8192),
-            preamble: Some(preamble),
-            temperature: Some(0.2),
-            tools: vec![],
-            prompt,
-        };
+        self.ask(preamble, prompt, 0.2).await
+    }
+
+    /// Extends the story so far with messages that were drained out of the context.
+    pub async fn summarize_story(
+        &self,
+        story: Option<&str>,
+        context: Vec<ChatMessage>,
+        user_name: &str,
+        assistant_name: &str,
+    ) -> anyhow::Result<String> {
+        let preamble = format!("# Story Summarization Assistant
+You keep a running narrative summary (\"the story so far\") of a long conversation between {} and other people, so that it can continue coherently after older messages are forgotten.
+
+## Task
+Rewrite the story so far so that it also covers the new messages you are given, keeping everything important from the previous version.
+
+## Format
+- Write in past tense, third person, as flowing prose
+- Keep the order in which things happened
+- Keep names, relationships, promises, running jokes, unresolved threads and the current situation
+- Leave out small talk and anything that did not move the conversation forward
+- Only output the updated story, nothing else", assistant_name);
+
+        let prompt = format!(
+            "## Story so far\n{}\n\n## New messages\n{}",
+            story.unwrap_or("Nothing happened yet."),
+            Self::transcript(context, user_name, assistant_name)
+        );
+
+        log::trace!("Story prompt:\n{:?}", prompt);
+
+        self.ask(preamble, prompt, 0.3).await
+    }
+
+    /// Condenses the story so far once it grew too big to keep in the prompt.
+    pub async fn compact_story(&self, story: &str, max_tokens: usize) -> anyhow::Result<String> {
+        let preamble = format!("# Story Compaction Assistant
+You are given the running narrative summary of a long conversation, which has grown too long.
+
+## Task
+Condense it to well under {} words, keeping the most recent events in the most detail and summarizing older ones in broad strokes. Never drop names, relationships or unresolved threads.
+
+Only output the condensed story, nothing else.", max_tokens / 2);
+
+        self.ask(preamble, story.to_string(), 0.2).await
+    }
+
+    /// Renders messages as a `name: content` transcript, oldest first.
+    fn transcript(context: Vec<ChatMessage>, user_name: &str, assistant_name: &str) -> String {
+        context
+            .into_iter()
+            .filter_map(|msg| {
+                let role = msg.role();
+                let speaker = msg.speaker.as_ref().map(|speaker| speaker.name.clone());
+
+                let content = match &msg.prompt {
+                    // prompts without content are the bot being nudged, e.g. freewill
+                    Some(envelope) => msg
+                        .content()
+                        .filter(|content| !content.is_empty())
+                        .or(envelope.system_note.clone()),
+                    None => msg.content(),
+                }?;
+
+                Some(format!(
+                    "{}: {}\n---\n",
+                    match role {
+                        MessageRole::User => speaker.as_deref().unwrap_or(user_name),
+                        MessageRole::Assistant => assistant_name,
+                    },
+                    content
+                ))
+            })
+            .collect::<Vec<String>>()
+            .join("")
+            .trim_end_matches("\n---\n")
+            .to_owned()
+    }
 
-        let response = self.completion_model.completion(request).await?;
+    /// Single tool-less completion, used for the summarization tasks.
+    async fn ask(
+        &self,
+        preamble: String,
+        prompt: String,
+        temperature: f64,
+    ) -> anyhow::Result<String> {
+        let response = self
+            .providers
+            .run(|model| {
+                let request = CompletionRequest {
+                    // todo decide if i want this or not
+                    // additional_params: Some(json!({
+                    //     "top_p": 0.2,
+                    //     "frequency_penalty": 0.2,
+                    //     "presence_penalty": 0.0,
+                    // })),
+                    additional_params: None,
+                    chat_history: vec![],
+                    documents: vec![],
+                    max_tokens: Some(8192),
+                    preamble: Some(preamble.clone()),
+                    temperature: Some(temperature),
+                    tools: vec![],
+                    prompt: Message::user(prompt.clone()),
+                };
+
+                async move { Ok(model.completion(request).await?) }
+            })
+            .await?;
 
         if let AssistantContent::Text(message) = response.first() {
             return Ok(message.text);
@@ -504,10 +881,10 @@ impl From<ToolResult> for ToolResultContent {
     }
 }
 
-pub enum CompletionResult {
-    /// Returns the message (assistant message)
-    Message(Message),
+pub struct CompletionResult {
+    /// The response (assistant message), carrying the tool steps that led to it
+    pub message: ChatMessage,
 
-    /// Returns the tool call and tool result (assistant and user messages)
-    Tool((Message, Message)),
+    /// Names of every tool that ran, in the order they were called
+    pub tools: Vec<String>,
 }
@@ -1,4 +1,12 @@
 mod agent;
+mod model;
+mod provider;
+mod registry;
+mod stream;
 mod tools;
 
 pub use agent::*;
+pub use model::*;
+pub use provider::*;
+pub use registry::*;
+pub use stream::*;
@@ -0,0 +1,44 @@
+use async_trait::async_trait;
+use rig::{
+    OneOrMany, completion::CompletionRequest, embeddings::Embedding, message::AssistantContent,
+    streaming::StreamingResult,
+};
+
+/// A completion model as the agent uses it, implemented by every provider's model so that
+/// it can be faked.
+#[async_trait]
+pub trait ChatModel: Send + Sync {
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> anyhow::Result<OneOrMany<AssistantContent>>;
+
+    async fn stream(&self, request: CompletionRequest) -> anyhow::Result<StreamingResult>;
+}
+
+#[async_trait]
+impl ChatModel for Box<dyn rig_dyn::CompletionModel> {
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> anyhow::Result<OneOrMany<AssistantContent>> {
+        Ok(rig_dyn::CompletionModel::completion(&**self, request).await?)
+    }
+
+    async fn stream(&self, request: CompletionRequest) -> anyhow::Result<StreamingResult> {
+        Ok(rig_dyn::CompletionModel::stream(&**self, request).await?)
+    }
+}
+
+/// An embedding model as memories use it.
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    async fn embed_text(&self, text: &str) -> anyhow::Result<Embedding>;
+}
+
+#[async_trait]
+impl Embedder for Box<dyn rig_dyn::EmbeddingModel> {
+    async fn embed_text(&self, text: &str) -> anyhow::Result<Embedding> {
+        Ok(rig_dyn::EmbeddingModel::embed_text(&**self, text).await?)
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    fmt::Display,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rig::completion::CompletionError;
+use rig_dyn::Provider;
+
+use crate::{
+    config::structure::{LLMConfig, RetryConfig},
+    utils::Environment,
+};
+
+use super::ChatModel;
+
+const DEFAULT_MAX_ATTEMPTS: usize = 3;
+const DEFAULT_BASE_DELAY_MS: u64 = 500;
+const DEFAULT_MAX_DELAY_MS: u64 = 8000;
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_COOLDOWN_SECS: u64 = 60;
+
+/// Why a provider failed, deciding whether it is worth trying again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailureKind {
+    RateLimited,
+    Unauthorized,
+    BadRequest,
+    Unavailable,
+}
+
+impl FailureKind {
+    /// Classifies a provider error from the HTTP status rig reports or, as rig only hands
+    /// back the body of most failed responses, from the error the provider answered with.
+    pub fn classify(error: &anyhow::Error) -> Self {
+        error
+            .chain()
+            .find_map(|cause| {
+                if let Some(why) = cause.downcast_ref::<CompletionError>() {
+                    Some(Self::from_completion(why))
+                } else {
+                    cause.downcast_ref::<reqwest::Error>().map(Self::from_http)
+                }
+            })
+            .unwrap_or(Self::Unavailable)
+    }
+
+    fn from_completion(error: &CompletionError) -> Self {
+        match error {
+            CompletionError::HttpError(why) => Self::from_http(why),
+            CompletionError::ProviderError(body) => {
+                Self::from_body(body).unwrap_or(Self::Unavailable)
+            }
+            // the request could not even be built, sending it again won't change that
+            CompletionError::RequestError(_) => Self::BadRequest,
+            _ => Self::Unavailable,
+        }
+    }
+
+    /// Without a status the request never got an answer, timing out or failing to connect.
+    fn from_http(error: &reqwest::Error) -> Self {
+        error
+            .status()
+            .map(|status| Self::from_status(status.as_u16()))
+            .unwrap_or(Self::Unavailable)
+    }
+
+    fn from_status(status: u16) -> Self {
+        match status {
+            429 => Self::RateLimited,
+            401 | 403 => Self::Unauthorized,
+            400 | 422 => Self::BadRequest,
+            _ => Self::Unavailable,
+        }
+    }
+
+    /// Reads the error object providers answer with, `{"error": {...}}` or the error
+    /// itself, by its numeric status or the type, code and status identifiers of OpenAI,
+    /// Anthropic and Google style APIs.
+    fn from_body(body: &str) -> Option<Self> {
+        let body = serde_json::from_str::<serde_json::Value>(body).ok()?;
+        let error = body.get("error").unwrap_or(&body);
+        let field = |name: &str| error.get(name);
+
+        if let Some(status) = ["code", "status"]
+            .into_iter()
+            .find_map(|name| field(name)?.as_u64())
+        {
+            return u16::try_from(status).ok().map(Self::from_status);
+        }
+
+        let identifiers = ["type", "code", "status"]
+            .into_iter()
+            .filter_map(|name| field(name)?.as_str())
+            .collect::<Vec<_>>();
+        let is = |kinds: &[&str]| identifiers.iter().any(|id| kinds.contains(id));
+
+        if is(&[
+            "rate_limit_error",
+            "rate_limit_exceeded",
+            "insufficient_quota",
+            "RESOURCE_EXHAUSTED",
+        ]) {
+            Some(Self::RateLimited)
+        } else if is(&[
+            "authentication_error",
+            "permission_error",
+            "invalid_api_key",
+            "UNAUTHENTICATED",
+            "PERMISSION_DENIED",
+        ]) {
+            Some(Self::Unauthorized)
+        } else if is(&["invalid_request_error", "INVALID_ARGUMENT"]) {
+            Some(Self::BadRequest)
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for FailureKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FailureKind::RateLimited => write!(f, "rate limited"),
+            FailureKind::Unauthorized => write!(f, "unauthorized"),
+            FailureKind::BadRequest => write!(f, "bad request"),
+            FailureKind::Unavailable => write!(f, "unavailable"),
+        }
+    }
+}
+
+/// Attached to the error of a failed completion, naming the provider that failed last.
+#[derive(Debug, Clone)]
+pub struct ProviderFailure {
+    pub provider: String,
+    pub kind: FailureKind,
+}
+
+impl Display for ProviderFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} failed ({})", self.provider, self.kind)
+    }
+}
+
+/// Keeps a provider that keeps failing out of rotation for a while.
+struct CircuitBreaker {
+    failures: u32,
+    open_until: Option<DateTime<Utc>>,
+}
+
+pub struct CompletionProvider {
+    pub label: String,
+    pub model: Arc<dyn ChatModel>,
+    breaker: Mutex<CircuitBreaker>,
+}
+
+impl CompletionProvider {
+    fn new(provider: &Provider, model_name: &str, model: Arc<dyn ChatModel>) -> Self {
+        let provider = serde_plain::to_string(provider).unwrap_or_else(|_| "unknown".into());
+
+        Self {
+            label: format!("{provider}/{model_name}"),
+            model,
+            breaker: Mutex::new(CircuitBreaker {
+                failures: 0,
+                open_until: None,
+            }),
+        }
+    }
+
+    fn is_open(&self, now: DateTime<Utc>) -> bool {
+        self.breaker
+            .lock()
+            .map(|breaker| breaker.open_until.is_some_and(|until| now < until))
+            .unwrap_or(false)
+    }
+
+    fn succeeded(&self) {
+        if let Ok(mut breaker) = self.breaker.lock() {
+            breaker.failures = 0;
+            breaker.open_until = None;
+        }
+    }
+
+    /// Counts a failure, opening the circuit once `threshold` of them happened in a row.
+    fn failed(&self, threshold: u32, cooldown: Duration, now: DateTime<Utc>) {
+        if let Ok(mut breaker) = self.breaker.lock() {
+            breaker.failures += 1;
+
+            if breaker.failures >= threshold {
+                log::warn!(
+                    "{} failed {} times in a row, skipping it for {}s",
+                    self.label,
+                    breaker.failures,
+                    cooldown.as_secs()
+                );
+                breaker.open_until = Some(
+                    now + chrono::Duration::from_std(cooldown).unwrap_or(chrono::Duration::MAX),
+                );
+            }
+        }
+    }
+}
+
+/// The configured completion model followed by its fallbacks, tried in order with
+/// exponential backoff between rounds.
+pub struct ProviderChain {
+    providers: Vec<CompletionProvider>,
+    max_attempts: usize,
+    base_delay: Duration,
+    max_delay: Duration,
+    failure_threshold: u32,
+    cooldown: Duration,
+    /// Waits between rounds, jitters them and times the circuit breakers
+    env: Environment,
+}
+
+impl ProviderChain {
+    pub async fn new(
+        primary: Arc<dyn ChatModel>,
+        config: &LLMConfig,
+        env: &Environment,
+    ) -> anyhow::Result<Self> {
+        let mut providers = vec![CompletionProvider::new(
+            &config.completion.provider,
+            &config.completion.model,
+            primary,
+        )];
+
+        for fallback in config.completion.fallbacks.iter().flatten() {
+            let client = fallback.provider.client(
+                fallback
+                    .api_key
+                    .as_deref()
+                    .unwrap_or(&config.completion.api_key),
+                fallback.custom_url.as_deref(),
+            )?;
+            let model: Arc<dyn ChatModel> =
+                Arc::new(client.completion_model(&fallback.model).await);
+
+            providers.push(CompletionProvider::new(
+                &fallback.provider,
+                &fallback.model,
+                model,
+            ));
+        }
+
+        let retry = config.retry.clone().unwrap_or_default();
+
+        Ok(Self {
+            providers,
+            max_attempts: retry.max_attempts.unwrap_or(DEFAULT_MAX_ATTEMPTS).max(1),
+            base_delay: Duration::from_millis(retry.base_delay_ms.unwrap_or(DEFAULT_BASE_DELAY_MS)),
+            max_delay: Duration::from_millis(retry.max_delay_ms.unwrap_or(DEFAULT_MAX_DELAY_MS)),
+            failure_threshold: retry
+                .failure_threshold
+                .unwrap_or(DEFAULT_FAILURE_THRESHOLD)
+                .max(1),
+            cooldown: Duration::from_secs(retry.cooldown_secs.unwrap_or(DEFAULT_COOLDOWN_SECS)),
+            env: env.clone(),
+        })
+    }
+
+    /// Runs `attempt` against every provider whose circuit is closed until one succeeds.
+    ///
+    /// Bad requests fail right away, unauthorized providers are skipped until their
+    /// cooldown is over, anything else is retried after a jittered exponential backoff.
+    /// Every error of `attempt` counts against the provider, so it should do nothing but
+    /// call the model.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> anyhow::Result<T>
+    where
+        F: FnMut(Arc<dyn ChatModel>) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let mut last_error = None;
+
+        for round in 0..self.max_attempts {
+            if round > 0 {
+                let delay = self.backoff(round - 1);
+                log::warn!(
+                    "every provider failed, retrying in {}ms (attempt {}/{})",
+                    delay.as_millis(),
+                    round + 1,
+                    self.max_attempts
+                );
+                self.env.clock.sleep(delay).await;
+            }
+
+            for provider in &self.providers {
+                if provider.is_open(self.env.now()) {
+                    log::debug!("skipping {}, its circuit is open", provider.label);
+                    continue;
+                }
+
+                match attempt(provider.model.clone()).await {
+                    Ok(value) => {
+                        provider.succeeded();
+                        log::info!("completion served by {}", provider.label);
+
+                        return Ok(value);
+                    }
+                    Err(why) => {
+                        let kind = FailureKind::classify(&why);
+                        log::warn!("{} failed ({kind}): {why:?}", provider.label);
+
+                        let why = why.context(ProviderFailure {
+                            provider: provider.label.clone(),
+                            kind,
+                        });
+
+                        match kind {
+                            FailureKind::BadRequest => return Err(why),
+                            FailureKind::Unauthorized => {
+                                provider.failed(1, self.cooldown, self.env.now());
+                            }
+                            FailureKind::RateLimited | FailureKind::Unavailable => {
+                                provider.failed(
+                                    self.failure_threshold,
+                                    self.cooldown,
+                                    self.env.now(),
+                                );
+                            }
+                        }
+
+                        last_error = Some(why);
+                    }
+                }
+            }
+
+            // waiting won't help if nothing is left to try
+            let now = self.env.now();
+            if self.providers.iter().all(|provider| provider.is_open(now)) {
+                break;
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| {
+            anyhow::anyhow!("every provider is cooling down after failing repeatedly")
+        }))
+    }
+
+    /// "Full jitter" backoff, a random delay up to the exponentially growing cap.
+    pub fn backoff(&self, retry: usize) -> Duration {
+        let cap = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(retry as u32))
+            .min(self.max_delay);
+
+        Duration::from_millis(self.env.rng.random_range(0..=cap.as_millis() as u64))
+    }
+}
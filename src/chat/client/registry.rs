@@ -0,0 +1,208 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use anyhow::anyhow;
+use tokio::sync::Mutex;
+
+use crate::{
+    chat::archive::{backend::LocalBackend, storage::MemoryStorage},
+    config::structure::{LLMConfig, MemoryBackendKind},
+    utils::Environment,
+};
+
+use super::{ChatModel, Embedder, provider::ProviderChain};
+
+/// The embedding model and memory storage every engine shares, so that the vector size
+/// is only probed once.
+#[derive(Clone)]
+pub struct SharedMemory {
+    pub embedding_model: Arc<dyn Embedder>,
+    pub storage: Arc<MemoryStorage>,
+}
+
+/// Clients shared by every engine, only built again once the part of the config they
+/// were built from changes.
+pub struct ModelRegistry {
+    /// The clock and random numbers provider chains retry on
+    env: Environment,
+    /// Keyed on the completion settings, personas can use models of their own
+    providers: Mutex<HashMap<String, Arc<ProviderChain>>>,
+    /// Keyed on the embedding settings and save folder
+    memory: Mutex<HashMap<String, SharedMemory>>,
+    /// Keyed on the folder they save in, as two local backends on the same folder would
+    /// overwrite each other's files
+    local_backends: Mutex<HashMap<Option<PathBuf>, Arc<LocalBackend>>>,
+}
+
+impl ModelRegistry {
+    pub fn new(env: Environment) -> Self {
+        Self {
+            env,
+            providers: Default::default(),
+            memory: Default::default(),
+            local_backends: Default::default(),
+        }
+    }
+
+    /// Returns the provider chain of the configured completion model and its fallbacks.
+    pub async fn providers(&self, config: &LLMConfig) -> anyhow::Result<Arc<ProviderChain>> {
+        let completion = &config.completion;
+        let key = Self::providers_key(config)?;
+
+        let mut providers = self.providers.lock().await;
+        if let Some(chain) = providers.get(&key) {
+            return Ok(chain.clone());
+        }
+
+        log::info!("creating completion client for {}", completion.model);
+
+        let client = completion
+            .provider
+            .client(&completion.api_key, completion.custom_url.as_deref())?;
+        let model: Arc<dyn ChatModel> = Arc::new(client.completion_model(&completion.model).await);
+        let chain = Arc::new(ProviderChain::new(model, config, &self.env).await?);
+
+        providers.insert(key, chain.clone());
+
+        Ok(chain)
+    }
+
+    /// Returns the embedding model and the memory storage, saving local memories in
+    /// `save_folder`.
+    pub async fn memory(
+        &self,
+        config: &LLMConfig,
+        save_folder: Option<&Path>,
+    ) -> anyhow::Result<SharedMemory> {
+        let key = Self::memory_key(config, save_folder)?;
+
+        let mut memory = self.memory.lock().await;
+        if let Some(shared) = memory.get(&key) {
+            return Ok(shared.clone());
+        }
+
+        log::info!("creating embedding client for {}", config.embedding.model);
+
+        let embedding_client = match &config.embedding.provider {
+            Some(provider) => provider.client(
+                config
+                    .embedding
+                    .api_key
+                    .as_deref()
+                    .unwrap_or(&config.completion.api_key),
+                config.embedding.custom_url.as_deref(),
+            )?,
+            None => config.completion.provider.client(
+                &config.completion.api_key,
+                config.completion.custom_url.as_deref(),
+            )?,
+        };
+
+        let embedding_model = match config.embedding.vector_size {
+            Some(vector_size) => embedding_client
+                .embedding_model_with_ndims(&config.embedding.model, vector_size, None)
+                .await
+                .ok_or(anyhow!("failed to create embedding model"))?,
+            None => embedding_client
+                .embedding_model(&config.embedding.model, None)
+                .await
+                .ok_or(anyhow!("failed to create embedding model"))?,
+        };
+        let embedding_model: Arc<dyn Embedder> = Arc::new(embedding_model);
+
+        // test embedding model and obtain true vector size
+        let vector_size = embedding_model.embed_text("a").await?.vec.len() as u64;
+
+        log::info!("vector size: {}", vector_size);
+
+        let storage = match config.embedding.backend.unwrap_or_default() {
+            MemoryBackendKind::Local => MemoryStorage::with_backend(
+                config,
+                self.local_backend(save_folder, vector_size).await?,
+                vector_size,
+            ),
+            MemoryBackendKind::Qdrant => MemoryStorage::new(config, save_folder, vector_size)?,
+        };
+        let shared = SharedMemory {
+            embedding_model,
+            storage: Arc::new(storage),
+        };
+
+        memory.insert(key, shared.clone());
+
+        Ok(shared)
+    }
+
+    /// The local backend saving in `save_folder`, shared by every storage saving there
+    /// unless the vector size changed.
+    async fn local_backend(
+        &self,
+        save_folder: Option<&Path>,
+        vector_size: u64,
+    ) -> anyhow::Result<Arc<LocalBackend>> {
+        let folder = save_folder.map(Path::to_path_buf);
+
+        let mut backends = self.local_backends.lock().await;
+        if let Some(backend) = backends.get(&folder) {
+            if backend.vector_size() == vector_size {
+                return Ok(backend.clone());
+            }
+        }
+
+        let backend = Arc::new(MemoryStorage::local_backend(save_folder, vector_size)?);
+        backends.insert(folder, backend.clone());
+
+        Ok(backend)
+    }
+
+    /// Serves `model` and `memory` to every engine configured with `config`, in place of
+    /// the configured providers.
+    #[cfg(test)]
+    pub async fn insert(
+        &self,
+        config: &LLMConfig,
+        save_folder: Option<&Path>,
+        model: Arc<dyn ChatModel>,
+        memory: SharedMemory,
+    ) -> anyhow::Result<()> {
+        let chain = Arc::new(ProviderChain::new(model, config, &self.env).await?);
+        self.providers
+            .lock()
+            .await
+            .insert(Self::providers_key(config)?, chain);
+        self.memory
+            .lock()
+            .await
+            .insert(Self::memory_key(config, save_folder)?, memory);
+
+        Ok(())
+    }
+
+    fn providers_key(config: &LLMConfig) -> anyhow::Result<String> {
+        let completion = &config.completion;
+
+        Ok(serde_json::to_string(&(
+            &completion.provider,
+            &completion.model,
+            &completion.api_key,
+            &completion.custom_url,
+            &completion.fallbacks,
+            &config.retry,
+        ))?)
+    }
+
+    /// The embedding client falls back to the completion provider and key.
+    fn memory_key(config: &LLMConfig, save_folder: Option<&Path>) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(&(
+            &config.embedding,
+            &config.similarity_threshold,
+            &config.completion.provider,
+            &config.completion.api_key,
+            &config.completion.custom_url,
+            save_folder,
+        ))?)
+    }
+}
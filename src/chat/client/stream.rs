@@ -0,0 +1,88 @@
+const OPENING_TAGS: [&str; 2] = ["<think>", "<reasoning>"];
+const CLOSING_TAGS: [&str; 2] = ["</think>", "</reasoning>"];
+
+/// Progress of a streamed completion, as seen by whoever displays it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamEvent {
+    /// More visible text of the response.
+    Delta(String),
+
+    /// Everything streamed so far should be thrown away, the response is
+    /// being retried or turned into a tool call.
+    Reset,
+}
+
+/// Incrementally strips `<think>` and `<reasoning>` blocks out of streamed
+/// text, holding back anything that could still turn out to be a tag.
+#[derive(Debug, Default)]
+pub struct ThinkFilter {
+    pending: String,
+    thinking: bool,
+    trim_newlines: bool,
+}
+
+impl ThinkFilter {
+    /// Feeds a delta into the filter, returning the part of it that can be
+    /// shown right away.
+    pub fn push(&mut self, text: &str) -> String {
+        self.pending.push_str(text);
+
+        let mut visible = String::new();
+
+        loop {
+            if self.thinking {
+                match find_tag(&self.pending, &CLOSING_TAGS) {
+                    Some((index, length)) => {
+                        log::trace!("Extracted thought process:\n{}", &self.pending[..index]);
+
+                        self.pending.drain(..index + length);
+                        self.thinking = false;
+                        self.trim_newlines = true;
+                    }
+                    None => {
+                        let keep = partial_tag_start(&self.pending, &CLOSING_TAGS);
+                        self.pending.drain(..keep);
+                        break;
+                    }
+                }
+            } else {
+                match find_tag(&self.pending, &OPENING_TAGS) {
+                    Some((index, length)) => {
+                        visible.push_str(&self.pending[..index]);
+
+                        self.pending.drain(..index + length);
+                        self.thinking = true;
+                    }
+                    None => {
+                        let keep = partial_tag_start(&self.pending, &OPENING_TAGS);
+                        visible.extend(self.pending.drain(..keep));
+                        break;
+                    }
+                }
+            }
+        }
+
+        if self.trim_newlines {
+            visible = visible.trim_start_matches('\n').to_string();
+            self.trim_newlines = visible.is_empty();
+        }
+
+        visible
+    }
+}
+
+/// Position and length of the first of `tags` found in `text`.
+fn find_tag(text: &str, tags: &[&str]) -> Option<(usize, usize)> {
+    tags.iter()
+        .filter_map(|tag| text.find(tag).map(|index| (index, tag.len())))
+        .min_by_key(|(index, _)| *index)
+}
+
+/// Index from which the end of `text` could be the beginning of one of `tags`,
+/// or the length of `text` if it cannot.
+fn partial_tag_start(text: &str, tags: &[&str]) -> usize {
+    match text.rfind('<') {
+        Some(index) if tags.iter().any(|tag| tag.starts_with(&text[index..])) => index,
+        _ => text.len(),
+    }
+}
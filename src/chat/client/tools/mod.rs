@@ -1,5 +1,7 @@
 mod recall;
+mod reminder;
 mod store;
 
 pub use recall::*;
+pub use reminder::*;
 pub use store::*;
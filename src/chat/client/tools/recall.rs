@@ -1,12 +1,13 @@
 use std::sync::Arc;
 
 use rig::{completion::ToolDefinition, tool::Tool};
-use rig_dyn::EmbeddingModel;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
-use serenity::all::UserId;
 
-use crate::chat::archive::storage::MemoryStorage;
+use crate::chat::{
+    archive::storage::{MemoryNamespace, MemoryStorage},
+    client::Embedder,
+};
 
 #[derive(Deserialize, Serialize)]
 pub struct Args {
@@ -19,14 +20,14 @@ pub struct Args {
 #[error("Memory Recall error")]
 pub struct MemoryRecallError;
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct MemoryRecall {
     #[serde(skip)]
-    model: Arc<Box<dyn EmbeddingModel>>,
+    model: Arc<dyn Embedder>,
     #[serde(skip)]
     storage: Arc<MemoryStorage>,
     #[serde(skip)]
-    user_id: UserId,
+    namespace: MemoryNamespace,
     #[serde(skip)]
     user_name: String,
     #[serde(skip)]
@@ -35,49 +36,51 @@ pub struct MemoryRecall {
 
 impl MemoryRecall {
     pub fn new(
-        model: Arc<Box<dyn EmbeddingModel>>,
+        model: Arc<dyn Embedder>,
         storage: Arc<MemoryStorage>,
-        user_id: UserId,
+        namespace: MemoryNamespace,
         user_name: String,
         assistant_name: String,
     ) -> Self {
         Self {
             model,
             storage,
-            user_id,
+            namespace,
             user_name,
             assistant_name,
         }
     }
 
-    fn search(&self, args: Args) -> anyhow::Result<Vec<String>> {
+    async fn search(&self, args: Args) -> anyhow::Result<Vec<String>> {
         log::info!("given args: {:?}", serde_json::to_string_pretty(&args)?);
 
-        let embedded = tokio::task::block_in_place(|| {
-            futures::executor::block_on(self.model.embed_text(&args.query))
-        })?
-        .vec
-        .iter()
-        .map(|&x| x as f32)
-        .collect::<Vec<f32>>();
+        let embedded = self
+            .model
+            .embed_text(&args.query)
+            .await?
+            .vec
+            .iter()
+            .map(|&x| x as f32)
+            .collect::<Vec<f32>>();
 
-        tokio::task::block_in_place(|| {
-            futures::executor::block_on(self.storage.search(
+        let memories = self
+            .storage
+            .search(
                 embedded,
-                self.user_id,
+                &self.namespace,
                 args.limit.unwrap_or(5),
                 args.threshold,
-            ))
-        })
-        .map(|mut x| {
-            x.iter_mut()
-                .map(|x| {
-                    x.content
-                        .replace("<user>", self.user_name.as_str())
-                        .replace("<assistant>", self.assistant_name.as_str())
-                })
-                .collect::<Vec<String>>()
-        })
+            )
+            .await?;
+
+        Ok(memories
+            .iter()
+            .map(|x| {
+                x.content
+                    .replace("<user>", self.user_name.as_str())
+                    .replace("<assistant>", self.assistant_name.as_str())
+            })
+            .collect())
     }
 }
 
@@ -115,7 +118,16 @@ impl Tool for MemoryRecall {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         log::info!("[memory_recall] querying vector db with \"{}\"", args.query);
-        let results = self.search(args).map_err(|_| MemoryRecallError)?;
+        // tools have to return futures that are Sync, which the embedder's are not, so the
+        // search runs on a task of its own
+        let recall = self.clone();
+        let results = tokio::spawn(async move { recall.search(args).await })
+            .await
+            .map_err(|_| MemoryRecallError)?
+            .map_err(|why| {
+                log::error!("[memory_recall] failed: {why:?}");
+                MemoryRecallError
+            })?;
         log::info!(
             "[memory_recall] results: {:?}",
             serde_json::to_string_pretty(&results)
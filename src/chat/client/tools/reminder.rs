@@ -0,0 +1,246 @@
+use std::{
+    path::PathBuf,
+    sync::{Arc, RwLock},
+};
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rig::{completion::ToolDefinition, tool::Tool};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use serenity::all::UserId;
+
+use crate::chat::{
+    context::{ConversationScope, UserProfile},
+    reminder::{self, Reminder, Reminders},
+};
+
+/// Why a reminder tool failed, shown to the model so it can tell the user.
+#[derive(Debug, thiserror::Error)]
+#[error("{0}")]
+pub struct ReminderError(String);
+
+impl From<anyhow::Error> for ReminderError {
+    fn from(why: anyhow::Error) -> Self {
+        Self(format!("{why:#}"))
+    }
+}
+
+/// The conversation the reminder tools work on.
+#[derive(Clone)]
+pub struct ReminderScope {
+    reminders: Arc<Reminders>,
+    scope: ConversationScope,
+    /// Whoever the conversation is currently answering, who reminders are set for.
+    speaker: Arc<RwLock<Option<UserId>>>,
+    /// Where the speaker's profile is, which may have a timezone of its own
+    save_folder: Option<PathBuf>,
+    /// The configured timezone, for speakers without one of their own
+    timezone: Tz,
+}
+
+impl ReminderScope {
+    pub fn new(
+        reminders: Arc<Reminders>,
+        scope: ConversationScope,
+        speaker: Arc<RwLock<Option<UserId>>>,
+        save_folder: Option<PathBuf>,
+        timezone: Tz,
+    ) -> Self {
+        Self {
+            reminders,
+            scope,
+            speaker,
+            save_folder,
+            timezone,
+        }
+    }
+
+    fn speaker(&self) -> anyhow::Result<Option<UserId>> {
+        Ok(*self
+            .speaker
+            .read()
+            .map_err(|_| anyhow::anyhow!("speaker lock poisoned"))?)
+    }
+
+    /// The timezone of the speaker, the configured one if nobody in particular is.
+    async fn timezone(&self) -> anyhow::Result<Tz> {
+        Ok(match self.speaker()? {
+            Some(user) => {
+                UserProfile::timezone_of(self.save_folder.as_deref(), user, Some(self.timezone))
+                    .await
+            }
+            None => self.timezone,
+        })
+    }
+
+    fn describe(reminder: &Reminder, timezone: Tz) -> Value {
+        json!({
+            "id": reminder.id,
+            "due": local(reminder.due, timezone),
+            "note": reminder.note,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CreateArgs {
+    when: String,
+    note: String,
+}
+
+pub struct ReminderCreate(pub ReminderScope);
+
+impl Tool for ReminderCreate {
+    const NAME: &'static str = "reminder_create";
+
+    type Error = ReminderError;
+    type Args = CreateArgs;
+    type Output = Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        serde_json::from_value(json!({
+            "name": "reminder_create",
+            "description": "Use to set a reminder when the user asks to be reminded of something, or when you agree to bring something up later. When it is due, you will be prompted to bring it up in the conversation. Times are in the user's timezone.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "when": {
+                        "type": "string",
+                        "description": "When the reminder is due, either a delay like \"30m\", \"2h\" or \"1d12h\", or a time like \"18:30\" or \"2025-06-01 18:30\""
+                    },
+                    "note": {
+                        "type": "string",
+                        "description": "What to remind about, with enough context to bring it up later"
+                    },
+                },
+                "required": ["when", "note"]
+            }
+        }))
+        .expect("Tool Definition")
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let scope = &self.0;
+        let timezone = scope.timezone().await?;
+        let due = reminder::parse_when(&args.when, scope.reminders.now(), timezone)?;
+        let reminder = scope
+            .reminders
+            .add(scope.scope, scope.speaker()?, due, args.note)
+            .await?;
+
+        log::info!(
+            "[reminder_create] reminder {} set for {} in {}",
+            reminder.id,
+            reminder.due,
+            scope.scope
+        );
+
+        Ok(json!({
+            "reminder_create_result": "Reminder set!",
+            "reminder": ReminderScope::describe(&reminder, timezone)
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ListArgs {}
+
+pub struct ReminderList(pub ReminderScope);
+
+impl Tool for ReminderList {
+    const NAME: &'static str = "reminder_list";
+
+    type Error = ReminderError;
+    type Args = ListArgs;
+    type Output = Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        serde_json::from_value(json!({
+            "name": "reminder_list",
+            "description": "Use to list the pending reminders of this conversation, the soonest first.",
+            "parameters": {
+                "type": "object",
+                "properties": {}
+            }
+        }))
+        .expect("Tool Definition")
+    }
+
+    async fn call(&self, _args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let scope = &self.0;
+        let timezone = scope.timezone().await?;
+        let reminders = scope.reminders.list(scope.scope)?;
+
+        Ok(json!({
+            "reminder_list_result": format!("{} pending reminders", reminders.len()),
+            "reminders": reminders
+                .iter()
+                .map(|r| ReminderScope::describe(r, timezone))
+                .collect::<Vec<_>>()
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CancelArgs {
+    id: u64,
+}
+
+pub struct ReminderCancel(pub ReminderScope);
+
+impl Tool for ReminderCancel {
+    const NAME: &'static str = "reminder_cancel";
+
+    type Error = ReminderError;
+    type Args = CancelArgs;
+    type Output = Value;
+
+    async fn definition(&self, _prompt: String) -> ToolDefinition {
+        serde_json::from_value(json!({
+            "name": "reminder_cancel",
+            "description": "Use to cancel a pending reminder when the user no longer needs it. Use reminder_list first if you do not know its id.",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "integer",
+                        "description": "The id of the reminder to cancel"
+                    },
+                },
+                "required": ["id"]
+            }
+        }))
+        .expect("Tool Definition")
+    }
+
+    async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
+        let scope = &self.0;
+
+        match scope
+            .reminders
+            .cancel(scope.scope, args.id, scope.speaker()?)
+            .await?
+        {
+            Some(reminder) => {
+                log::info!(
+                    "[reminder_cancel] reminder {} cancelled in {}",
+                    reminder.id,
+                    scope.scope
+                );
+
+                Ok(json!({
+                    "reminder_cancel_result": "Reminder cancelled",
+                    "reminder": ReminderScope::describe(&reminder, scope.timezone().await?)
+                }))
+            }
+            None => Err(ReminderError(format!("there is no reminder {}", args.id))),
+        }
+    }
+}
+
+fn local(due: DateTime<Utc>, timezone: Tz) -> String {
+    due.with_timezone(&timezone)
+        .format("%Y-%m-%d %H:%M (%Z)")
+        .to_string()
+}
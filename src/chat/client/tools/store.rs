@@ -1,11 +1,13 @@
 use rig::{completion::ToolDefinition, embeddings::Embedding, tool::Tool};
-use rig_dyn::EmbeddingModel;
 use serde::{Deserialize, Serialize};
 use serde_json::{Value, json};
 use serenity::all::UserId;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
-use crate::chat::archive::storage::{Memory, MemoryStorage};
+use crate::chat::{
+    archive::storage::{Memory, MemoryNamespace, MemoryStorage},
+    client::Embedder,
+};
 
 #[derive(Debug, thiserror::Error)]
 #[error("Memory Store error")]
@@ -16,14 +18,17 @@ pub struct Args {
     memory: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct MemoryStore {
     #[serde(skip)]
-    model: Arc<Box<dyn EmbeddingModel>>,
+    model: Arc<dyn Embedder>,
     #[serde(skip)]
     storage: Arc<MemoryStorage>,
     #[serde(skip)]
-    user_id: UserId,
+    namespace: MemoryNamespace,
+    /// Whoever the conversation is currently answering, used to attribute memories.
+    #[serde(skip)]
+    speaker: Arc<RwLock<Option<UserId>>>,
     #[serde(skip)]
     user_name: String,
     #[serde(skip)]
@@ -32,39 +37,40 @@ pub struct MemoryStore {
 
 impl MemoryStore {
     pub fn new(
-        model: Arc<Box<dyn EmbeddingModel>>,
+        model: Arc<dyn Embedder>,
         storage: Arc<MemoryStorage>,
-        user_id: UserId,
+        namespace: MemoryNamespace,
+        speaker: Arc<RwLock<Option<UserId>>>,
         user_name: String,
         assistant_name: String,
     ) -> Self {
         Self {
             model,
             storage,
-            user_id,
+            namespace,
+            speaker,
             user_name,
             assistant_name,
         }
     }
 
-    fn store(&self, memory: &str) -> anyhow::Result<()> {
+    async fn store(&self, memory: &str) -> anyhow::Result<()> {
         let memory = memory
             .replace(self.user_name.as_str(), "<user>")
             .replace(self.assistant_name.as_str(), "<assistant>");
 
-        let Embedding { document, vec } = tokio::task::block_in_place(|| {
-            futures::executor::block_on(self.model.embed_text(&memory))
-        })?;
+        let Embedding { document, vec } = self.model.embed_text(&memory).await?;
 
         let vec = vec.into_iter().map(|x| x as f32).collect::<Vec<f32>>();
 
-        tokio::task::block_in_place(|| {
-            futures::executor::block_on(self.storage.store(
-                Memory::new(document),
-                vec,
-                self.user_id,
-            ))
-        })
+        let speaker = *self
+            .speaker
+            .read()
+            .map_err(|_| anyhow::anyhow!("speaker lock poisoned"))?;
+
+        self.storage
+            .store(Memory::new(document, speaker), vec, &self.namespace)
+            .await
     }
 }
 
@@ -94,7 +100,16 @@ impl Tool for MemoryStore {
 
     async fn call(&self, args: Self::Args) -> Result<Self::Output, Self::Error> {
         log::info!("[memory_store] saving memory:\n\"{}\"", args.memory);
-        let result = self.store(&args.memory).map_err(|_| MemoryStoreError)?;
+        // tools have to return futures that are Sync, which the embedder's are not, so the
+        // memory is stored on a task of its own
+        let store = self.clone();
+        let result = tokio::spawn(async move { store.store(&args.memory).await })
+            .await
+            .map_err(|_| MemoryStoreError)?
+            .map_err(|why| {
+                log::error!("[memory_store] failed: {why:?}");
+                MemoryStoreError
+            })?;
         log::info!(
             "[memory_store] result: {:?}",
             serde_json::to_string_pretty(&result)
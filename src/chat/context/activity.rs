@@ -0,0 +1,209 @@
+use std::{
+    collections::HashMap,
+    fmt::Display,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+
+use super::{MessageRole, message::ChatMessage};
+
+const FILE_NAME: &str = "activity.bin";
+
+/// How busy an hour has to be, compared to the average hour, to count as active.
+const ACTIVE_SHARE: f64 = 0.5;
+
+/// What freewill decides on, kept for every direct conversation so that deciding does
+/// not load it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Activity {
+    /// When the latest message of the conversation was sent
+    pub last_message: Option<DateTime<Utc>>,
+    /// When the user sent each of their messages still in the context, oldest first
+    pub user_messages: Vec<DateTime<Utc>>,
+    /// How many freewill messages were sent since the user last said something
+    pub unanswered: usize,
+}
+
+impl Activity {
+    /// The activity of a conversation, given its selected messages oldest first.
+    pub fn of<'a, I>(messages: I) -> Self
+    where
+        I: DoubleEndedIterator<Item = &'a ChatMessage> + Clone,
+    {
+        Self {
+            last_message: messages.clone().next_back().map(|message| message.sent_at),
+            user_messages: messages
+                .clone()
+                .filter(|message| is_from_user(message))
+                .map(|message| message.sent_at)
+                .collect(),
+            unanswered: messages
+                .rev()
+                .take_while(|message| !is_from_user(message))
+                // every freewill message answers a prompt of its own
+                .filter(|message| message.role() == MessageRole::User && message.freewill)
+                .count(),
+        }
+    }
+}
+
+/// Whether a message was written by the user, freewill and reminder prompts being user
+/// messages too. Drains also flag user messages as freewill, but those have a speaker.
+fn is_from_user(message: &ChatMessage) -> bool {
+    message.role() == MessageRole::User
+        && !message.reminder
+        && (!message.freewill || message.speaker.is_some())
+}
+
+/// The activity of every direct conversation, saved next to the contexts.
+pub struct Activities {
+    folder: Option<PathBuf>,
+    users: Mutex<HashMap<UserId, Activity>>,
+    /// Held while writing, so that writes land in the order they were made
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl Activities {
+    /// Loads the activity of every user, none if it is not saved to disk.
+    pub fn load(folder: Option<&Path>) -> Self {
+        let users = folder
+            .map(|folder| folder.join(FILE_NAME))
+            .filter(|path| path.is_file())
+            .and_then(|path| {
+                File::open(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| Ok(ciborium::from_reader(file)?))
+                    .map_err(|e| log::error!("Failed to load activity: {e}"))
+                    .ok()
+            })
+            .unwrap_or_default();
+
+        Self {
+            folder: folder.map(Path::to_path_buf),
+            users: Mutex::new(users),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// The activity of a user, `None` if their conversation was never recorded.
+    pub fn get(&self, user: UserId) -> Option<Activity> {
+        self.lock().ok()?.get(&user).cloned()
+    }
+
+    /// Records the activity of a user's conversation, only saving it if it changed.
+    pub async fn record(&self, user: UserId, activity: Activity) -> anyhow::Result<()> {
+        {
+            let mut users = self.lock()?;
+            if users.get(&user) == Some(&activity) {
+                return Ok(());
+            }
+
+            users.insert(user, activity);
+        }
+
+        self.persist().await
+    }
+
+    fn lock(&self) -> anyhow::Result<MutexGuard<'_, HashMap<UserId, Activity>>> {
+        self.users
+            .lock()
+            .map_err(|_| anyhow!("activity lock poisoned"))
+    }
+
+    /// Writes the activity to a temporary file and renames it over the old one.
+    async fn persist(&self) -> anyhow::Result<()> {
+        let Some(folder) = &self.folder else {
+            return Ok(());
+        };
+
+        // taken before encoding, so that a later write never loses to an earlier one
+        let _writing = self.writing.lock().await;
+        let mut bytes = vec![];
+        ciborium::into_writer(&*self.lock()?, &mut bytes)?;
+
+        tokio::fs::create_dir_all(folder).await?;
+        let path = folder.join(FILE_NAME);
+        let tmp = path.with_extension("bin.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(tmp, path).await?;
+
+        Ok(())
+    }
+}
+
+/// The hours of the day a user usually talks in, learned from when they sent their
+/// messages.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActiveHours([bool; 24]);
+
+impl ActiveHours {
+    /// How many messages it takes before active hours are learned, every hour counts as
+    /// active until then.
+    pub const MIN_SAMPLES: usize = 20;
+
+    /// Learns the active hours in `timezone`, `None` if there are too few messages to tell.
+    pub fn learn(times: &[DateTime<Utc>], timezone: Tz) -> Option<Self> {
+        if times.len() < Self::MIN_SAMPLES {
+            return None;
+        }
+
+        let mut counts = [0usize; 24];
+        for time in times {
+            counts[time.with_timezone(&timezone).hour() as usize] += 1;
+        }
+
+        // hours right next to busy ones count too, so that a user active at 9 and 11 is
+        // also active at 10
+        let average = times.len() as f64 / 24.0;
+        let mut hours = [false; 24];
+        for (hour, active) in hours.iter_mut().enumerate() {
+            let around = counts[(hour + 23) % 24] + counts[hour] + counts[(hour + 1) % 24];
+            *active = around as f64 / 3.0 >= average * ACTIVE_SHARE;
+        }
+
+        Some(Self(hours))
+    }
+
+    pub fn contains(&self, hour: u32) -> bool {
+        self.0[hour as usize % 24]
+    }
+}
+
+impl Display for ActiveHours {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.iter().all(|active| *active) {
+            return write!(f, "all day");
+        }
+
+        // start from an inactive hour so that no range is split around midnight
+        let Some(offset) = self.0.iter().position(|active| !active) else {
+            return write!(f, "all day");
+        };
+
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for i in 1..=24 {
+            let hour = (offset + i) % 24;
+            match (self.0[hour], start) {
+                (true, None) => start = Some(hour),
+                (false, Some(from)) => {
+                    ranges.push(format!("{from:02}:00-{hour:02}:00"));
+                    start = None;
+                }
+                _ => (),
+            }
+        }
+
+        match ranges.is_empty() {
+            true => write!(f, "never"),
+            false => write!(f, "{}", ranges.join(", ")),
+        }
+    }
+}
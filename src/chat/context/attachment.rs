@@ -0,0 +1,168 @@
+use base64::{Engine, prelude::BASE64_STANDARD};
+use rig::{
+    OneOrMany,
+    message::{ContentFormat, ImageMediaType, Message as RigMessage, UserContent},
+};
+use serde::{Deserialize, Serialize};
+
+/// Something sent along with a user message, kept with it so that it survives
+/// regenerating and restarts.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Attachment {
+    /// Shown as is to models that support images
+    Image {
+        name: String,
+        media_type: String,
+        /// Where Discord serves it, which may have expired since
+        #[serde(default)]
+        url: Option<String>,
+        /// Base64 encoded, only kept for the turn it was sent in
+        #[serde(skip)]
+        data: Option<String>,
+    },
+    /// Inlined into the message
+    Text {
+        name: String,
+        content: String,
+        truncated: bool,
+    },
+    /// Too big or not supported, the model is only told that it exists
+    Omitted { name: String, reason: String },
+}
+
+impl Attachment {
+    pub fn image(name: String, media_type: String, url: String, bytes: &[u8]) -> Self {
+        Self::Image {
+            name,
+            media_type,
+            url: Some(url),
+            data: Some(BASE64_STANDARD.encode(bytes)),
+        }
+    }
+
+    /// Drops the bytes of an image once the model has seen it, so they are not
+    /// kept in memory or saved with the conversation.
+    pub fn forget_data(&mut self) {
+        if let Self::Image { data, .. } = self {
+            *data = None;
+        }
+    }
+
+    /// Decodes a text file, keeping at most `max_chars` of it.
+    pub fn text(name: String, bytes: &[u8], max_chars: usize) -> Self {
+        let content = String::from_utf8_lossy(bytes);
+
+        match content.char_indices().nth(max_chars) {
+            Some((index, _)) => Self::Text {
+                name,
+                content: content[..index].to_string(),
+                truncated: true,
+            },
+            None => Self::Text {
+                name,
+                content: content.into_owned(),
+                truncated: false,
+            },
+        }
+    }
+
+    pub fn omitted(name: String, reason: impl Into<String>) -> Self {
+        Self::Omitted {
+            name,
+            reason: reason.into(),
+        }
+    }
+
+    /// How the attachment reads to the model, images only get a mention.
+    pub fn render(&self) -> String {
+        match self {
+            Self::Image {
+                name, data: None, ..
+            } => format!("Attached image `{name}`, which you saw when it was sent."),
+            Self::Image { name, .. } => format!("Attached image `{name}`."),
+            Self::Text {
+                name,
+                content,
+                truncated,
+            } => format!(
+                "Attached file `{name}`{}:\n```\n{content}\n```",
+                match truncated {
+                    true => " (truncated)",
+                    false => "",
+                }
+            ),
+            Self::Omitted { name, reason } => {
+                format!("Attached file `{name}`, which you cannot see ({reason}).")
+            }
+        }
+    }
+
+    pub fn is_image(&self) -> bool {
+        matches!(self, Self::Image { data: Some(_), .. })
+    }
+
+    pub fn into_content(self) -> UserContent {
+        match self {
+            Self::Image {
+                media_type,
+                data: Some(data),
+                ..
+            } => UserContent::image(
+                data,
+                Some(ContentFormat::Base64),
+                image_media_type(&media_type),
+                None,
+            ),
+            other => UserContent::text(other.render()),
+        }
+    }
+}
+
+/// The media type of an image format models can be shown, if it is one.
+pub fn image_media_type(mime: &str) -> Option<ImageMediaType> {
+    match mime.split(';').next().unwrap_or_default().trim() {
+        "image/jpeg" | "image/jpg" => Some(ImageMediaType::JPEG),
+        "image/png" => Some(ImageMediaType::PNG),
+        "image/gif" => Some(ImageMediaType::GIF),
+        "image/webp" => Some(ImageMediaType::WEBP),
+        "image/heic" => Some(ImageMediaType::HEIC),
+        "image/heif" => Some(ImageMediaType::HEIF),
+        _ => None,
+    }
+}
+
+/// Swaps the images of a message for a mention of them, for models that cannot see.
+pub fn without_images(message: RigMessage) -> RigMessage {
+    match message {
+        RigMessage::User { content } => {
+            let parts = content.into_iter().map(|part| match part {
+                UserContent::Image(_) => {
+                    UserContent::text("Attached an image, which you cannot see.")
+                }
+                other => other,
+            });
+
+            RigMessage::User {
+                // never empty, as it comes from a OneOrMany
+                content: OneOrMany::many(parts).expect("message has content"),
+            }
+        }
+        assistant => assistant,
+    }
+}
+
+/// Appends attachments to a user message as extra content parts.
+pub fn with_attachments(message: RigMessage, attachments: Vec<Attachment>) -> RigMessage {
+    match message {
+        RigMessage::User { content } if !attachments.is_empty() => RigMessage::User {
+            content: OneOrMany::many(
+                content
+                    .into_iter()
+                    .chain(attachments.into_iter().map(Attachment::into_content)),
+            )
+            .expect("message has content"),
+        },
+        other => other,
+    }
+}
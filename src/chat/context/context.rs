@@ -1,16 +1,27 @@
-use std::{fs::File, hash::Hash, path::PathBuf};
+use std::{collections::HashMap, hash::Hash, sync::Arc};
 
 use anyhow::{Result, anyhow};
-use branch_context::{Message, Messages};
+use branch_context::Messages;
+use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
-use regex::Regex;
-use rig::message::{Message as RigMessage, UserContent};
+use rig::message::Message as RigMessage;
 use serde::{Deserialize, Serialize};
 use serenity::all::{ChannelId, Http, Message as SerenityMessage, MessageId, UserId};
 
-use crate::{config::structure::ContextConfig, utils};
-
-use super::{MessageRole, message::ChatMessage};
+use crate::{
+    chat::{prompt::SystemPrompt, reminder::Reminder},
+    config::structure::ContextConfig,
+    utils::{self, Environment, rng::SharedRng},
+};
+
+use super::{
+    ConversationScope, MessageRole, Speaker, UserProfile,
+    activity::{Activities, Activity},
+    attachment::{self, Attachment},
+    message::{ChatMessage, PromptEnvelope},
+    store::{ConversationStore, SavedContextRef},
+    tokens::{HeuristicTokenizer, IMAGE_TOKENS, MESSAGE_OVERHEAD, Tokenizer},
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MessageIdentifier {
@@ -35,19 +46,6 @@ impl Hash for MessageIdentifier {
     }
 }
 
-impl From<Option<(MessageId, ChannelId)>> for MessageIdentifier {
-    fn from(value: Option<(MessageId, ChannelId)>) -> Self {
-        match value {
-            Some((message_id, channel_id)) => Self {
-                message_id: message_id.get(),
-                channel_id: channel_id.get(),
-                random: false,
-                message_ids: vec![message_id.get()],
-            },
-            None => Self::random(),
-        }
-    }
-}
 impl From<(MessageId, ChannelId)> for MessageIdentifier {
     fn from(value: (MessageId, ChannelId)) -> Self {
         Self {
@@ -69,11 +67,12 @@ impl From<(MessageId, ChannelId, Vec<MessageId>)> for MessageIdentifier {
     }
 }
 impl MessageIdentifier {
-    pub fn random() -> Self {
-        let message_id = rand::random();
+    /// An identifier for a message that was never sent on Discord.
+    pub fn random(rng: &SharedRng) -> Self {
+        let message_id = rng.random();
         Self {
             message_id,
-            channel_id: rand::random(),
+            channel_id: rng.random(),
             random: true,
             message_ids: vec![message_id],
         }
@@ -117,128 +116,95 @@ pub struct UserPrompt {
     pub time_since: String,
     pub relevant_memories: Vec<String>,
     pub system_note: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub speaker: Option<Speaker>,
     #[serde(skip)]
     pub freewill: bool,
+    #[serde(skip)]
+    pub reminder: bool,
+    /// Sent to the model as parts of their own rather than in the JSON
+    #[serde(skip)]
+    pub attachments: Vec<Attachment>,
 }
 
 pub struct ChatContext {
     messages: IndexMap<MessageIdentifier, Messages<ChatMessage>>,
-    save_path: Option<PathBuf>,
+    store: Option<Arc<dyn ConversationStore>>,
+    scope: ConversationScope,
+    tokenizer: Arc<dyn Tokenizer>,
+    /// Narrative of everything that was drained out of the context so far
+    summary: Option<String>,
+    /// Persona the conversation is held with, `None` being the default one
+    persona: Option<String>,
+    /// Where the conversation last took place, for the bot to reach out in
+    channel: Option<ChannelId>,
+    /// Where the time and random message ids come from
+    env: Environment,
+    /// Where the activity of direct conversations is recorded for freewill
+    activities: Arc<Activities>,
+    /// Profiles of everyone who spoke so far, so that not every message reads them
+    profiles: HashMap<UserId, UserProfile>,
     pub config: ContextConfig,
 }
-impl TryInto<ChatMessage> for UserPrompt {
-    type Error = anyhow::Error;
-
-    fn try_into(mut self) -> Result<ChatMessage, Self::Error> {
-        let mut message = String::new();
-
-        if self.system_note.is_some() {
-            message.push_str(&format!(
-                "System Note:\n{}\n\n",
-                self.system_note.as_ref().unwrap()
-            ));
-        }
-
-        message.push_str(&format!(
-            "The current time is {}, {} since the last message before this one.\n\n",
-            self.current_time, self.time_since
-        ));
-
-        if self.relevant_memories.len() > 0 {
-            message.push_str(&format!(
-                "You have recalled the following memories:\n{}\n\n",
-                self.relevant_memories
-                    .iter_mut()
-                    .map(|m| format!("```memory\n{}\n```", m))
-                    .collect::<Vec<_>>()
-                    .join("\n")
-            ));
-        }
-
-        if let Some(content) = self.content {
-            message.push_str(&format!(
-                "Respond to the following message:\n{}\n\n",
-                content
-            ));
-        }
-
-        // remove the last double newline
-        message.pop();
-        message.pop();
-
-        log::trace!("message:\n{}\n\n", message);
-
-        // let mut message = ChatMessage::user(serde_json::to_string(&self)?);
-        let mut message = ChatMessage::user(message);
-
-        message.freewill = self.freewill;
-        Ok(message)
+impl From<UserPrompt> for ChatMessage {
+    fn from(prompt: UserPrompt) -> Self {
+        let mut message = ChatMessage::user(prompt.content.unwrap_or_default());
+
+        message.freewill = prompt.freewill;
+        message.reminder = prompt.reminder;
+        message.speaker = prompt.speaker;
+        // the bytes of images are only sent with the prompt itself, not kept in the history
+        message.attachments = prompt.attachments;
+        message
+            .attachments
+            .iter_mut()
+            .for_each(Attachment::forget_data);
+        message.prompt = Some(PromptEnvelope {
+            current_time: prompt.current_time,
+            time_since: prompt.time_since,
+            relevant_memories: prompt.relevant_memories,
+            system_note: prompt.system_note,
+        });
+
+        log::trace!("message:\n{}\n\n", message.text().unwrap_or_default());
+
+        message
     }
 }
 impl TryInto<RigMessage> for UserPrompt {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<RigMessage, Self::Error> {
-        Ok(RigMessage::user(serde_json::to_string(&self)?))
+        Ok(attachment::with_attachments(
+            RigMessage::user(serde_json::to_string(&self)?),
+            self.attachments,
+        ))
     }
 }
 impl TryFrom<ChatMessage> for UserPrompt {
     type Error = anyhow::Error;
 
     fn try_from(value: ChatMessage) -> Result<Self, Self::Error> {
-        let regex = Regex::new(
-            r"^(?:System Note:\n((?:.|\n)*)(?:\n\n)?)?(?:The current time is (.*), (.*) since the last message before this one\.(?:\n\n)?)(?:You have recalled the following memories:((?:.|\n)*)(?:\n\n)?)?(?:Respond to the following message:\n((?:.|\n)*)(?:\n\n)?)$",
-        )?;
-
-        let content = value.content().unwrap_or_default();
-        let matches = regex
-            .captures(&content)
-            .ok_or(anyhow::anyhow!("message did not match regex pattern"))?;
-
-        let system_note = matches.get(1).map(|m| m.as_str().trim().to_string());
-        let current_time = matches
-            .get(2)
-            .map(|m| m.as_str().trim().to_string())
-            .ok_or(anyhow::anyhow!("current time not found in message"))?;
-        let time_since = matches
-            .get(3)
-            .map(|m| m.as_str().trim().to_string())
-            .ok_or(anyhow::anyhow!("time since not found in message"))?;
-        let relevant_memories = matches
-            .get(4)
-            .map(|m| m.as_str().trim())
-            .map(|memories_text| {
-                let memory_regex = Regex::new(r"```memory\n*((?:.|\n)*?)\n*```").unwrap();
-                let mut memories = Vec::new();
-
-                for cap in memory_regex.captures_iter(memories_text) {
-                    if let Some(memory_content) = cap.get(1) {
-                        let memory_content = memory_content.as_str().trim().to_string();
-                        if memory_content.len() > 0 {
-                            memories.push(memory_content);
-                        }
-                    }
-                }
-
-                memories
-            })
-            .unwrap_or_default();
-        let content = matches.get(5).map(|m| m.as_str().trim().to_string());
+        let envelope = value
+            .prompt
+            .clone()
+            .ok_or(anyhow::anyhow!("message is not a user prompt"))?;
 
-        // serde_json::from_str::<Self>(
-        //     &value
-        //         .content()
-        //         .ok_or(anyhow::anyhow!("message does not have a content"))?,
-        // )
-        // .map_err(|why| anyhow::anyhow!("failed to deserialize user prompt: {why:?}"))
+        // an empty prompt is only ever sent to nudge the bot
+        let content = value
+            .content()
+            .filter(|content| !content.is_empty() || !value.attachments.is_empty());
 
         Ok(Self {
             content,
-            current_time,
-            time_since,
-            relevant_memories,
-            system_note,
+            current_time: envelope.current_time,
+            time_since: envelope.time_since,
+            relevant_memories: envelope.relevant_memories,
+            system_note: envelope.system_note,
+            speaker: value.speaker,
             freewill: value.freewill,
+            reminder: value.reminder,
+            attachments: value.attachments,
         })
     }
 }
@@ -247,115 +213,207 @@ pub struct ContextWindow {
     pub user_prompt: Option<UserPrompt>,
     pub system_prompt: String,
     pub history: Vec<ChatMessage>,
+    /// Drained messages that still have to be stored as long term memories
     pub overflow: Option<Vec<ChatMessage>>,
+    /// Every drained message, oldest first, for the story so far
+    pub drained: Vec<ChatMessage>,
 }
 
 impl ChatContext {
-    pub async fn new(config: &ContextConfig, user_id: UserId) -> Self {
+    pub async fn new(
+        config: &ContextConfig,
+        scope: ConversationScope,
+        env: Environment,
+        activities: Arc<Activities>,
+        store: Option<Arc<dyn ConversationStore>>,
+    ) -> Self {
         log::info!("creating new context");
 
-        let save_path = &config
-            .save_to_disk_folder
-            .as_ref()
-            .map(|path| {
-                if path.is_file() {
-                    std::fs::remove_file(&path)
-                        .map_err(|e| {
-                            log::error!("Failed to remove file: {e}");
-                            e
-                        })
-                        .ok()?;
-                }
-
-                std::fs::create_dir_all(&path)
-                    .map_err(|e| {
-                        log::error!("Failed to create dir: {e}");
-                        e
-                    })
-                    .ok()?;
-
-                Some(path.join(format!("context-{}.bin", user_id)))
-            })
-            .flatten();
-
-        let result = match save_path {
-            Some(path) => {
-                File::open(path)
-                    .ok()
-                    .and_then(|mut file| {
-                        ciborium::from_reader(&mut file)
-                            .map_err(|e| {
-                                log::error!("Failed to deserialize context: {e}");
-                                e
-                            })
-                            .ok()
-                    })
-                    .map(
-                        async |messages: IndexMap<MessageIdentifier, Messages<ChatMessage>>| {
-                            log::info!(
-                                "Recovered context with {} messages for user {}",
-                                messages.len(),
-                                user_id
-                            );
-
-                            // get latest message and reenable buttons
-                            let context = Self {
-                                messages,
-                                save_path: save_path.clone(),
-                                config: config.clone(),
-                            };
-
-                            Ok(context)
-                        },
-                    )
-            }
+        let saved = match &store {
+            Some(store) => store
+                .load(scope)
+                .await
+                .map_err(|why| log::error!("Failed to load context for {scope}: {why:?}"))
+                .ok()
+                .flatten(),
             None => None,
         };
 
-        match result {
-            Some(future) => future.await.unwrap_or_else(|_: anyhow::Error| Self {
-                messages: IndexMap::new(),
-                save_path: save_path.clone(),
-                config: config.clone(),
-            }),
+        match saved {
+            Some(saved) => {
+                log::info!(
+                    "Recovered context with {} messages for {}",
+                    saved.messages.len(),
+                    scope
+                );
+
+                // contexts saved before the channel was kept still have it in their ids
+                let channel = saved.channel.or_else(|| {
+                    saved
+                        .messages
+                        .keys()
+                        .rev()
+                        .find(|id| !id.random)
+                        .map(|id| id.channel())
+                });
+
+                Self {
+                    messages: saved.messages,
+                    store,
+                    scope,
+                    config: config.clone(),
+                    tokenizer: Arc::new(HeuristicTokenizer),
+                    summary: saved.summary,
+                    persona: saved.persona,
+                    channel,
+                    env,
+                    activities,
+                    profiles: HashMap::new(),
+                }
+            }
             None => Self {
                 messages: IndexMap::new(),
-                save_path: save_path.clone(),
+                store,
+                scope,
                 config: config.clone(),
+                tokenizer: Arc::new(HeuristicTokenizer),
+                summary: None,
+                persona: None,
+                channel: None,
+                env,
+                activities,
+                profiles: HashMap::new(),
             },
         }
     }
 
+    /// The profile of a user, read once and kept until [ChatContext::forget_profile].
+    pub async fn profile(&mut self, user: UserId) -> &UserProfile {
+        if !self.profiles.contains_key(&user) {
+            let profile = match self.config.save_to_disk_folder.as_deref() {
+                Some(folder) => UserProfile::load(folder, user).await,
+                None => UserProfile::default(),
+            };
+            self.profiles.insert(user, profile);
+        }
+
+        &self.profiles[&user]
+    }
+
+    /// Drops the kept profile of a user, after it changed.
+    pub fn forget_profile(&mut self, user: UserId) {
+        self.profiles.remove(&user);
+    }
+
+    /// Goes by the name the speaker picked in their profile, if any.
+    pub async fn with_profile(&mut self, speaker: Speaker) -> Speaker {
+        let profile = self.profile(speaker.id).await;
+        speaker.with_profile(profile)
+    }
+
+    /// Moves the context to another store, it is only written there on its next save.
+    pub fn set_store(&mut self, store: Option<Arc<dyn ConversationStore>>) {
+        self.store = store;
+    }
+
+    /// Writes the context to its store, if it is saved at all.
+    pub async fn save(&self) -> anyhow::Result<()> {
+        if let Some(store) = &self.store {
+            log::debug!(
+                "Saving context with {} messages for {}",
+                self.messages.len(),
+                self.scope
+            );
+
+            store
+                .save(
+                    self.scope,
+                    &SavedContextRef {
+                        messages: &self.messages,
+                        summary: self.summary.as_deref(),
+                        persona: self.persona.as_deref(),
+                        channel: self.channel,
+                    },
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// Saves the context once a turn is over, unless configured not to. The activity of
+    /// direct conversations is recorded either way.
+    pub async fn autosave(&self) {
+        self.record_activity().await;
+
+        let after_turn = self
+            .config
+            .autosave
+            .as_ref()
+            .and_then(|autosave| autosave.after_turn)
+            .unwrap_or(true);
+
+        if after_turn {
+            if let Err(why) = self.save().await {
+                log::error!("failed to autosave context for {}: {why:?}", self.scope);
+            }
+        }
+    }
+
     pub async fn shutdown(&self) -> anyhow::Result<()> {
-        if let Some(path) = &self.save_path {
+        if self.store.is_some() {
             log::info!(
-                "Saving context with {} messages to {}",
+                "Saving context with {} messages for {}",
                 self.messages.len(),
-                path.display()
+                self.scope
             );
+        }
+
+        self.save().await
+    }
 
-            let file = File::options().write(true).create(true).open(path)?;
-            file.set_len(0)?;
-            ciborium::into_writer(&self.messages, file)?;
+    pub async fn clear(&mut self) -> anyhow::Result<()> {
+        self.messages.clear();
+        self.summary = None;
+        self.record_activity().await;
+        if let Some(store) = &self.store {
+            store.remove(self.scope).await?;
         }
 
         Ok(())
     }
 
-    pub fn clear(&mut self) {
-        self.messages.clear();
-        if let Some(path) = &self.save_path {
-            std::fs::remove_file(path).ok();
+    async fn record_activity(&self) {
+        if let ConversationScope::Direct(user) = self.scope {
+            if let Err(why) = self.activities.record(user, self.activity()).await {
+                log::error!("failed to record the activity of {user}: {why:?}");
+            }
         }
     }
 
-    pub fn add_message(
-        &mut self,
-        message: impl Into<Message<ChatMessage>>,
-        id: impl Into<MessageIdentifier>,
-    ) {
-        let message = Messages::new(message.into());
-        self.messages.insert(id.into(), message);
+    /// Adds a message as sent just now, on the conversation's clock.
+    pub fn add_message(&mut self, message: ChatMessage, id: impl Into<MessageIdentifier>) {
+        let id = id.into();
+        if !id.random {
+            self.channel = Some(id.channel());
+        }
+
+        let message = Messages::new(message.stamped(self.now()).into());
+        self.messages.insert(id, message);
+    }
+
+    /// Where the conversation last took place.
+    pub fn channel(&self) -> Option<ChannelId> {
+        self.channel
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.env.now()
+    }
+
+    /// An identifier for a message that is not on Discord, like a freewill prompt.
+    pub fn random_id(&self) -> MessageIdentifier {
+        MessageIdentifier::random(&self.env.rng)
     }
 
     pub fn add_user_message(
@@ -363,11 +421,25 @@ impl ChatContext {
         message: UserPrompt,
         id: impl Into<MessageIdentifier>,
     ) -> anyhow::Result<()> {
-        self.add_message(TryInto::<ChatMessage>::try_into(message)?, id);
+        self.add_message(message.into(), id);
 
         Ok(())
     }
 
+    /// Records a group message the bot was not asked to answer, so that it
+    /// is still part of the conversation once it is.
+    pub fn observe_message(
+        &mut self,
+        content: String,
+        speaker: Speaker,
+        id: impl Into<MessageIdentifier>,
+    ) {
+        let mut message = ChatMessage::user(format!("{}: {}", speaker.name, content));
+        message.speaker = Some(speaker);
+
+        self.add_message(message, id);
+    }
+
     pub fn latest(&self) -> Option<&Messages<ChatMessage>> {
         self.messages.last().map(|(_, m)| m)
     }
@@ -389,6 +461,15 @@ impl ChatContext {
             .find(|(_, m)| m.selected().role() == role)
     }
 
+    /// The Discord messages the users sent in the context.
+    pub fn user_message_ids(&self) -> Vec<MessageId> {
+        self.messages
+            .iter()
+            .filter(|(id, messages)| !id.random && messages.selected().role() == MessageRole::User)
+            .map(|(id, _)| id.message())
+            .collect()
+    }
+
     #[allow(unused)]
     /// Returns the message with the given id (not index, if you want the index use [ChatContext::get])
     pub fn find(&self, id: impl Into<MessageIdentifier>) -> Option<&Messages<ChatMessage>> {
@@ -449,39 +530,165 @@ impl ChatContext {
         Ok(())
     }
 
-    /// If STM is full, drain until STM is x% of max_stm
-    async fn drain_overflow(&mut self) -> Option<Vec<ChatMessage>> {
-        if self.messages.len() >= self.config.max_stm {
-            let to_remove = self.messages.len()
-                - (self
-                    .config
-                    .stm_drain_percentage
-                    .map(|p| 1.0 - p)
-                    .unwrap_or(0.8) // default to 80% drain
-                    * self.config.max_stm as f64)
-                    .round() as usize;
-            log::info!("context close to or full, draining {to_remove} messages");
-
-            // set the latest message to be a "freewill" message
-            // (even though it's not, just mark it as the delimiter for any next drains)
-            if let Some(latest) = self.latest_mut() {
-                latest.mut_selected().freewill = true;
-            }
+    /// If STM is full, drain until STM is x% of max_stm, or x% of the history budget when
+    /// budgeting by tokens.
+    ///
+    /// Returns the drained messages that were not stored as memories yet, newest first, and
+    /// every drained message, oldest first.
+    async fn drain_overflow(
+        &mut self,
+        reserved_tokens: usize,
+    ) -> Option<(Vec<ChatMessage>, Vec<ChatMessage>)> {
+        let keep = 1.0 - self.config.stm_drain_percentage.unwrap_or(0.2); // default to 20% drain
+
+        let to_remove = match self.history_budget(reserved_tokens) {
+            Some(budget) => {
+                let tokens = self.history_tokens();
+                if tokens < budget {
+                    return None;
+                }
 
-            Some(
+                // drop the oldest messages until what's left fits in x% of the budget
+                let target = (keep * budget as f64).round() as usize;
+                let mut remaining = tokens;
                 self.messages
-                    .drain(0..to_remove)
-                    .rev()
-                    // only return all the way until a freewill message
-                    .map_while(|(_, messages)| {
-                        let message = messages.into_selected();
-                        (!message.freewill).then_some(message)
+                    .values()
+                    .take_while(|messages| {
+                        let fits = remaining <= target;
+                        remaining -= self.message_tokens(messages.selected());
+                        !fits
                     })
-                    .collect::<Vec<ChatMessage>>(),
-            )
-        } else {
-            None
+                    .count()
+            }
+            None if self.messages.len() >= self.config.max_stm => {
+                self.messages.len() - (keep * self.config.max_stm as f64).round() as usize
+            }
+            None => return None,
+        };
+        if to_remove == 0 {
+            return None;
+        }
+        log::info!("context close to or full, draining {to_remove} messages");
+
+        // set the latest message to be a "freewill" message
+        // (even though it's not, just mark it as the delimiter for any next drains)
+        if let Some(latest) = self.latest_mut() {
+            latest.mut_selected().freewill = true;
+        }
+
+        let drained = self
+            .messages
+            .drain(0..to_remove)
+            .map(|(_, messages)| messages.into_selected())
+            .collect::<Vec<ChatMessage>>();
+
+        let overflow = drained
+            .iter()
+            .rev()
+            // only return all the way until a freewill message
+            .map_while(|message| (!message.freewill).then(|| message.clone()))
+            .collect::<Vec<ChatMessage>>();
+
+        Some((overflow, drained))
+    }
+
+    /// Tokens left for the history once the system prompt, memories and `reserved_tokens`
+    /// are accounted for, if budgeting by tokens.
+    fn history_budget(&self, reserved_tokens: usize) -> Option<usize> {
+        let budget = self.config.token_budget.as_ref()?;
+        let memories = (budget.max_tokens as f64 * budget.memories_share.unwrap_or(0.1)) as usize;
+
+        Some(
+            budget
+                .max_tokens
+                .saturating_sub(memories)
+                .saturating_sub(reserved_tokens),
+        )
+    }
+
+    /// Tokens taken up by the system prompt, warning when it goes over its share.
+    fn system_tokens(&self, system_prompt: &str) -> usize {
+        let tokens = self.tokenizer.count(system_prompt) + MESSAGE_OVERHEAD;
+
+        if let Some(budget) = &self.config.token_budget {
+            let share = (budget.max_tokens as f64 * budget.system_share.unwrap_or(0.2)) as usize;
+            if tokens > share {
+                log::warn!(
+                    "system prompt takes {tokens} tokens, over its share of {share}, the history will have less room"
+                );
+            }
+
+            return tokens.max(share);
         }
+
+        tokens
+    }
+
+    fn history_tokens(&self) -> usize {
+        self.messages
+            .values()
+            .map(|messages| self.message_tokens(messages.selected()))
+            .sum()
+    }
+
+    fn message_tokens(&self, message: &ChatMessage) -> usize {
+        let steps = message
+            .tool_steps
+            .iter()
+            .map(|step| {
+                let json = serde_json::to_string(step).unwrap_or_default();
+                self.tokenizer.count(&json) + 2 * MESSAGE_OVERHEAD
+            })
+            .sum::<usize>();
+
+        steps
+            + self.tokenizer.count(&message.text().unwrap_or_default())
+            + self.attachment_tokens(&message.attachments)
+            + MESSAGE_OVERHEAD
+    }
+
+    fn attachment_tokens(&self, attachments: &[Attachment]) -> usize {
+        attachments
+            .iter()
+            .map(|attachment| match attachment.is_image() {
+                true => IMAGE_TOKENS,
+                false => self.tokenizer.count(&attachment.render()),
+            })
+            .sum()
+    }
+
+    /// Drops the least relevant recalled memories that do not fit in their share of the
+    /// token budget, if budgeting by tokens.
+    pub fn fit_memories(&self, prompt: &mut UserPrompt) {
+        let Some(budget) = &self.config.token_budget else {
+            return;
+        };
+        let mut remaining =
+            (budget.max_tokens as f64 * budget.memories_share.unwrap_or(0.1)) as usize;
+
+        // memories are recalled most relevant first
+        let recalled = prompt.relevant_memories.len();
+        prompt.relevant_memories.retain(|memory| {
+            let tokens = self.tokenizer.count(memory);
+            let fits = tokens <= remaining;
+            if fits {
+                remaining -= tokens;
+            }
+            fits
+        });
+
+        if prompt.relevant_memories.len() < recalled {
+            log::info!(
+                "dropped {} recalled memories over the token budget",
+                recalled - prompt.relevant_memories.len()
+            );
+        }
+    }
+
+    /// Replaces the tokenizer used to budget the context, [HeuristicTokenizer] by default.
+    #[allow(unused)]
+    pub fn set_tokenizer(&mut self, tokenizer: Arc<dyn Tokenizer>) {
+        self.tokenizer = tokenizer;
     }
 
     async fn get_messages(&self) -> Vec<ChatMessage> {
@@ -504,46 +711,65 @@ impl ChatContext {
             .collect::<Vec<_>>()
     }
 
-    pub async fn get_context(&mut self, user_prompt: Option<String>) -> Result<ContextWindow> {
+    pub async fn get_context(
+        &mut self,
+        user_prompt: Option<String>,
+        speaker: Option<Speaker>,
+        attachments: Vec<Attachment>,
+    ) -> Result<ContextWindow> {
+        let system_prompt = self.system_prompt(speaker.as_ref());
+
         let user_prompt: Option<UserPrompt> = match user_prompt {
             Some(prompt) => Some(UserPrompt {
                 content: Some(prompt),
-                current_time: self.config.system.get_time(),
+                current_time: self.config.system.get_time(self.now()),
                 relevant_memories: vec![],
                 time_since: utils::time_to_string(self.time_since_last()),
                 system_note: None,
+                speaker,
                 freewill: false,
+                reminder: false,
+                attachments,
             }),
             None => None,
         };
 
         if self.messages.is_empty() {
-            let system_prompt = self
-                .config
-                .system
-                .clone()
-                .build(chrono::Duration::seconds(0));
-
             return Ok(ContextWindow {
                 history: vec![],
                 overflow: None,
+                drained: vec![],
                 system_prompt: system_prompt.to_string(),
                 user_prompt,
             });
         }
 
+        // the prompt has to fit alongside the history
+        let reserved = self.system_tokens(&system_prompt.to_string())
+            + user_prompt
+                .as_ref()
+                .map(|prompt| {
+                    let content = prompt.content.as_deref().unwrap_or_default();
+                    self.tokenizer.count(content)
+                        + self.attachment_tokens(&prompt.attachments)
+                        + MESSAGE_OVERHEAD
+                })
+                .unwrap_or_default();
+
+        let (overflow, drained) = match self.drain_overflow(reserved).await {
+            Some((overflow, drained)) => (Some(overflow), drained),
+            None => (None, vec![]),
+        };
+
         // Add the messages
         let ctx = self.get_messages().await;
 
-        let drained = self.drain_overflow().await;
-
-        let system_prompt = self.config.system.clone().build(self.time_since_last());
-
         Ok(ContextWindow {
             user_prompt,
             system_prompt: system_prompt.to_string(),
             history: ctx,
-            overflow: drained,
+            overflow,
+            drained,
         })
     }
 
@@ -567,21 +793,14 @@ impl ChatContext {
             .cloned()
             .collect::<Vec<_>>();
 
-        // Extract the last text message from the user as the prompt
+        // Extract the last prompt from the user as the prompt
         let last_message = ctx
             .iter()
-            .rposition(|msg| {
-                matches!(
-                    msg.inner,
-                    RigMessage::User {
-                        content: ref c
-                    } if matches!(c.first(), UserContent::Text(_))
-                )
-            })
+            .rposition(|msg| msg.prompt.is_some())
             .map(|idx| ctx.remove(idx))
-            .ok_or_else(|| anyhow::anyhow!("No user text messages found for prompting"))?;
+            .ok_or_else(|| anyhow::anyhow!("No user prompts found for prompting"))?;
 
-        let system_prompt = self.config.system.clone().build(self.time_since_last());
+        let system_prompt = self.system_prompt(last_message.speaker.as_ref());
 
         // if let Some(pos) = context.iter().rposition(|m| m.role == "assistant") {
         //     context.remove(pos);
@@ -592,16 +811,53 @@ impl ChatContext {
             history: ctx,
             system_prompt: system_prompt.to_string(),
             overflow: None, // there is no overflow when regenerating
+            drained: vec![],
         })
     }
 
     pub async fn freewill_context(&mut self, user_prompt: Option<String>) -> Result<ContextWindow> {
+        self.nudge_context(
+            user_prompt,
+            "Please attempt to pull the user back into the conversation, making sure to keep the same tone and style as you normally would, following all previous instructions, yet keeping the time difference in mind. Your response should only contain the actual response, not your thoughts or anything else.".to_string(),
+            false,
+        )
+        .await
+    }
+
+    /// A context for bringing up a reminder that came due.
+    pub async fn reminder_context(&mut self, reminder: &Reminder) -> Result<ContextWindow> {
+        let asked_by = match (self.scope, reminder.user) {
+            (ConversationScope::Direct(_), _) => self.config.system.user_name.clone(),
+            (_, Some(user)) => format!("<@{user}>"),
+            (_, None) => "the channel".to_string(),
+        };
+
+        self.nudge_context(
+            None,
+            format!(
+                "A reminder {asked_by} asked you to set is due now: \"{}\". Bring it up in the conversation, making sure to keep the same tone and style as you normally would, following all previous instructions. Your response should only contain the actual response, not your thoughts or anything else.",
+                reminder.note
+            ),
+            true,
+        )
+        .await
+    }
+
+    /// A context for the bot to speak up on its own, `system_note` telling it why. Reminders
+    /// are flagged as such rather than as freewill.
+    async fn nudge_context(
+        &mut self,
+        user_prompt: Option<String>,
+        system_note: String,
+        reminder: bool,
+    ) -> Result<ContextWindow> {
         let ContextWindow {
             history,
             overflow,
+            drained,
             system_prompt,
             ..
-        } = self.get_context(user_prompt).await?;
+        } = self.get_context(user_prompt, None, vec![]).await?;
 
         // let message = ChatMessage::user(format!(
         //     "*it's been around {} since you last said something, and the user did not respond. your next response should attempt to pull the user back into the conversation. please respond once again, making sure to keep the same tone and style as you normally would, following all previous instructions, yet keeping the time difference in mind. your response should only contain the actual response, not your thoughts or anything else.*\n\n\"...\"",
@@ -609,33 +865,85 @@ impl ChatContext {
         // ));
         let message = UserPrompt {
             content: None,
-            current_time: self.config.system.get_time(),
+            current_time: self.config.system.get_time(self.now()),
             relevant_memories: vec![],
             time_since: utils::time_to_string(self.time_since_last()),
-            system_note: Some(
-                "Please attempt to pull the user back into the conversation, making sure to keep the same tone and style as you normally would, following all previous instructions, yet keeping the time difference in mind. Your response should only contain the actual response, not your thoughts or anything else.".to_string(),
-            ),
-            freewill: true,
+            system_note: Some(system_note),
+            speaker: None,
+            freewill: !reminder,
+            reminder,
+            attachments: vec![],
         };
 
-        // id-less message
-        self.add_message(TryInto::<ChatMessage>::try_into(message.clone())?, None);
-
         Ok(ContextWindow {
             user_prompt: Some(message),
             history,
             system_prompt,
             overflow,
+            drained,
         })
     }
 
+    /// Builds the system prompt, listing everyone who took part in the
+    /// conversation when it isn't a direct message.
+    pub fn system_prompt(&self, speaker: Option<&Speaker>) -> SystemPrompt {
+        let mut builder = self.config.system.clone();
+        builder.story_so_far = self.summary.clone();
+
+        if !self.scope.is_direct() {
+            let mut participants = self
+                .messages
+                .values()
+                .filter_map(|messages| messages.selected().speaker.as_ref())
+                .chain(speaker)
+                .map(|speaker| speaker.name.clone())
+                .collect::<Vec<_>>();
+            participants.sort();
+            participants.dedup();
+
+            builder.participants = Some(participants);
+        }
+
+        builder.build(self.now(), self.time_since_last())
+    }
+
+    /// The story so far, if anything was drained yet.
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    pub fn set_summary(&mut self, summary: String) {
+        self.summary = Some(summary);
+    }
+
+    pub fn persona(&self) -> Option<&str> {
+        self.persona.as_deref()
+    }
+
+    pub fn set_persona(&mut self, persona: Option<String>) {
+        self.persona = persona;
+    }
+
+    pub fn count_tokens(&self, text: &str) -> usize {
+        self.tokenizer.count(text)
+    }
+
+    pub fn scope(&self) -> ConversationScope {
+        self.scope
+    }
+
     pub fn time_since_last(&self) -> chrono::Duration {
         let last = match self.latest() {
             Some(last) => last,
             None => return chrono::Duration::seconds(0),
         };
 
-        chrono::Utc::now() - last.selected().sent_at
+        self.now() - last.selected().sent_at
+    }
+
+    /// What freewill decides on, see [Activity].
+    pub fn activity(&self) -> Activity {
+        Activity::of(self.messages.values().map(|messages| messages.selected()))
     }
 
     #[allow(unused)]
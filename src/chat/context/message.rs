@@ -1,15 +1,199 @@
-use std::fmt::Display;
+use std::{fmt::Display, sync::LazyLock};
 
 use branch_context::{Message, Messages};
 use chrono::{DateTime, Utc};
+use regex::Regex;
 use rig::message::{AssistantContent, Message as RigMessage, UserContent};
 use serde::{Deserialize, Serialize};
 
+use super::{
+    Speaker,
+    attachment::{self, Attachment},
+};
+
+/// How prompts were rendered before they had an envelope, `None` if it does not compile.
+static LEGACY_PROMPT: LazyLock<Option<Regex>> = LazyLock::new(|| {
+    compile(
+        r"^(?:System Note:\n((?:.|\n)*)(?:\n\n)?)?(?:The current time is (.*), (.*) since the last message before this one\.(?:\n\n)?)(?:You have recalled the following memories:((?:.|\n)*)(?:\n\n)?)?(?:Respond to the following message(?: from (.*?))?:\n((?:.|\n)*)(?:\n\n)?)$",
+    )
+});
+static LEGACY_MEMORY: LazyLock<Option<Regex>> =
+    LazyLock::new(|| compile(r"```memory\n*((?:.|\n)*?)\n*```"));
+
+fn compile(pattern: &str) -> Option<Regex> {
+    Regex::new(pattern)
+        .map_err(|why| log::error!("Failed to compile the legacy prompt pattern: {why}"))
+        .ok()
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(from = "StoredChatMessage")]
 pub struct ChatMessage {
+    /// The message as written, prompts only get rendered when building a request.
     pub inner: RigMessage,
     pub sent_at: DateTime<Utc>,
     pub freewill: bool,
+    /// Whether the bot spoke up to bring up a reminder, which unlike freewill does not
+    /// wait on the user to answer.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reminder: bool,
+    /// Whoever wrote this message, only set for user messages.
+    #[serde(default)]
+    pub speaker: Option<Speaker>,
+    /// Tool calls made before this (assistant) message, kept together with it so that
+    /// regenerating or draining it never leaves half of an exchange behind.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_steps: Vec<ToolStep>,
+    /// Files sent along with this (user) message.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+    /// Everything a user prompt was sent with, unset for messages that are not prompts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<PromptEnvelope>,
+}
+
+/// The structured parts of a user prompt besides its content.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PromptEnvelope {
+    pub current_time: String,
+    pub time_since: String,
+    pub relevant_memories: Vec<String>,
+    pub system_note: Option<String>,
+}
+
+impl PromptEnvelope {
+    /// Renders the prompt into the text the model sees.
+    pub fn render(&self, content: Option<&str>, speaker: Option<&Speaker>) -> String {
+        let mut message = String::new();
+
+        if let Some(system_note) = &self.system_note {
+            message.push_str(&format!("System Note:\n{system_note}\n\n"));
+        }
+
+        message.push_str(&format!(
+            "The current time is {}, {} since the last message before this one.\n\n",
+            self.current_time, self.time_since
+        ));
+
+        if !self.relevant_memories.is_empty() {
+            message.push_str(&format!(
+                "You have recalled the following memories:\n{}\n\n",
+                self.relevant_memories
+                    .iter()
+                    .map(|m| format!("```memory\n{}\n```", m))
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            ));
+        }
+
+        if let Some(content) = content {
+            match speaker {
+                Some(speaker) => message.push_str(&format!(
+                    "Respond to the following message from {}:\n{}\n\n",
+                    speaker.name, content
+                )),
+                None => message.push_str(&format!(
+                    "Respond to the following message:\n{}\n\n",
+                    content
+                )),
+            }
+        }
+
+        // remove the last double newline
+        message.pop();
+        message.pop();
+
+        message
+    }
+
+    /// Recovers the envelope and content of a prompt saved before envelopes existed, back
+    /// when prompts were stored already rendered.
+    fn parse_legacy(text: &str) -> Option<(Self, Option<String>)> {
+        let regex = LEGACY_PROMPT.as_ref()?;
+        let memory_regex = LEGACY_MEMORY.as_ref()?;
+
+        let matches = regex.captures(text)?;
+
+        let relevant_memories = matches
+            .get(4)
+            .map(|m| {
+                memory_regex
+                    .captures_iter(m.as_str().trim())
+                    .filter_map(|cap| cap.get(1))
+                    .map(|memory| memory.as_str().trim().to_string())
+                    .filter(|memory| !memory.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let envelope = Self {
+            current_time: matches.get(2)?.as_str().trim().to_string(),
+            time_since: matches.get(3)?.as_str().trim().to_string(),
+            relevant_memories,
+            system_note: matches.get(1).map(|m| m.as_str().trim().to_string()),
+        };
+
+        // the speaker's name (capture 5) is kept alongside the message
+        Some((
+            envelope,
+            matches.get(6).map(|m| m.as_str().trim().to_string()),
+        ))
+    }
+}
+
+/// [ChatMessage] as found on disk, possibly from before prompts had an envelope.
+#[derive(Deserialize)]
+struct StoredChatMessage {
+    inner: RigMessage,
+    sent_at: DateTime<Utc>,
+    freewill: bool,
+    #[serde(default)]
+    reminder: bool,
+    #[serde(default)]
+    speaker: Option<Speaker>,
+    #[serde(default)]
+    tool_steps: Vec<ToolStep>,
+    #[serde(default)]
+    attachments: Vec<Attachment>,
+    #[serde(default)]
+    prompt: Option<PromptEnvelope>,
+}
+
+impl From<StoredChatMessage> for ChatMessage {
+    fn from(stored: StoredChatMessage) -> Self {
+        let mut message = Self {
+            inner: stored.inner,
+            sent_at: stored.sent_at,
+            freewill: stored.freewill,
+            reminder: stored.reminder,
+            speaker: stored.speaker,
+            tool_steps: stored.tool_steps,
+            attachments: stored.attachments,
+            prompt: stored.prompt,
+        };
+
+        if message.prompt.is_none() && message.role() == MessageRole::User {
+            let legacy = message
+                .content()
+                .and_then(|text| PromptEnvelope::parse_legacy(&text));
+
+            if let Some((envelope, content)) = legacy {
+                message.inner = RigMessage::user(content.unwrap_or_default());
+                message.prompt = Some(envelope);
+            }
+        }
+
+        message
+    }
+}
+
+/// A round of tool calls and their results.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct ToolStep {
+    /// Assistant message with every call of the round
+    pub call: RigMessage,
+    /// User message with the result of every call
+    pub result: RigMessage,
 }
 
 #[derive(PartialEq, Eq)]
@@ -33,6 +217,11 @@ impl ChatMessage {
             inner: RigMessage::assistant(content),
             sent_at: Utc::now(),
             freewill: false,
+            reminder: false,
+            speaker: None,
+            tool_steps: vec![],
+            attachments: vec![],
+            prompt: None,
         }
     }
 
@@ -41,9 +230,20 @@ impl ChatMessage {
             inner: RigMessage::user(content),
             sent_at: Utc::now(),
             freewill: false,
+            reminder: false,
+            speaker: None,
+            tool_steps: vec![],
+            attachments: vec![],
+            prompt: None,
         }
     }
 
+    /// The message as sent at `at`, which has to come from the conversation's clock.
+    pub fn stamped(mut self, at: DateTime<Utc>) -> Self {
+        self.sent_at = at;
+        self
+    }
+
     pub fn content(&self) -> Option<String> {
         match &self.inner {
             RigMessage::Assistant { content } => {
@@ -67,6 +267,38 @@ impl ChatMessage {
         }
     }
 
+    /// The text of the message as the model sees it, prompts rendered.
+    pub fn text(&self) -> Option<String> {
+        let content = self.content();
+
+        match &self.prompt {
+            Some(envelope) => {
+                // prompts without content are the bot being nudged, e.g. freewill
+                let content =
+                    content.filter(|content| !content.is_empty() || !self.attachments.is_empty());
+                Some(envelope.render(content.as_deref(), self.speaker.as_ref()))
+            }
+            None => content,
+        }
+    }
+
+    /// The messages this one expands to in a completion request, tool steps first.
+    pub fn into_history(self) -> Vec<RigMessage> {
+        let inner = match (&self.prompt, self.text()) {
+            (Some(_), Some(text)) => RigMessage::user(text),
+            _ => self.inner,
+        };
+
+        let mut history = self
+            .tool_steps
+            .into_iter()
+            .flat_map(|step| [step.call, step.result])
+            .collect::<Vec<_>>();
+        history.push(attachment::with_attachments(inner, self.attachments));
+
+        history
+    }
+
     pub fn role(&self) -> MessageRole {
         match &self.inner {
             RigMessage::User { .. } => MessageRole::User,
@@ -81,6 +313,11 @@ impl Default for ChatMessage {
             inner: RigMessage::user(""),
             sent_at: Utc::now(),
             freewill: false,
+            reminder: false,
+            speaker: None,
+            tool_steps: vec![],
+            attachments: vec![],
+            prompt: None,
         }
     }
 }
@@ -110,6 +347,11 @@ impl From<RigMessage> for ChatMessage {
             inner: message,
             sent_at: Utc::now(),
             freewill: false,
+            reminder: false,
+            speaker: None,
+            tool_steps: vec![],
+            attachments: vec![],
+            prompt: None,
         }
     }
 }
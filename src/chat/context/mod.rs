@@ -1,5 +1,19 @@
+mod activity;
+mod attachment;
 mod context;
 mod message;
+mod profile;
+mod scope;
+mod store;
+mod tokens;
 
+pub use activity::{ActiveHours, Activities, Activity};
+pub use attachment::{Attachment, image_media_type, without_images};
 pub use context::{ChatContext, ContextWindow, MessageIdentifier, UserPrompt};
-pub use message::{ChatMessage, MessageRole};
+pub use message::{ChatMessage, MessageRole, PromptEnvelope, ToolStep};
+pub use profile::UserProfile;
+pub use scope::{ChannelKinds, ConversationScope, Speaker};
+pub use store::{
+    ConversationStore, SavedContext, autosave_interval, migrate_to_sqlite, open_store,
+};
+pub use tokens::{HeuristicTokenizer, Tokenizer};
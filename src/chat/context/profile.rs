@@ -0,0 +1,105 @@
+use std::path::{Path, PathBuf};
+
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+
+use crate::chat::prompt::SystemPromptBuilder;
+
+use super::Speaker;
+
+/// A user's own overrides of the prompt's user settings, saved next to the contexts.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct UserProfile {
+    pub user_name: Option<String>,
+    pub user_about: Option<String>,
+    pub timezone: Option<Tz>,
+    /// Whether the bot may reach out on its own, it does unless turned off
+    pub freewill: Option<bool>,
+}
+
+impl UserProfile {
+    fn path(folder: &Path, user: UserId) -> PathBuf {
+        folder.join(format!("profile-{user}.bin"))
+    }
+
+    /// Loads a user's profile, empty if they never set one.
+    pub async fn load(folder: &Path, user: UserId) -> Self {
+        tokio::fs::read(Self::path(folder, user))
+            .await
+            .ok()
+            .and_then(|bytes| {
+                ciborium::from_reader(&bytes[..])
+                    .map_err(|e| {
+                        log::error!("Failed to deserialize profile of {user}: {e}");
+                        e
+                    })
+                    .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Saves a user's profile, an empty one is removed altogether.
+    pub async fn save(&self, folder: &Path, user: UserId) -> anyhow::Result<()> {
+        let path = Self::path(folder, user);
+
+        if self.is_empty() {
+            tokio::fs::remove_file(path).await.ok();
+            return Ok(());
+        }
+
+        let mut bytes = vec![];
+        ciborium::into_writer(self, &mut bytes)?;
+
+        // written aside and renamed over the old one, so a crash mid-write never loses it
+        tokio::fs::create_dir_all(folder).await?;
+        let tmp = path.with_extension("bin.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(tmp, path).await?;
+
+        Ok(())
+    }
+
+    /// The timezone of a user: the one in their profile, `fallback` if they did not set
+    /// one, UTC without either.
+    pub async fn timezone_of(folder: Option<&Path>, user: UserId, fallback: Option<Tz>) -> Tz {
+        let timezone = match folder {
+            Some(folder) => Self::load(folder, user).await.timezone,
+            None => None,
+        };
+
+        timezone.or(fallback).unwrap_or(Tz::UTC)
+    }
+
+    pub fn freewill_enabled(&self) -> bool {
+        self.freewill.unwrap_or(true)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Overrides the builder's user settings with whatever the profile sets.
+    pub fn apply(&self, builder: &mut SystemPromptBuilder) {
+        if let Some(user_name) = &self.user_name {
+            builder.user_name = user_name.clone();
+        }
+        if let Some(user_about) = &self.user_about {
+            builder.user_about = Some(user_about.clone());
+        }
+        if let Some(timezone) = self.timezone {
+            builder.timezone = Some(timezone);
+        }
+    }
+}
+
+impl Speaker {
+    /// Goes by the name the speaker picked in their profile, if any.
+    pub fn with_profile(mut self, profile: &UserProfile) -> Self {
+        if let Some(user_name) = &profile.user_name {
+            self.name = user_name.clone();
+        }
+
+        self
+    }
+}
@@ -0,0 +1,137 @@
+use std::{collections::HashMap, fmt::Display, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use serenity::all::{CacheHttp, Channel, ChannelId, GuildId, User, UserId};
+
+/// Whether each guild channel resolved so far is a thread, which never changes, so that
+/// every message does not have to look its channel up. A channel is forgotten once its
+/// conversation is no longer loaded.
+#[derive(Default)]
+pub struct ChannelKinds(Mutex<HashMap<ChannelId, bool>>);
+
+impl ChannelKinds {
+    fn is_thread(&self, channel: ChannelId) -> Option<bool> {
+        self.0
+            .lock()
+            .ok()
+            .and_then(|threads| threads.get(&channel).copied())
+    }
+
+    fn insert(&self, channel: ChannelId, is_thread: bool) {
+        if let Ok(mut threads) = self.0.lock() {
+            threads.insert(channel, is_thread);
+        }
+    }
+
+    /// Forgets every channel but the ones `keep` holds for.
+    pub fn retain(&self, keep: impl Fn(ChannelId) -> bool) {
+        if let Ok(mut threads) = self.0.lock() {
+            threads.retain(|channel, _| keep(*channel));
+        }
+    }
+
+    pub fn forget(&self, channel: ChannelId) {
+        if let Ok(mut threads) = self.0.lock() {
+            threads.remove(&channel);
+        }
+    }
+}
+
+/// The conversation an engine belongs to.
+///
+/// Direct messages are keyed on the user, guild channels and threads on the
+/// channel, so that everyone talking in the same channel shares one context.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ConversationScope {
+    Direct(UserId),
+    Channel(ChannelId),
+    Thread(ChannelId),
+}
+
+impl ConversationScope {
+    /// Figures out the scope of a message sent by `user` in `channel_id`.
+    pub async fn resolve(
+        cache_http: impl CacheHttp,
+        kinds: &ChannelKinds,
+        channel_id: ChannelId,
+        guild_id: Option<GuildId>,
+        user: UserId,
+    ) -> anyhow::Result<Self> {
+        if guild_id.is_none() {
+            return Ok(Self::Direct(user));
+        }
+
+        let is_thread = match kinds.is_thread(channel_id) {
+            Some(is_thread) => is_thread,
+            None => match channel_id.to_channel(cache_http).await? {
+                Channel::Guild(channel) => {
+                    let is_thread = channel.thread_metadata.is_some();
+                    kinds.insert(channel_id, is_thread);
+
+                    is_thread
+                }
+                _ => return Ok(Self::Direct(user)),
+            },
+        };
+
+        Ok(match is_thread {
+            true => Self::Thread(channel_id),
+            false => Self::Channel(channel_id),
+        })
+    }
+
+    pub fn is_direct(&self) -> bool {
+        matches!(self, Self::Direct(_))
+    }
+
+    /// Unique key used to name every file and collection of this scope.
+    ///
+    /// Direct scopes are keyed on the bare user id so that saves made before
+    /// scopes existed keep working.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Direct(user) => user.to_string(),
+            Self::Channel(channel) => format!("channel_{channel}"),
+            Self::Thread(channel) => format!("thread_{channel}"),
+        }
+    }
+
+    /// Inverse of [ConversationScope::key].
+    pub fn from_key(key: &str) -> Option<Self> {
+        let (kind, id) = key.split_once('_').unwrap_or(("", key));
+        let id = id.parse::<u64>().ok().filter(|id| *id != 0)?;
+
+        match kind {
+            "" => Some(Self::Direct(UserId::new(id))),
+            "channel" => Some(Self::Channel(ChannelId::new(id))),
+            "thread" => Some(Self::Thread(ChannelId::new(id))),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ConversationScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Direct(user) => write!(f, "user {user}"),
+            Self::Channel(channel) => write!(f, "channel {channel}"),
+            Self::Thread(channel) => write!(f, "thread {channel}"),
+        }
+    }
+}
+
+/// The author of a user message.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Speaker {
+    pub id: UserId,
+    pub name: String,
+}
+
+impl From<&User> for Speaker {
+    fn from(user: &User) -> Self {
+        Self {
+            id: user.id,
+            name: user.display_name().to_string(),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+
+use crate::chat::context::ConversationScope;
+
+use super::{ConversationStore, SavedContext, SavedContextRef, snapshot};
+
+/// Keeps every conversation in a `context-{scope}.bin` snapshot of its own, along with
+/// `backups` previous ones. Backups are rotated once a session and then at most every
+/// `rotate_every`, so that saving after every turn does not push out the older ones.
+pub struct FileStore {
+    folder: PathBuf,
+    backups: usize,
+    rotate_every: Duration,
+    rotated: Mutex<HashMap<ConversationScope, Instant>>,
+}
+
+impl FileStore {
+    pub fn new(folder: PathBuf, backups: usize, rotate_every: Duration) -> Self {
+        Self {
+            folder,
+            backups,
+            rotate_every,
+            rotated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the backups of a scope are due to be rotated, marking them rotated if so.
+    fn rotate(&self, scope: ConversationScope) -> bool {
+        let Ok(mut rotated) = self.rotated.lock() else {
+            return true;
+        };
+
+        let due = rotated.get(&scope).is_none_or(|last| {
+            // without a periodic autosave, once a session is all there is
+            !self.rotate_every.is_zero() && last.elapsed() >= self.rotate_every
+        });
+        if due {
+            rotated.insert(scope, Instant::now());
+        }
+
+        due
+    }
+
+    fn path(&self, scope: ConversationScope) -> PathBuf {
+        self.folder.join(format!("context-{}.bin", scope.key()))
+    }
+}
+
+#[async_trait]
+impl ConversationStore for FileStore {
+    async fn load(&self, scope: ConversationScope) -> anyhow::Result<Option<SavedContext>> {
+        Ok(snapshot::read(&self.path(scope), self.backups))
+    }
+
+    async fn save(
+        &self,
+        scope: ConversationScope,
+        context: &SavedContextRef<'_>,
+    ) -> anyhow::Result<()> {
+        // without rotating, the latest snapshot is replaced and the backups are left alone
+        let backups = match self.rotate(scope) {
+            true => self.backups,
+            false => 0,
+        };
+
+        snapshot::write(&self.path(scope), context, backups).await
+    }
+
+    async fn remove(&self, scope: ConversationScope) -> anyhow::Result<()> {
+        // backups would bring the history back on the next start
+        snapshot::remove(&self.path(scope), self.backups);
+        if let Ok(mut rotated) = self.rotated.lock() {
+            rotated.remove(&scope);
+        }
+
+        Ok(())
+    }
+
+    async fn scopes(&self) -> anyhow::Result<Vec<ConversationScope>> {
+        // extract the scope from every "context-*.bin" file, backups included in case only
+        // those survived a crash
+        let regex = regex::Regex::new(r"^context-((?:(?:channel|thread)_)?\d+)\.bin(?:\.\d+)?$")?;
+        let mut scopes = HashSet::new();
+
+        for file in std::fs::read_dir(&self.folder)? {
+            let filename = file?.file_name();
+            if let Some(captures) = regex.captures(&filename.to_string_lossy()) {
+                let key = captures
+                    .get(1)
+                    .ok_or(anyhow::anyhow!("no id found"))?
+                    .as_str();
+                let scope = ConversationScope::from_key(key)
+                    .ok_or(anyhow::anyhow!("invalid saved context \"{key}\""))?;
+
+                scopes.insert(scope);
+            }
+        }
+
+        Ok(scopes.into_iter().collect())
+    }
+}
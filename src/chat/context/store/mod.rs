@@ -0,0 +1,156 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use branch_context::Messages;
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+use serenity::all::ChannelId;
+
+use crate::config::structure::{ContextConfig, ConversationStoreKind};
+
+use super::{ConversationScope, MessageIdentifier, message::ChatMessage};
+
+mod file;
+mod snapshot;
+mod sqlite;
+
+pub use file::FileStore;
+pub use sqlite::SqliteStore;
+
+/// Snapshots kept besides the latest one, unless configured otherwise.
+const DEFAULT_BACKUPS: usize = 3;
+/// How often every loaded context is saved, unless configured otherwise.
+const DEFAULT_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// What is saved for every conversation.
+#[derive(Deserialize)]
+pub struct SavedContext {
+    pub messages: IndexMap<MessageIdentifier, Messages<ChatMessage>>,
+    pub summary: Option<String>,
+    pub persona: Option<String>,
+    /// Where the conversation last took place
+    #[serde(default)]
+    pub channel: Option<ChannelId>,
+}
+
+impl SavedContext {
+    pub fn as_ref(&self) -> SavedContextRef<'_> {
+        SavedContextRef {
+            messages: &self.messages,
+            summary: self.summary.as_deref(),
+            persona: self.persona.as_deref(),
+            channel: self.channel,
+        }
+    }
+}
+
+/// Borrowed counterpart of [SavedContext], to save without cloning the messages.
+#[derive(Serialize)]
+pub struct SavedContextRef<'a> {
+    pub messages: &'a IndexMap<MessageIdentifier, Messages<ChatMessage>>,
+    pub summary: Option<&'a str>,
+    pub persona: Option<&'a str>,
+    pub channel: Option<ChannelId>,
+}
+
+/// Where conversations are kept between restarts, one per scope.
+#[async_trait]
+pub trait ConversationStore: Send + Sync {
+    /// Returns the saved conversation of the scope, if there is one.
+    async fn load(&self, scope: ConversationScope) -> anyhow::Result<Option<SavedContext>>;
+
+    /// Replaces the saved conversation of the scope.
+    async fn save(
+        &self,
+        scope: ConversationScope,
+        context: &SavedContextRef<'_>,
+    ) -> anyhow::Result<()>;
+
+    async fn remove(&self, scope: ConversationScope) -> anyhow::Result<()>;
+
+    /// Returns every scope with a saved conversation, in no particular order.
+    async fn scopes(&self) -> anyhow::Result<Vec<ConversationScope>>;
+}
+
+fn backups(config: &ContextConfig) -> usize {
+    config
+        .autosave
+        .as_ref()
+        .and_then(|autosave| autosave.backups)
+        .unwrap_or(DEFAULT_BACKUPS)
+}
+
+/// How often every loaded context is saved, zero if it is not periodically. Backups are
+/// rotated at most this often as well.
+pub fn autosave_interval(config: &ContextConfig) -> Duration {
+    config
+        .autosave
+        .as_ref()
+        .and_then(|autosave| autosave.interval_secs)
+        .map(Duration::from_secs)
+        .unwrap_or(DEFAULT_AUTOSAVE_INTERVAL)
+}
+
+/// Opens the configured store, `None` if conversations are not saved at all.
+pub fn open_store(config: &ContextConfig) -> anyhow::Result<Option<Arc<dyn ConversationStore>>> {
+    let Some(folder) = &config.save_to_disk_folder else {
+        return Ok(None);
+    };
+
+    if folder.is_file() {
+        std::fs::remove_file(folder)?;
+    }
+    std::fs::create_dir_all(folder)?;
+
+    let files = FileStore::new(folder.clone(), backups(config), autosave_interval(config));
+
+    Ok(Some(match config.store.unwrap_or_default() {
+        ConversationStoreKind::File => Arc::new(files),
+        ConversationStoreKind::Sqlite => Arc::new(SqliteStore::open(
+            &folder.join("conversations.sqlite"),
+            Some(files),
+        )?),
+    }))
+}
+
+/// Moves every conversation only saved to files into the SQLite database, returning how
+/// many were moved. Files are only removed once the database reads their conversation
+/// back, and never with `keep`. Conversations the database already has are left alone.
+pub async fn migrate_to_sqlite(config: &ContextConfig, keep: bool) -> anyhow::Result<usize> {
+    let Some(folder) = &config.save_to_disk_folder else {
+        anyhow::bail!("conversations are not saved, context.save_to_disk_folder is not set");
+    };
+
+    let files = FileStore::new(folder.clone(), backups(config), autosave_interval(config));
+    let database = SqliteStore::open(&folder.join("conversations.sqlite"), None)?;
+
+    let mut moved = 0;
+    for scope in files.scopes().await? {
+        if database.load(scope).await?.is_some() {
+            log::warn!("skipping {scope}, the database already has it, its files are kept");
+            continue;
+        }
+
+        let Some(saved) = files.load(scope).await? else {
+            log::warn!("skipping {scope}, none of its files could be read");
+            continue;
+        };
+
+        database.save(scope, &saved.as_ref()).await?;
+
+        let written = database
+            .load(scope)
+            .await?
+            .is_some_and(|written| written.messages.len() == saved.messages.len());
+        if !written {
+            anyhow::bail!("{scope} could not be read back from the database, its files are kept");
+        }
+        moved += 1;
+
+        if !keep {
+            files.remove(scope).await?;
+        }
+    }
+
+    Ok(moved)
+}
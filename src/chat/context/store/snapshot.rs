@@ -0,0 +1,112 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::bail;
+use tokio::io::AsyncWriteExt;
+
+use super::{SavedContext, SavedContextRef};
+
+/// Starts every saved context, followed by the format version as a little endian u16.
+const MAGIC: &[u8; 8] = b"CHATCTX\0";
+const FORMAT_VERSION: u16 = 1;
+
+fn encode(context: &SavedContextRef<'_>) -> anyhow::Result<Vec<u8>> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    ciborium::into_writer(context, &mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn decode(bytes: &[u8]) -> anyhow::Result<SavedContext> {
+    let Some(body) = bytes.strip_prefix(MAGIC) else {
+        // headerless contexts predate versioning, the oldest being just the messages
+        return Ok(
+            ciborium::from_reader::<SavedContext, _>(bytes).or_else(|_| {
+                ciborium::from_reader(bytes).map(|messages| SavedContext {
+                    messages,
+                    summary: None,
+                    persona: None,
+                    channel: None,
+                })
+            })?,
+        );
+    };
+
+    let (version, body) = body
+        .split_first_chunk::<2>()
+        .ok_or(anyhow::anyhow!("truncated header"))?;
+
+    match u16::from_le_bytes(*version) {
+        1 => Ok(ciborium::from_reader(body)?),
+        version => bail!("unknown format version {version}, saved by a newer version?"),
+    }
+}
+
+/// Path of the `n`th newest backup of a snapshot.
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    path.with_extension(format!("bin.{n}"))
+}
+
+/// Atomically replaces the snapshot at `path`, keeping the `backups` previous ones.
+pub(super) async fn write(
+    path: &Path,
+    context: &SavedContextRef<'_>,
+    backups: usize,
+) -> anyhow::Result<()> {
+    let bytes = encode(context)?;
+
+    let temporary = path.with_extension("bin.tmp");
+    let mut file = tokio::fs::File::create(&temporary).await?;
+    file.write_all(&bytes).await?;
+    file.sync_all().await?;
+    drop(file);
+
+    if backups > 0 && tokio::fs::try_exists(path).await? {
+        for n in (1..backups).rev() {
+            let backup = backup_path(path, n);
+            if tokio::fs::try_exists(&backup).await? {
+                tokio::fs::rename(&backup, backup_path(path, n + 1)).await?;
+            }
+        }
+
+        tokio::fs::rename(path, backup_path(path, 1)).await?;
+    }
+
+    tokio::fs::rename(&temporary, path).await?;
+
+    Ok(())
+}
+
+/// Loads the newest snapshot that can still be read, falling back to the backups when
+/// the latest one is missing or corrupt.
+pub(super) fn read(path: &Path, backups: usize) -> Option<SavedContext> {
+    std::iter::once(path.to_path_buf())
+        .chain((1..=backups).map(|n| backup_path(path, n)))
+        .find_map(|candidate| {
+            let bytes = std::fs::read(&candidate).ok()?;
+
+            match decode(&bytes) {
+                Ok(saved) => {
+                    if candidate != path {
+                        log::warn!("Recovered context from backup {}", candidate.display());
+                    }
+
+                    Some(saved)
+                }
+                Err(why) => {
+                    log::error!("Failed to load context {}: {why}", candidate.display());
+                    None
+                }
+            }
+        })
+}
+
+/// Deletes a snapshot along with its backups.
+pub(super) fn remove(path: &Path, backups: usize) {
+    std::fs::remove_file(path).ok();
+    std::fs::remove_file(path.with_extension("bin.tmp")).ok();
+
+    for n in 1..=backups {
+        std::fs::remove_file(backup_path(path, n)).ok();
+    }
+}
@@ -0,0 +1,447 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use branch_context::Messages;
+use indexmap::IndexMap;
+use rusqlite::{Connection, OptionalExtension, params};
+use serenity::all::ChannelId;
+
+use crate::chat::context::{ChatMessage, ConversationScope, MessageIdentifier};
+
+use super::{ConversationStore, FileStore, SavedContext, SavedContextRef};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS conversations (
+        scope TEXT PRIMARY KEY,
+        summary TEXT,
+        persona TEXT,
+        channel_id INTEGER,
+        updated_at INTEGER NOT NULL
+    );
+
+    CREATE TABLE IF NOT EXISTS messages (
+        scope TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        message_id INTEGER NOT NULL,
+        channel_id INTEGER NOT NULL,
+        random INTEGER NOT NULL,
+        message_ids TEXT NOT NULL,
+        selected INTEGER NOT NULL,
+        PRIMARY KEY (scope, position)
+    );
+
+    CREATE TABLE IF NOT EXISTS branches (
+        scope TEXT NOT NULL,
+        position INTEGER NOT NULL,
+        branch INTEGER NOT NULL,
+        role TEXT NOT NULL,
+        content TEXT,
+        sent_at INTEGER NOT NULL,
+        data BLOB NOT NULL,
+        PRIMARY KEY (scope, position, branch)
+    );
+
+    CREATE INDEX IF NOT EXISTS branches_sent_at ON branches (scope, sent_at);
+";
+
+/// Keeps every conversation in a single SQLite database, with a row per message and
+/// per branch of it, so that history can be queried without loading it all.
+///
+/// Conversations that were only ever saved to files are loaded from `legacy` and
+/// move into the database the next time they are saved.
+pub struct SqliteStore {
+    connection: Arc<Mutex<Connection>>,
+    legacy: Option<FileStore>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path, legacy: Option<FileStore>) -> anyhow::Result<Self> {
+        let connection = Connection::open(path)?;
+
+        // every engine writes its own conversation, wait for each other instead of failing
+        connection.busy_timeout(Duration::from_secs(5))?;
+        connection.pragma_update(None, "journal_mode", "WAL")?;
+        connection.execute_batch(SCHEMA)?;
+
+        // databases created before conversations kept their channel
+        let has_channel = connection
+            .prepare("SELECT 1 FROM pragma_table_info('conversations') WHERE name = 'channel_id'")?
+            .exists([])?;
+        if !has_channel {
+            connection.execute(
+                "ALTER TABLE conversations ADD COLUMN channel_id INTEGER",
+                [],
+            )?;
+        }
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+            legacy,
+        })
+    }
+
+    /// Runs `f` with the connection on a blocking thread, off the async workers.
+    async fn with_connection<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let connection = self.connection.clone();
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .map_err(|_| anyhow::anyhow!("sqlite connection poisoned"))?;
+
+            f(&mut connection)
+        })
+        .await?
+    }
+}
+
+/// A message as it is saved, encoded up front so that saving does not borrow the context.
+struct MessageRow {
+    message_id: i64,
+    channel_id: i64,
+    random: bool,
+    message_ids: String,
+    selected: i64,
+    branches: Vec<BranchRow>,
+}
+
+struct BranchRow {
+    role: String,
+    content: Option<String>,
+    sent_at: i64,
+    data: Vec<u8>,
+}
+
+impl MessageRow {
+    fn new(
+        identifier: &MessageIdentifier,
+        messages: &Messages<ChatMessage>,
+    ) -> anyhow::Result<Self> {
+        let (branches, selected) = branches(messages);
+
+        Ok(Self {
+            message_id: identifier.message_id as i64,
+            channel_id: identifier.channel_id as i64,
+            random: identifier.random,
+            message_ids: serde_json::to_string(&identifier.message_ids)?,
+            selected: selected as i64,
+            branches: branches
+                .iter()
+                .map(|message| {
+                    let mut data = vec![];
+                    ciborium::into_writer(message, &mut data)?;
+
+                    Ok(BranchRow {
+                        role: message.role().to_string(),
+                        content: message.text(),
+                        sent_at: message.sent_at.timestamp_millis(),
+                        data,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        })
+    }
+}
+
+/// Every branch of a message, oldest first, along with the index of the selected one.
+fn branches(messages: &Messages<ChatMessage>) -> (Vec<ChatMessage>, usize) {
+    let mut cursor = messages.clone();
+
+    let mut selected = 0;
+    while cursor.backward {
+        cursor.backward();
+        selected += 1;
+    }
+
+    let mut branches = vec![cursor.selected().clone()];
+    while cursor.forward {
+        cursor.forward();
+        branches.push(cursor.selected().clone());
+    }
+
+    (branches, selected)
+}
+
+/// Inverse of [branches].
+fn from_branches(branches: Vec<ChatMessage>, selected: usize) -> Option<Messages<ChatMessage>> {
+    let mut branches = branches.into_iter();
+
+    let mut messages = Messages::new(branches.next()?.into());
+    let mut count = 1;
+    for branch in branches {
+        messages.push(branch); // pushes and selects
+        count += 1;
+    }
+    for _ in selected.min(count - 1) + 1..count {
+        messages.backward();
+    }
+
+    Some(messages)
+}
+
+#[async_trait]
+impl ConversationStore for SqliteStore {
+    async fn load(&self, scope: ConversationScope) -> anyhow::Result<Option<SavedContext>> {
+        let key = scope.key();
+
+        let saved = self
+            .with_connection(move |connection| {
+                let Some((summary, persona, channel)) = connection
+                    .query_row(
+                        "SELECT summary, persona, channel_id FROM conversations WHERE scope = ?1",
+                        [&key],
+                        |row| Ok((row.get(0)?, row.get(1)?, row.get::<_, Option<i64>>(2)?)),
+                    )
+                    .optional()?
+                else {
+                    return Ok(None);
+                };
+
+                let mut branches: HashMap<i64, Vec<ChatMessage>> = HashMap::new();
+                let mut statement = connection.prepare(
+                "SELECT position, data FROM branches WHERE scope = ?1 ORDER BY position, branch",
+            )?;
+                let rows = statement.query_map([&key], |row| {
+                    Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?))
+                })?;
+                for row in rows {
+                    let (position, data) = row?;
+                    branches
+                        .entry(position)
+                        .or_default()
+                        .push(ciborium::from_reader(data.as_slice())?);
+                }
+
+                let mut messages = IndexMap::new();
+                let mut statement = connection.prepare(
+                    "SELECT position, message_id, channel_id, random, message_ids, selected
+                FROM messages WHERE scope = ?1 ORDER BY position",
+                )?;
+                let rows = statement.query_map([&key], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, bool>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, i64>(5)?,
+                    ))
+                })?;
+                for row in rows {
+                    let (position, message_id, channel_id, random, message_ids, selected) = row?;
+
+                    let Some(message) = branches
+                        .remove(&position)
+                        .and_then(|branches| from_branches(branches, selected as usize))
+                    else {
+                        log::warn!("message {position} of {scope} has no branches, skipping it");
+                        continue;
+                    };
+
+                    let identifier = MessageIdentifier {
+                        message_id: message_id as u64,
+                        channel_id: channel_id as u64,
+                        random,
+                        message_ids: serde_json::from_str(&message_ids)?,
+                    };
+                    messages.insert(identifier, message);
+                }
+
+                Ok(Some(SavedContext {
+                    messages,
+                    summary,
+                    persona,
+                    channel: channel
+                        .filter(|channel| *channel > 0)
+                        .map(|channel| ChannelId::new(channel as u64)),
+                }))
+            })
+            .await?;
+
+        match (saved, &self.legacy) {
+            (Some(saved), _) => Ok(Some(saved)),
+            (None, Some(legacy)) => {
+                let saved = legacy.load(scope).await?;
+                if saved.is_some() {
+                    log::info!(
+                        "loaded {scope} from its file, it will be saved to sqlite from now on"
+                    );
+                }
+
+                Ok(saved)
+            }
+            (None, None) => Ok(None),
+        }
+    }
+
+    async fn save(
+        &self,
+        scope: ConversationScope,
+        context: &SavedContextRef<'_>,
+    ) -> anyhow::Result<()> {
+        let key = scope.key();
+        let summary = context.summary.map(str::to_string);
+        let persona = context.persona.map(str::to_string);
+        let channel = context.channel.map(|channel| channel.get() as i64);
+        let rows = context
+            .messages
+            .iter()
+            .map(|(identifier, messages)| MessageRow::new(identifier, messages))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+
+            transaction.execute(
+                "INSERT INTO conversations (scope, summary, persona, channel_id, updated_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)
+                ON CONFLICT (scope) DO UPDATE SET
+                    summary = excluded.summary,
+                    persona = excluded.persona,
+                    channel_id = excluded.channel_id,
+                    updated_at = excluded.updated_at",
+                params![
+                    key,
+                    summary,
+                    persona,
+                    channel,
+                    chrono::Utc::now().timestamp()
+                ],
+            )?;
+
+            // rows that did not change are left as they are, most of a conversation does
+            // not between two saves
+            {
+                let mut upsert_message = transaction.prepare(
+                    "INSERT INTO messages
+                    (scope, position, message_id, channel_id, random, message_ids, selected)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ON CONFLICT (scope, position) DO UPDATE SET
+                        message_id = excluded.message_id,
+                        channel_id = excluded.channel_id,
+                        random = excluded.random,
+                        message_ids = excluded.message_ids,
+                        selected = excluded.selected
+                    WHERE message_id IS NOT excluded.message_id
+                        OR channel_id IS NOT excluded.channel_id
+                        OR random IS NOT excluded.random
+                        OR message_ids IS NOT excluded.message_ids
+                        OR selected IS NOT excluded.selected",
+                )?;
+                let mut upsert_branch = transaction.prepare(
+                    "INSERT INTO branches
+                    (scope, position, branch, role, content, sent_at, data)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                    ON CONFLICT (scope, position, branch) DO UPDATE SET
+                        role = excluded.role,
+                        content = excluded.content,
+                        sent_at = excluded.sent_at,
+                        data = excluded.data
+                    WHERE data IS NOT excluded.data",
+                )?;
+                let mut delete_branches = transaction.prepare(
+                    "DELETE FROM branches WHERE scope = ?1 AND position = ?2 AND branch >= ?3",
+                )?;
+
+                for (position, row) in rows.iter().enumerate() {
+                    let position = position as i64;
+
+                    upsert_message.execute(params![
+                        key,
+                        position,
+                        row.message_id,
+                        row.channel_id,
+                        row.random,
+                        row.message_ids,
+                        row.selected,
+                    ])?;
+
+                    for (branch, data) in row.branches.iter().enumerate() {
+                        upsert_branch.execute(params![
+                            key,
+                            position,
+                            branch as i64,
+                            data.role,
+                            data.content,
+                            data.sent_at,
+                            data.data,
+                        ])?;
+                    }
+                    delete_branches.execute(params![key, position, row.branches.len() as i64])?;
+                }
+            }
+
+            // messages dropped from the end since the last save
+            let count = rows.len() as i64;
+            transaction.execute(
+                "DELETE FROM messages WHERE scope = ?1 AND position >= ?2",
+                params![key, count],
+            )?;
+            transaction.execute(
+                "DELETE FROM branches WHERE scope = ?1 AND position >= ?2",
+                params![key, count],
+            )?;
+
+            transaction.commit()?;
+
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove(&self, scope: ConversationScope) -> anyhow::Result<()> {
+        let key = scope.key();
+
+        self.with_connection(move |connection| {
+            let transaction = connection.transaction()?;
+            transaction.execute("DELETE FROM conversations WHERE scope = ?1", [&key])?;
+            transaction.execute("DELETE FROM messages WHERE scope = ?1", [&key])?;
+            transaction.execute("DELETE FROM branches WHERE scope = ?1", [&key])?;
+            transaction.commit()?;
+
+            Ok(())
+        })
+        .await?;
+
+        // the file would be loaded again otherwise
+        if let Some(legacy) = &self.legacy {
+            legacy.remove(scope).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn scopes(&self) -> anyhow::Result<Vec<ConversationScope>> {
+        let mut scopes = self
+            .with_connection(|connection| {
+                let mut statement = connection.prepare("SELECT scope FROM conversations")?;
+                let keys = statement
+                    .query_map([], |row| row.get::<_, String>(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                Ok(keys
+                    .iter()
+                    .filter_map(|key| ConversationScope::from_key(key))
+                    .collect::<Vec<_>>())
+            })
+            .await?;
+
+        if let Some(legacy) = &self.legacy {
+            for scope in legacy.scopes().await? {
+                if !scopes.contains(&scope) {
+                    scopes.push(scope);
+                }
+            }
+        }
+
+        Ok(scopes)
+    }
+}
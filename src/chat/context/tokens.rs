@@ -0,0 +1,37 @@
+/// Tokens taken by every message on top of its content (role, separators, etc).
+pub const MESSAGE_OVERHEAD: usize = 4;
+
+/// Rough cost of an attached image, providers vary a lot.
+pub const IMAGE_TOKENS: usize = 1000;
+
+/// Counts how many tokens a piece of text takes up in the model's context.
+///
+/// Any `Fn(&str) -> usize` is a tokenizer, so the model's real one can be plugged in
+/// with [super::ChatContext::set_tokenizer].
+pub trait Tokenizer: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+}
+
+impl<F> Tokenizer for F
+where
+    F: Fn(&str) -> usize + Send + Sync,
+{
+    fn count(&self, text: &str) -> usize {
+        self(text)
+    }
+}
+
+/// Estimates around 4 characters per token, never less than a token per word.
+///
+/// Good enough to budget a context window without knowing the model's vocabulary.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count(&self, text: &str) -> usize {
+        let chars = text.chars().count().div_ceil(4);
+        let words = text.split_whitespace().count();
+
+        chars.max(words)
+    }
+}
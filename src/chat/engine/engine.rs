@@ -1,70 +1,253 @@
-use std::ops::{Deref, DerefMut};
+use std::{
+    ops::{Deref, DerefMut},
+    sync::Arc,
+};
 
 use anyhow::anyhow;
-use serenity::all::UserId;
+use chrono_tz::Tz;
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
     chat::{
-        client::{CompletionAgent, CompletionResult},
-        context::{ContextWindow, MessageIdentifier},
+        client::{
+            CompletionAgent, CompletionAgentSettings, CompletionResult, ModelRegistry,
+            ProviderFailure, StreamEvent,
+        },
+        context::{
+            Activities, Attachment, ContextWindow, ConversationScope, ConversationStore,
+            MessageIdentifier, Speaker,
+        },
+        reminder::{Reminder, Reminders},
     },
-    config::{store::ChatBotConfig, structure::ChatBotConfigInner},
+    config::{diff::ConfigChanges, store::ChatBotConfig, structure::ChatBotConfigInner},
+    utils::Environment,
 };
 
 use super::super::context::{ChatContext, ChatMessage};
 
+/// Size the story so far is compacted at, unless configured otherwise.
+const DEFAULT_SUMMARY_TOKENS: usize = 1500;
+
 pub struct ChatEngine {
     pub client: CompletionAgent,
-    user_id: UserId,
+    scope: ConversationScope,
     context: ChatContext,
+    reminders: Arc<Reminders>,
 }
 
 impl ChatEngine {
-    pub async fn new(config: ChatBotConfig, user_id: UserId) -> anyhow::Result<Self> {
-        let ChatBotConfigInner {
-            context: context_config,
-            llm: llm_config,
-            ..
-        } = config.into_inner();
+    pub async fn new(
+        config: ChatBotConfig,
+        models: &ModelRegistry,
+        env: &Environment,
+        reminders: Arc<Reminders>,
+        activities: Arc<Activities>,
+        store: Option<Arc<dyn ConversationStore>>,
+        scope: ConversationScope,
+    ) -> anyhow::Result<Self> {
+        let config = config.into_inner();
 
-        let context = ChatContext::new(&context_config, user_id).await;
-        let client = CompletionAgent::new(
-            llm_config,
-            user_id,
-            context_config.system.user_name,
-            context_config.system.chatbot_name,
-        )
-        .await?;
+        let mut context =
+            ChatContext::new(&config.context, scope, env.clone(), activities, store).await;
+        let client = Self::persona_client(&config, models, scope, &mut context, &reminders).await?;
 
         Ok(Self {
             client,
             context,
-            user_id,
+            scope,
+            reminders,
         })
     }
 
     // initializes with
-    pub async fn reload(self, config: ChatBotConfig) -> anyhow::Result<Self> {
-        // let client = client.unwrap_or(ChatClient::new(&config.llm, user_id))
+    pub async fn reload(
+        mut self,
+        config: ChatBotConfig,
+        models: &ModelRegistry,
+    ) -> anyhow::Result<Self> {
+        let config = config.into_inner();
+
+        let client = Self::persona_client(
+            &config,
+            models,
+            self.scope,
+            &mut self.context,
+            &self.reminders,
+        )
+        .await?;
+
+        Ok(Self {
+            client,
+            context: self.context,
+            scope: self.scope,
+            reminders: self.reminders,
+        })
+    }
+
+    /// Switches the conversation over to another persona, `None` being the default one.
+    pub async fn switch_persona(
+        &mut self,
+        config: ChatBotConfig,
+        models: &ModelRegistry,
+        persona: Option<String>,
+    ) -> anyhow::Result<()> {
+        let config = config.into_inner();
+        config.with_persona(persona.as_deref())?;
+
+        let previous = self.context.persona().map(str::to_string);
+        self.context.set_persona(persona);
+
+        match Self::persona_client(
+            &config,
+            models,
+            self.scope,
+            &mut self.context,
+            &self.reminders,
+        )
+        .await
+        {
+            Ok(client) => {
+                self.client = client;
+                Ok(())
+            }
+            Err(why) => {
+                self.context.set_persona(previous);
+                Err(why)
+            }
+        }
+    }
+
+    /// Applies a config change, only rebuilding what depends on the sections that changed.
+    pub async fn apply_config(
+        &mut self,
+        config: ChatBotConfig,
+        models: &ModelRegistry,
+        changes: ConfigChanges,
+    ) -> anyhow::Result<()> {
+        let config = config.into_inner();
+
+        // the memory storage is part of the client, nothing is left to reuse
+        if changes.embedding {
+            self.client = Self::persona_client(
+                &config,
+                models,
+                self.scope,
+                &mut self.context,
+                &self.reminders,
+            )
+            .await?;
+            return Ok(());
+        }
+
+        let persona_config = Self::persona_config(&config, self.scope, &mut self.context).await;
+
+        if changes.system || changes.context {
+            let system = &persona_config.context.system;
+            let current = &self.context.config.system;
+
+            // the memory tools are named after both sides of the conversation, reminders
+            // are set in the timezone, memories and profiles are kept in the folder
+            if system.user_name != current.user_name
+                || system.chatbot_name != current.chatbot_name
+                || system.timezone != current.timezone
+                || persona_config.context.save_to_disk_folder
+                    != self.context.config.save_to_disk_folder
+            {
+                self.client = Self::persona_client(
+                    &config,
+                    models,
+                    self.scope,
+                    &mut self.context,
+                    &self.reminders,
+                )
+                .await?;
+                return Ok(());
+            }
+
+            self.context.config = persona_config.context;
+        }
+
+        if changes.completion {
+            // tools are only set up along with the client
+            if persona_config.llm.use_tools != self.client.config().use_tools {
+                self.client = Self::persona_client(
+                    &config,
+                    models,
+                    self.scope,
+                    &mut self.context,
+                    &self.reminders,
+                )
+                .await?;
+                return Ok(());
+            }
+
+            self.client
+                .update_completion(persona_config.llm, models)
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    /// The config as seen by the context's persona, falling back to the default persona if
+    /// it's no longer configured. Direct messages also get the user's profile merged into
+    /// the prompt.
+    async fn persona_config(
+        config: &ChatBotConfigInner,
+        scope: ConversationScope,
+        context: &mut ChatContext,
+    ) -> ChatBotConfigInner {
+        let mut config = match config.with_persona(context.persona()) {
+            Ok(config) => config,
+            Err(why) => {
+                log::warn!("{why}, falling back to the default persona for {scope}");
+                context.set_persona(None);
+                config.clone()
+            }
+        };
+
+        if let ConversationScope::Direct(user) = scope {
+            context
+                .profile(user)
+                .await
+                .apply(&mut config.context.system);
+        }
+
+        config
+    }
+
+    /// Creates the client of the context's persona and hands its prompt to the context.
+    async fn persona_client(
+        config: &ChatBotConfigInner,
+        models: &ModelRegistry,
+        scope: ConversationScope,
+        context: &mut ChatContext,
+        reminders: &Arc<Reminders>,
+    ) -> anyhow::Result<CompletionAgent> {
         let ChatBotConfigInner {
             context: context_config,
             llm: llm_config,
             ..
-        } = config.into_inner();
+        } = Self::persona_config(config, scope, context).await;
 
         let client = CompletionAgent::new(
             llm_config,
-            self.user_id,
-            context_config.system.user_name,
-            context_config.system.chatbot_name,
+            models,
+            scope,
+            context.persona(),
+            CompletionAgentSettings {
+                user_name: context_config.system.user_name.clone(),
+                assistant_name: context_config.system.chatbot_name.clone(),
+                timezone: context_config.system.timezone.unwrap_or(Tz::UTC),
+            },
+            context_config.save_to_disk_folder.as_deref(),
+            reminders.clone(),
         )
         .await?;
 
-        Ok(Self {
-            client,
-            context: self.context,
-            user_id: self.user_id,
-        })
+        context.config = context_config;
+
+        Ok(client)
     }
 
     pub fn into_context(self) -> ChatContext {
@@ -74,7 +257,22 @@ impl ChatEngine {
     pub async fn user_prompt(
         &mut self,
         prompt: Option<(String, MessageIdentifier)>,
+        speaker: Option<Speaker>,
         context: Option<ContextType>,
+    ) -> anyhow::Result<ChatMessage> {
+        self.user_prompt_stream(prompt, speaker, vec![], context, None)
+            .await
+    }
+
+    /// Same as [ChatEngine::user_prompt], with the prompt's attachments and streaming the
+    /// response to `deltas` when given.
+    pub async fn user_prompt_stream(
+        &mut self,
+        prompt: Option<(String, MessageIdentifier)>,
+        speaker: Option<Speaker>,
+        attachments: Vec<Attachment>,
+        context: Option<ContextType>,
+        deltas: Option<UnboundedSender<StreamEvent>>,
     ) -> anyhow::Result<ChatMessage> {
         let retries = 5;
 
@@ -85,15 +283,36 @@ impl ChatEngine {
                 None => (None, None),
             };
 
-            let context: ContextWindow = match context {
-                Some(ContextType::User) => self.context.get_context(prompt).await?,
+            let mut context: ContextWindow = match context {
+                Some(ContextType::User) => {
+                    self.context
+                        .get_context(prompt, speaker.clone(), attachments.clone())
+                        .await?
+                }
                 Some(ContextType::Freewill) => self.context.freewill_context(prompt).await?,
+                Some(ContextType::Reminder(ref reminder)) => {
+                    self.context.reminder_context(reminder).await?
+                }
                 Some(ContextType::Regen(ref message_id)) => {
                     self.context.get_regen_context(message_id).await?
                 }
-                None => self.context.get_context(prompt).await?,
+                None => {
+                    self.context
+                        .get_context(prompt, speaker.clone(), attachments.clone())
+                        .await?
+                }
             };
 
+            if !context.drained.is_empty() {
+                if let Err(why) = self.update_summary(context.drained).await {
+                    log::error!("failed to update the story so far: {why:?}");
+                }
+
+                // the system prompt tells the story so far, which only now has what was
+                // just drained
+                context.system_prompt = self.context.system_prompt(speaker.as_ref()).to_string();
+            }
+
             if let Some(drained) = context.overflow {
                 log::info!("drained {} messages", drained.len());
                 self.client
@@ -112,63 +331,102 @@ impl ChatEngine {
             }
             .ok_or(anyhow!("unable to get a user prompt"))?;
 
-            // retry if we get an error as well, but only up to the max retries
-            let response = match self
-                .client
-                .completion(&mut prompt, context.system_prompt, context.history)
-                .await
-            {
+            //? traditional RAG
+            self.client.rag_recall(&mut prompt).await?;
+            self.context.fit_memories(&mut prompt);
+
+            // retry malformed responses as well, but only up to the max retries
+            let response = match &deltas {
+                Some(deltas) => {
+                    self.client
+                        .completion_stream(&prompt, context.system_prompt, context.history, deltas)
+                        .await
+                }
+                None => {
+                    self.client
+                        .completion(&prompt, context.system_prompt, context.history)
+                        .await
+                }
+            };
+
+            let response = match response {
                 Ok(response) => response,
                 Err(why) => {
-                    if i + 1 >= retries {
+                    // provider failures were already retried and fell back by the client
+                    if i + 1 >= retries || why.downcast_ref::<ProviderFailure>().is_some() {
                         return Err(why);
                     } else {
                         log::warn!("error:\n{why:?}\nretrying, attempt {i}");
+                        Self::reset_stream(&deltas);
                         i += 1;
                         continue;
                     }
                 }
             };
 
-            match response {
-                CompletionResult::Message(completion_message) => {
-                    let message = ChatMessage::from(completion_message);
-
-                    let content = message.content();
-
-                    if let Some(content) = content {
-                        log::trace!("output:\n{content}");
-
-                        if content.len() > 0 {
-                            self.context.add_user_message(
-                                prompt,
-                                message_id.unwrap_or(MessageIdentifier::random()),
-                            )?;
-                            return Ok(message);
-                        } else {
-                            log::error!("no content in message");
-                            i += 1;
-                            continue;
-                        }
-                    } else {
-                        log::error!("no content in message");
-                        continue;
-                    }
-                }
-                CompletionResult::Tool((call, response)) => {
-                    self.context.add_message(ChatMessage::from(call), None);
-                    self.context.add_message(ChatMessage::from(response), None);
+            let CompletionResult { message, tools } = response;
+
+            if !tools.is_empty() {
+                log::info!("tools used in this response: {}", tools.join(", "));
+            }
 
-                    log::info!("called functions, prompting again");
+            if let Some(content) = message.content() {
+                log::trace!("output:\n{content}");
 
+                if content.len() > 0 {
+                    let message_id = message_id.unwrap_or_else(|| self.context.random_id());
+                    self.context.add_user_message(prompt, message_id)?;
+                    return Ok(message);
+                } else {
+                    log::error!("no content in message");
+                    Self::reset_stream(&deltas);
+                    i += 1;
                     continue;
                 }
+            } else {
+                log::error!("no content in message");
+                Self::reset_stream(&deltas);
+                continue;
             }
         }
 
         Err(anyhow::anyhow!("too many retries"))
     }
 
+    /// Folds drained messages into the story so far, compacting it when it gets too big.
+    async fn update_summary(&mut self, drained: Vec<ChatMessage>) -> anyhow::Result<()> {
+        let config = self.context.config.summary.clone().unwrap_or_default();
+        if !config.enabled.unwrap_or(true) {
+            return Ok(());
+        }
+
+        let mut summary = self
+            .client
+            .summarize_story(
+                self.context.summary(),
+                drained,
+                &self.context.config.system.user_name,
+                &self.context.config.system.chatbot_name,
+            )
+            .await?;
+
+        let max_tokens = config.max_tokens.unwrap_or(DEFAULT_SUMMARY_TOKENS);
+        if self.context.count_tokens(&summary) > max_tokens {
+            log::info!("story so far is over {max_tokens} tokens, compacting");
+            summary = self.client.compact_story(&summary, max_tokens).await?;
+        }
+
+        self.context.set_summary(summary);
+
+        Ok(())
+    }
+
+    fn reset_stream(deltas: &Option<UnboundedSender<StreamEvent>>) {
+        if let Some(deltas) = deltas {
+            let _ = deltas.send(StreamEvent::Reset);
+        }
+    }
+
     pub async fn summarize_and_store(
         &self,
         context: Vec<ChatMessage>,
@@ -182,8 +440,8 @@ impl ChatEngine {
         self.context.shutdown().await
     }
 
-    pub fn clear_context(&mut self) {
-        self.context.clear()
+    pub async fn clear_context(&mut self) -> anyhow::Result<()> {
+        self.context.clear().await
     }
 }
 
@@ -205,4 +463,6 @@ pub enum ContextType {
     User,
     Freewill,
     Regen(MessageIdentifier),
+    /// A reminder that came due, for the bot to bring up
+    Reminder(Reminder),
 }
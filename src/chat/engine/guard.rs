@@ -1,42 +1,65 @@
-use serenity::all::UserId;
 use std::collections::HashMap;
 use tokio::sync::{RwLock, RwLockReadGuard};
 
-use crate::{bot::Data, utils::macros::config};
+use crate::{bot::Data, chat::context::ConversationScope, utils::macros::config};
 
-use super::ChatEngine;
+use super::{ChatEngine, residency};
 
 /// Wraps an engine reference together with its write guard.
 pub struct EngineGuard<'a> {
     // Keep the guard so the reference remains valid.
-    _guard: RwLockReadGuard<'a, HashMap<UserId, RwLock<ChatEngine>>>,
-    user: UserId,
+    _guard: RwLockReadGuard<'a, HashMap<ConversationScope, RwLock<ChatEngine>>>,
+    scope: ConversationScope,
 }
 
 impl<'a> EngineGuard<'a> {
-    pub async fn lock(data: &'a Data, user: UserId) -> anyhow::Result<Self> {
-        let user_map = data.user_map.read().await;
-        let contains = user_map.contains_key(&user);
-        drop(user_map);
-        match contains {
-            true => (),
-            false => {
-                let mut user_map = data.user_map.write().await;
-                let config = config!(data);
-                let engine = ChatEngine::new(config, user.clone()).await?;
-
-                user_map.insert(user, RwLock::new(engine));
+    /// Locks the engine of a scope, loading it first if it is not resident.
+    pub async fn lock(data: &'a Data, scope: ConversationScope) -> anyhow::Result<Self> {
+        let guard = Self::peek(data, scope).await?;
+        residency::touch(data, scope);
+
+        Ok(guard)
+    }
+
+    /// Same as [EngineGuard::lock], except that an engine that is already loaded is not
+    /// marked as used, so that looking at it does not keep it from being evicted. One it
+    /// has to load is marked as used, like every freshly loaded engine.
+    pub async fn peek(data: &'a Data, scope: ConversationScope) -> anyhow::Result<Self> {
+        let contains = data.user_map.read().await.contains_key(&scope);
+        if !contains {
+            // built before taking the write lock, so that loading one engine does not stall
+            // every other conversation
+            let config = config!(data);
+            let engine = ChatEngine::new(
+                config,
+                &data.models,
+                &data.env,
+                data.reminders.clone(),
+                data.activities.clone(),
+                data.store().await,
+                scope,
+            )
+            .await?;
+
+            let mut user_map = data.user_map.write().await;
+            // it might have been loaded while this one was being built
+            if !user_map.contains_key(&scope) {
+                residency::make_room(data, &mut user_map).await;
+
+                data.track(engine.user_message_ids());
+                user_map.insert(scope, RwLock::new(engine));
+                residency::touch(data, scope);
             }
-        };
+        }
 
         let user_map = data.user_map.read().await;
         Ok(Self {
             _guard: user_map,
-            user,
+            scope,
         })
     }
 
     pub async fn engine(&self) -> &RwLock<ChatEngine> {
-        self._guard.get(&self.user).unwrap()
+        self._guard.get(&self.scope).unwrap()
     }
 }
@@ -1,5 +1,7 @@
 mod engine;
 mod guard;
+mod residency;
 
 pub use engine::{ChatEngine, ContextType};
 pub use guard::EngineGuard;
+pub use residency::{apply_config, evict_idle, touch};
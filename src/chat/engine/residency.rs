@@ -0,0 +1,234 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use tokio::sync::RwLock;
+
+use crate::{
+    bot::Data,
+    chat::context::{ConversationScope, ConversationStore, open_store},
+    config::diff::ConfigChanges,
+    utils::macros::config,
+};
+
+use super::ChatEngine;
+
+/// How long an engine stays loaded without being used, unless configured otherwise.
+const DEFAULT_IDLE_SECS: u64 = 1800;
+/// How many engines can be loaded at once, unless configured otherwise.
+const DEFAULT_MAX_RESIDENT: usize = 64;
+
+type UserMap = HashMap<ConversationScope, RwLock<ChatEngine>>;
+
+/// Marks the engine of a scope as just used.
+pub fn touch(data: &Data, scope: ConversationScope) {
+    if let Ok(mut last_used) = data.last_used.lock() {
+        last_used.insert(scope, Instant::now());
+    }
+}
+
+/// When the engine of a scope was last used, if it was loaded through [super::EngineGuard].
+fn last_used(data: &Data, scope: ConversationScope) -> Option<Instant> {
+    data.last_used
+        .lock()
+        .ok()
+        .and_then(|last_used| last_used.get(&scope).copied())
+}
+
+/// Saves the engine of a scope and unloads it, it is loaded back on its next use.
+async fn evict(
+    data: &Data,
+    user_map: &mut UserMap,
+    scope: ConversationScope,
+) -> anyhow::Result<()> {
+    if let Some(engine) = user_map.get(&scope) {
+        let engine = engine.read().await;
+        // keep it loaded if it cannot be saved, it would be lost otherwise
+        engine.shutdown().await?;
+        let messages = engine.user_message_ids();
+        drop(engine);
+
+        user_map.remove(&scope);
+        data.untrack(scope, messages);
+    }
+
+    if let Ok(mut last_used) = data.last_used.lock() {
+        last_used.remove(&scope);
+    }
+    data.saved_scopes.write().await.insert(scope);
+
+    Ok(())
+}
+
+/// Evicts the least recently used engines until there is room for one more.
+pub async fn make_room(data: &Data, user_map: &mut UserMap) {
+    let context = config!(data).context;
+
+    // nothing is saved, evicting would lose the conversation
+    if context.save_to_disk_folder.is_none() {
+        log::debug!("conversations are not saved, not evicting any to make room");
+        return;
+    }
+
+    let max_resident = context
+        .residency
+        .and_then(|residency| residency.max_resident)
+        .unwrap_or(DEFAULT_MAX_RESIDENT);
+
+    // 0 means no limit
+    while max_resident > 0 && user_map.len() >= max_resident {
+        let Some(scope) = user_map
+            .keys()
+            .copied()
+            .min_by_key(|scope| last_used(data, *scope))
+        else {
+            return;
+        };
+
+        log::info!("{} engines loaded, evicting {scope}", user_map.len());
+
+        if let Err(why) = evict(data, user_map, scope).await {
+            log::error!("failed to evict {scope}: {why:?}");
+            return;
+        }
+    }
+}
+
+/// Forgets the kind of every channel without a loaded conversation, every guild message
+/// resolves its channel whether or not it is answered.
+async fn forget_channel_kinds(data: &Data) {
+    let user_map = data.user_map.read().await;
+    data.channel_kinds.retain(|channel| {
+        user_map.contains_key(&ConversationScope::Channel(channel))
+            || user_map.contains_key(&ConversationScope::Thread(channel))
+    });
+}
+
+/// Evicts every engine that was not used for longer than the configured idle time.
+pub async fn evict_idle(data: &Data) {
+    forget_channel_kinds(data).await;
+
+    let context = config!(data).context;
+
+    // nothing is saved, evicting would lose the conversation
+    if context.save_to_disk_folder.is_none() {
+        return;
+    }
+
+    let idle = context
+        .residency
+        .and_then(|residency| residency.idle_secs)
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(DEFAULT_IDLE_SECS));
+
+    // 0 means never
+    if idle.is_zero() {
+        return;
+    }
+
+    let is_idle =
+        |scope: ConversationScope| last_used(data, scope).is_none_or(|last| last.elapsed() >= idle);
+
+    // most of the time nothing is idle, which should not hold up every other engine
+    let idle_scopes = data
+        .user_map
+        .read()
+        .await
+        .keys()
+        .copied()
+        .filter(|scope| is_idle(*scope))
+        .collect::<Vec<_>>();
+    if idle_scopes.is_empty() {
+        return;
+    }
+
+    let mut user_map = data.user_map.write().await;
+    for scope in idle_scopes {
+        // used again while waiting for the lock
+        if !is_idle(scope) {
+            continue;
+        }
+
+        log::info!("evicting {scope}, idle for over {}s", idle.as_secs());
+
+        if let Err(why) = evict(data, &mut user_map, scope).await {
+            log::error!("failed to evict {scope}: {why:?}");
+        }
+    }
+}
+
+/// Updates every resident engine after the config changed, evicted ones pick the new
+/// config up once they are loaded again.
+pub async fn apply_config(data: &Data, changes: ConfigChanges) {
+    if changes.is_empty() {
+        return;
+    }
+
+    let config = config!(data);
+    let store = match changes.store {
+        true => reopen_store(data).await,
+        false => None,
+    };
+
+    let user_map = data.user_map.read().await;
+    for (scope, engine) in user_map.iter() {
+        let mut engine = engine.write().await;
+
+        let result: anyhow::Result<()> = async {
+            // resident conversations move along to the new store
+            if let Some(store) = &store {
+                engine.set_store(store.clone());
+                engine.save().await?;
+            }
+
+            engine
+                .apply_config(config.clone(), &data.models, changes)
+                .await
+        }
+        .await;
+
+        if let Err(why) = result {
+            log::error!("failed to apply the new config to {scope}: {why:?}");
+        }
+    }
+}
+
+/// Opens the newly configured conversation store in place of the old one, returning it
+/// unless it could not be opened, in which case the old one is kept.
+async fn reopen_store(data: &Data) -> Option<Option<Arc<dyn ConversationStore>>> {
+    let config = config!(data);
+
+    let store = match open_store(&config.context) {
+        Ok(store) => store,
+        Err(why) => {
+            log::error!("failed to open the new conversation store, keeping the old one: {why:?}");
+            return None;
+        }
+    };
+
+    // saved conversations are looked up in the new store from now on
+    let scopes = match &store {
+        Some(store) => store.scopes().await.unwrap_or_else(|why| {
+            log::error!("failed to list the conversations of the new store: {why:?}");
+            vec![]
+        }),
+        None => vec![],
+    };
+    {
+        let mut saved_scopes = data.saved_scopes.write().await;
+        saved_scopes.clear();
+        saved_scopes.extend(scopes);
+    }
+
+    *data.store.write().await = store.clone();
+    // profiles are next to the conversations
+    data.profiles.write().await.clear();
+    log::warn!(
+        "opened the new conversation store, reminders and freewill activity stay where they \
+        were until the bot is restarted"
+    );
+
+    Some(store)
+}
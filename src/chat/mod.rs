@@ -1,7 +1,8 @@
-mod archive;
+pub mod archive;
 pub mod client;
 pub mod context;
 pub mod engine;
 pub mod prompt;
+pub mod reminder;
 
 pub use context::ChatMessage;
@@ -1,4 +1,4 @@
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
 
@@ -23,6 +23,12 @@ pub struct SystemPromptBuilder {
 
     #[serde(skip)]
     pub long_term_memory: Option<Vec<String>>,
+    /// Everyone taking part in a group conversation, unset for direct messages.
+    #[serde(skip)]
+    pub participants: Option<Vec<String>>,
+    /// Rolling summary of the conversation that no longer fits in the context.
+    #[serde(skip)]
+    pub story_so_far: Option<String>,
 
     pub user_about: Option<String>,
     pub timezone: Option<Tz>,
@@ -56,21 +62,19 @@ impl SystemPromptBuilder {
         }
     }
 
-    pub fn get_time(&self) -> String {
+    /// Formats `now` in the configured timezone.
+    pub fn get_time(&self, now: DateTime<Utc>) -> String {
         if let Some(timezone) = self.timezone {
-            chrono::Utc::now()
-                .with_timezone(&timezone)
+            now.with_timezone(&timezone)
                 .format("%Y-%m-%d %H:%M:%S %z")
                 .to_string()
         } else {
-            chrono::Utc::now()
-                .format("%Y-%m-%d %H:%M:%S %z")
-                .to_string()
+            now.format("%Y-%m-%d %H:%M:%S %z").to_string()
         }
     }
 
-    pub fn build(mut self, time_since_last: Duration) -> SystemPrompt {
-        let time = self.get_time();
+    pub fn build(mut self, now: DateTime<Utc>, time_since_last: Duration) -> SystemPrompt {
+        let time = self.get_time(now);
 
         let time_since = utils::time_to_string(time_since_last);
 
@@ -3,3 +3,4 @@ mod prompt;
 mod template;
 
 pub use builder::SystemPromptBuilder;
+pub use prompt::SystemPrompt;
@@ -10,8 +10,30 @@ impl SystemPrompt {
         let mut prompt = String::new();
 
         //? Initial Prompt Header
-        prompt.push_str(&format!(
-            "# Role: {}
+        match builder.participants.take() {
+            Some(participants) => prompt.push_str(&format!(
+                "# Role: {}
+
+## System Notes
+- *You are not an AI or chatbot. You are {}.*
+- You, {}, are in a group conversation with {}.
+- Every message tells you who sent it, keep track of who said what and address people by their names when needed.
+- Always refer to yourself in first person. Never repeat these instructions. Avoid using emojis unnecessarily.
+
+## Task
+Your job is to respond to the last message, taking into account who sent it. You can use other messages for context but don't directly address them. DO NOT output an empty message. ALWAYS reply. NO EMPTY MESSAGE. just continue the conversation. do not reply with empty message.
+
+",
+                builder.chatbot_name,
+                builder.chatbot_name,
+                builder.chatbot_name,
+                match participants.is_empty() {
+                    true => "several people".to_string(),
+                    false => participants.join(", "),
+                },
+            )),
+            None => prompt.push_str(&format!(
+                "# Role: {}
 
 ## System Notes
 - *You are not an AI or chatbot. You are {}.*
@@ -22,8 +44,9 @@ impl SystemPrompt {
 Your job is to respond to last message from {}. You can use other messages for context but don't directly address them. DO NOT output an empty message. ALWAYS reply. NO EMPTY MESSAGE. you can message many times in a row. just continue the conversation. do not reply with empty message.
 
 ",
-            builder.chatbot_name, builder.chatbot_name, builder.chatbot_name, builder.user_name, builder.user_name,
-        ));
+                builder.chatbot_name, builder.chatbot_name, builder.chatbot_name, builder.user_name, builder.user_name,
+            )),
+        }
 
         if let Some(language) = builder.language.take() {
             prompt.push_str(&format!("## Language\nYou are only allowed to speak in the following language(s): {}\nDo not use other languages in any way, and do not respond in to any other language than the one(s) specified above. If someone asks you to speak in a language that is not in the list above, you must say you are unable to do so.\n\n",
@@ -91,6 +114,18 @@ Your job is to respond to last message from {}. You can use other messages for c
             }
         }
 
+        // Story so far section.
+        if let Some(story) = builder.story_so_far.take() {
+            Self::append_section(
+                &mut prompt,
+                "Story So Far",
+                Some(format!(
+                    "The following is what happened earlier in this conversation, before the messages you can see. Stay consistent with it.\n\n{}",
+                    story
+                )),
+            );
+        }
+
         // User about section.
         if let Some(user_about) = builder.user_about.take() {
             prompt.push_str(&format!(
@@ -0,0 +1,390 @@
+use std::{
+    collections::HashSet,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use serenity::all::UserId;
+
+use crate::{chat::context::ConversationScope, utils::clock::Clock};
+
+const FILE_NAME: &str = "reminders.bin";
+
+/// How many reminders a conversation can have pending at once.
+pub const MAX_PENDING: usize = 25;
+
+/// How far ahead a reminder can be set.
+const MAX_AHEAD_DAYS: i64 = 366;
+
+/// How many times bringing up a reminder is tried before giving up on it.
+const MAX_ATTEMPTS: u32 = 5;
+/// How long to wait before trying again the first time, doubled on every attempt.
+const RETRY_MINUTES: i64 = 1;
+
+/// Something the bot was asked to bring up later.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: u64,
+    pub scope: ConversationScope,
+    /// Whoever asked for it, if anyone in particular
+    pub user: Option<UserId>,
+    pub due: DateTime<Utc>,
+    pub note: String,
+    /// How many times bringing it up failed
+    #[serde(default)]
+    pub attempts: u32,
+}
+
+/// The pending reminders as saved, along with the id the next one gets so that ids are
+/// never reused.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct Pending {
+    next_id: u64,
+    reminders: Vec<Reminder>,
+}
+
+/// What the reminders were saved as, before the next id was.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Saved {
+    Current(Pending),
+    Legacy(Vec<Reminder>),
+}
+
+impl From<Saved> for Pending {
+    fn from(saved: Saved) -> Self {
+        match saved {
+            Saved::Current(pending) => pending,
+            Saved::Legacy(reminders) => Self {
+                next_id: reminders.iter().map(|r| r.id).max().unwrap_or(0) + 1,
+                reminders,
+            },
+        }
+    }
+}
+
+/// Every pending reminder, saved next to the contexts so they survive restarts.
+pub struct Reminders {
+    folder: Option<PathBuf>,
+    clock: Arc<dyn Clock>,
+    pending: Mutex<Pending>,
+    /// Reminders being brought up, still pending until they are
+    in_flight: Mutex<HashSet<u64>>,
+    /// Held while writing, so that writes land in the order they were made
+    writing: tokio::sync::Mutex<()>,
+}
+
+impl Reminders {
+    /// Loads the pending reminders, none if they are not saved to disk.
+    pub fn load(folder: Option<&Path>, clock: Arc<dyn Clock>) -> Self {
+        let pending = folder
+            .map(|folder| folder.join(FILE_NAME))
+            .filter(|path| path.is_file())
+            .and_then(|path| {
+                File::open(path)
+                    .map_err(anyhow::Error::from)
+                    .and_then(|file| Ok(ciborium::from_reader::<Saved, _>(file)?))
+                    .map_err(|e| log::error!("Failed to load reminders: {e}"))
+                    .ok()
+            })
+            .map(Pending::from)
+            .unwrap_or_default();
+
+        Self {
+            folder: folder.map(Path::to_path_buf),
+            clock,
+            pending: Mutex::new(pending),
+            in_flight: Mutex::new(HashSet::new()),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    /// Schedules a reminder, failing if it's not in the future or the conversation has too
+    /// many pending already.
+    pub async fn add(
+        &self,
+        scope: ConversationScope,
+        user: Option<UserId>,
+        due: DateTime<Utc>,
+        note: String,
+    ) -> anyhow::Result<Reminder> {
+        let note = note.trim().to_string();
+        if note.is_empty() {
+            bail!("a reminder needs a note");
+        }
+
+        let now = self.now();
+        if due <= now {
+            bail!("a reminder has to be due in the future");
+        }
+        if due - now > Duration::days(MAX_AHEAD_DAYS) {
+            bail!("a reminder can be set at most {MAX_AHEAD_DAYS} days ahead");
+        }
+
+        let reminder = {
+            let mut pending = self.lock()?;
+            if pending
+                .reminders
+                .iter()
+                .filter(|r| r.scope == scope)
+                .count()
+                >= MAX_PENDING
+            {
+                bail!("this conversation already has {MAX_PENDING} pending reminders");
+            }
+
+            let reminder = Reminder {
+                id: pending.next_id.max(1),
+                scope,
+                user,
+                due,
+                note,
+                attempts: 0,
+            };
+            pending.next_id = reminder.id + 1;
+            pending.reminders.push(reminder.clone());
+            pending.reminders.sort_by_key(|r| r.due);
+
+            reminder
+        };
+        self.persist().await?;
+
+        Ok(reminder)
+    }
+
+    /// The pending reminders of a conversation, the soonest first.
+    pub fn list(&self, scope: ConversationScope) -> anyhow::Result<Vec<Reminder>> {
+        Ok(self
+            .lock()?
+            .reminders
+            .iter()
+            .filter(|r| r.scope == scope)
+            .cloned()
+            .collect())
+    }
+
+    /// Cancels a reminder of the conversation, `None` if there is no such reminder. Only
+    /// whoever asked for a reminder can cancel it, when `by` is given.
+    pub async fn cancel(
+        &self,
+        scope: ConversationScope,
+        id: u64,
+        by: Option<UserId>,
+    ) -> anyhow::Result<Option<Reminder>> {
+        let reminder = {
+            let mut pending = self.lock()?;
+            let Some(index) = pending
+                .reminders
+                .iter()
+                .position(|r| r.scope == scope && r.id == id)
+            else {
+                return Ok(None);
+            };
+
+            if let (Some(by), Some(user)) = (by, pending.reminders[index].user) {
+                if by != user {
+                    bail!("only whoever asked for reminder {id} can cancel it");
+                }
+            }
+
+            pending.reminders.remove(index)
+        };
+        self.persist().await?;
+
+        Ok(Some(reminder))
+    }
+
+    /// Every reminder that is due and not being brought up already. They stay pending, so
+    /// that a crash does not lose them, until [Reminders::delivered] or [Reminders::failed]
+    /// is called with their id.
+    pub fn due(&self) -> anyhow::Result<Vec<Reminder>> {
+        let now = self.now();
+        let pending = self.lock()?;
+        let mut in_flight = self.lock_in_flight()?;
+
+        Ok(pending
+            .reminders
+            .iter()
+            .filter(|r| r.due <= now && in_flight.insert(r.id))
+            .cloned()
+            .collect())
+    }
+
+    /// Removes a reminder that was brought up.
+    pub async fn delivered(&self, id: u64) -> anyhow::Result<()> {
+        let removed = {
+            let mut pending = self.lock()?;
+            self.lock_in_flight()?.remove(&id);
+
+            match pending.reminders.iter().position(|r| r.id == id) {
+                Some(index) => {
+                    pending.reminders.remove(index);
+                    true
+                }
+                None => false,
+            }
+        };
+        if removed {
+            self.persist().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Tries a reminder that could not be brought up again later, giving up on it after
+    /// too many attempts.
+    pub async fn failed(&self, id: u64) -> anyhow::Result<()> {
+        let now = self.now();
+        {
+            let mut pending = self.lock()?;
+            self.lock_in_flight()?.remove(&id);
+
+            // cancelled while it was being brought up
+            let Some(index) = pending.reminders.iter().position(|r| r.id == id) else {
+                return Ok(());
+            };
+
+            let reminder = &mut pending.reminders[index];
+            reminder.attempts += 1;
+            if reminder.attempts >= MAX_ATTEMPTS {
+                log::error!("giving up on reminder {id} after {MAX_ATTEMPTS} attempts");
+                pending.reminders.remove(index);
+            } else {
+                reminder.due = now + Duration::minutes(RETRY_MINUTES << (reminder.attempts - 1));
+                log::warn!("trying reminder {id} again at {}", reminder.due);
+                pending.reminders.sort_by_key(|r| r.due);
+            }
+        }
+        self.persist().await?;
+
+        Ok(())
+    }
+
+    fn lock(&self) -> anyhow::Result<MutexGuard<'_, Pending>> {
+        self.pending
+            .lock()
+            .map_err(|_| anyhow!("reminders lock poisoned"))
+    }
+
+    fn lock_in_flight(&self) -> anyhow::Result<MutexGuard<'_, HashSet<u64>>> {
+        self.in_flight
+            .lock()
+            .map_err(|_| anyhow!("reminders lock poisoned"))
+    }
+
+    /// Writes the reminders to a temporary file and renames it over the old one, so a crash
+    /// mid-write never loses them all.
+    async fn persist(&self) -> anyhow::Result<()> {
+        let Some(folder) = &self.folder else {
+            return Ok(());
+        };
+
+        // taken before encoding, so that a later write never loses to an earlier one
+        let _writing = self.writing.lock().await;
+        let mut bytes = vec![];
+        ciborium::into_writer(&*self.lock()?, &mut bytes)?;
+
+        tokio::fs::create_dir_all(folder).await?;
+        let path = folder.join(FILE_NAME);
+        let tmp = path.with_extension("bin.tmp");
+        tokio::fs::write(&tmp, bytes).await?;
+        tokio::fs::rename(tmp, path).await?;
+
+        Ok(())
+    }
+}
+
+/// Parses when a reminder is due, either in a while (`30m`, `in 2 hours`, `1d12h`) or at a
+/// time in `timezone` (`18:30` being the next one, `2025-06-01 18:30`).
+pub fn parse_when(input: &str, now: DateTime<Utc>, timezone: Tz) -> anyhow::Result<DateTime<Utc>> {
+    let input = input.trim();
+
+    let delay = input.to_lowercase();
+    let delay = delay.strip_prefix("in ").unwrap_or(&delay).replace(' ', "");
+    if let Some(delay) = parse_delay(&delay)? {
+        return now
+            .checked_add_signed(delay)
+            .ok_or(anyhow!("\"{input}\" is too far ahead"));
+    }
+
+    for format in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(input, format) {
+            return local(naive, timezone);
+        }
+    }
+
+    if let Ok(time) = NaiveTime::parse_from_str(input, "%H:%M") {
+        let today = now.with_timezone(&timezone).date_naive();
+        let due = local(today.and_time(time), timezone)?;
+        if due > now {
+            return Ok(due);
+        }
+
+        // already past today
+        let tomorrow = today.succ_opt().ok_or(anyhow!("{today} has no tomorrow"))?;
+        return local(tomorrow.and_time(time), timezone);
+    }
+
+    bail!(
+        "\"{input}\" is neither a delay like 30m, 2h or 1d12h nor a time like 18:30 or 2025-06-01 18:30"
+    )
+}
+
+/// A delay such as `1h30m`, spaces removed, `None` if it is not one. Fails on delays
+/// longer than a reminder can be set ahead.
+fn parse_delay(input: &str) -> anyhow::Result<Option<Duration>> {
+    let max = Duration::days(MAX_AHEAD_DAYS);
+    let too_long = || anyhow!("a reminder can be set at most {MAX_AHEAD_DAYS} days ahead");
+
+    let mut total = Duration::zero();
+    let mut rest = input;
+
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let Ok(amount) = rest[..digits].parse::<i64>() else {
+            // too many digits to be anything but too long
+            if digits > 0 {
+                return Err(too_long());
+            }
+            return Ok(None);
+        };
+        rest = &rest[digits..];
+
+        let unit = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let delay = match &rest[..unit] {
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::try_minutes(amount),
+            "h" | "hour" | "hours" => Duration::try_hours(amount),
+            "d" | "day" | "days" => Duration::try_days(amount),
+            "w" | "week" | "weeks" => Duration::try_weeks(amount),
+            _ => return Ok(None),
+        };
+        total = delay
+            .and_then(|delay| total.checked_add(&delay))
+            .filter(|total| *total <= max)
+            .ok_or_else(too_long)?;
+        rest = &rest[unit..];
+    }
+
+    Ok((total > Duration::zero()).then_some(total))
+}
+
+fn local(naive: NaiveDateTime, timezone: Tz) -> anyhow::Result<DateTime<Utc>> {
+    timezone
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|due| due.with_timezone(&Utc))
+        .ok_or(anyhow!("{naive} does not exist in {timezone}"))
+}
@@ -0,0 +1,285 @@
+use std::{path::PathBuf, sync::Arc};
+
+use anyhow::{Context, bail};
+use branch_context::Messages;
+use serde::{Deserialize, Serialize};
+use serenity::all::ChannelId;
+
+use crate::{
+    chat::{
+        ChatMessage,
+        archive::storage::{Memory, MemoryNamespace, MemoryStorage},
+        client::ModelRegistry,
+        context::{
+            ConversationScope, MessageIdentifier, SavedContext, UserProfile, migrate_to_sqlite,
+            open_store,
+        },
+    },
+    config::structure::{ChatBotConfigInner, ConversationStoreKind},
+    utils::Environment,
+};
+
+use super::MemoriesArgs;
+
+/// Everything kept about a scope, as written by `export` and read by `import`.
+#[derive(Serialize, Deserialize)]
+pub struct ScopeExport {
+    pub scope: ConversationScope,
+    pub summary: Option<String>,
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub channel: Option<ChannelId>,
+    /// In order, message identifiers cannot be JSON keys
+    pub messages: Vec<(MessageIdentifier, Messages<ChatMessage>)>,
+    pub profile: Option<UserProfile>,
+    pub memories: Vec<PersonaMemories>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct PersonaMemories {
+    /// `None` for the default persona
+    pub persona: Option<String>,
+    pub memories: Vec<Memory>,
+}
+
+/// Opens the memory storage, only asking the embedding model for its vector size if it is
+/// not configured.
+async fn memory_storage(config: &ChatBotConfigInner) -> anyhow::Result<Arc<MemoryStorage>> {
+    let save_folder = config.context.save_to_disk_folder.as_deref();
+
+    Ok(match config.llm.embedding.vector_size {
+        Some(vector_size) => Arc::new(MemoryStorage::new(
+            &config.llm,
+            save_folder,
+            vector_size as u64,
+        )?),
+        None => {
+            ModelRegistry::new(Environment::default())
+                .memory(&config.llm, save_folder)
+                .await?
+                .storage
+        }
+    })
+}
+
+/// The memory namespace of every persona of the scope, the default one first.
+fn namespaces(
+    config: &ChatBotConfigInner,
+    scope: ConversationScope,
+) -> Vec<(Option<String>, MemoryNamespace)> {
+    std::iter::once(None)
+        .chain(config.personas.iter().flatten().map(|(name, _)| Some(name)))
+        .map(|persona| {
+            let namespace = MemoryNamespace::new(scope, persona.map(String::as_str));
+            (persona.cloned(), namespace)
+        })
+        .collect()
+}
+
+fn namespace(config: &ChatBotConfigInner, args: &MemoriesArgs) -> anyhow::Result<MemoryNamespace> {
+    if let Some(persona) = &args.persona {
+        config.with_persona(Some(persona.as_str()))?;
+    }
+
+    Ok(MemoryNamespace::new(args.scope, args.persona.as_deref()))
+}
+
+pub async fn export(
+    config: &ChatBotConfigInner,
+    scope: ConversationScope,
+    output: Option<PathBuf>,
+) -> anyhow::Result<()> {
+    let saved = match open_store(&config.context)? {
+        Some(store) => store.load(scope).await?,
+        None => None,
+    };
+    let profile = match (scope, &config.context.save_to_disk_folder) {
+        (ConversationScope::Direct(user), Some(folder)) => {
+            Some(UserProfile::load(folder, user).await).filter(|profile| !profile.is_empty())
+        }
+        _ => None,
+    };
+
+    let storage = memory_storage(config).await?;
+    let mut memories = Vec::new();
+    for (persona, namespace) in namespaces(config, scope) {
+        let listed = storage.list(&namespace).await?;
+        if !listed.is_empty() {
+            memories.push(PersonaMemories {
+                persona,
+                memories: listed,
+            });
+        }
+    }
+
+    let (summary, persona, channel, messages) = match saved {
+        Some(SavedContext {
+            messages,
+            summary,
+            persona,
+            channel,
+        }) => (summary, persona, channel, messages.into_iter().collect()),
+        None => (None, None, None, Vec::new()),
+    };
+
+    let export = ScopeExport {
+        scope,
+        summary,
+        persona,
+        channel,
+        messages,
+        profile,
+        memories,
+    };
+
+    let output = output.unwrap_or_else(|| PathBuf::from(format!("export-{}.json", scope.key())));
+    std::fs::write(&output, serde_json::to_vec_pretty(&export)?)
+        .with_context(|| format!("failed to write {}", output.display()))?;
+
+    log::info!(
+        "exported {} messages and {} memories of {scope} to {}",
+        export.messages.len(),
+        export
+            .memories
+            .iter()
+            .map(|group| group.memories.len())
+            .sum::<usize>(),
+        output.display()
+    );
+
+    Ok(())
+}
+
+pub async fn import(config: &ChatBotConfigInner, file: PathBuf) -> anyhow::Result<()> {
+    let bytes =
+        std::fs::read(&file).with_context(|| format!("failed to read {}", file.display()))?;
+    let export: ScopeExport = serde_json::from_slice(&bytes)
+        .with_context(|| format!("{} is not an export", file.display()))?;
+    let scope = export.scope;
+
+    if !export.messages.is_empty() || export.summary.is_some() {
+        let Some(store) = open_store(&config.context)? else {
+            bail!("conversations are not saved, context.save_to_disk_folder is not set");
+        };
+
+        let saved = SavedContext {
+            messages: export.messages.into_iter().collect(),
+            summary: export.summary,
+            persona: export.persona,
+            channel: export.channel,
+        };
+        store.save(scope, &saved.as_ref()).await?;
+
+        log::info!("imported {} messages of {scope}", saved.messages.len());
+    }
+
+    if let (Some(profile), ConversationScope::Direct(user), Some(folder)) =
+        (export.profile, scope, &config.context.save_to_disk_folder)
+    {
+        profile.save(folder, user).await?;
+        log::info!("imported the profile of {scope}");
+    }
+
+    if export.memories.is_empty() {
+        return Ok(());
+    }
+
+    // memories are embedded again, the export does not carry their vectors
+    let shared = ModelRegistry::new(Environment::default())
+        .memory(&config.llm, config.context.save_to_disk_folder.as_deref())
+        .await?;
+
+    for group in export.memories {
+        let namespace = MemoryNamespace::new(scope, group.persona.as_deref());
+        let count = group.memories.len();
+
+        for memory in group.memories {
+            let embedding = shared
+                .embedding_model
+                .embed_text(&memory.content)
+                .await?
+                .vec
+                .into_iter()
+                .map(|x| x as f32)
+                .collect::<Vec<f32>>();
+
+            shared.storage.store(memory, embedding, &namespace).await?;
+        }
+
+        log::info!("imported {count} memories into {}", namespace.as_str());
+    }
+
+    Ok(())
+}
+
+pub async fn list_memories(config: &ChatBotConfigInner, args: MemoriesArgs) -> anyhow::Result<()> {
+    let namespace = namespace(config, &args)?;
+    let memories = memory_storage(config).await?.list(&namespace).await?;
+
+    for memory in &memories {
+        println!(
+            "{}\t{}\t{}",
+            memory.id,
+            memory.date.format("%Y-%m-%d %H:%M"),
+            memory.content.replace('\n', " ")
+        );
+    }
+
+    log::info!("{} memories in {}", memories.len(), namespace.as_str());
+
+    Ok(())
+}
+
+pub async fn prune_memories(
+    config: &ChatBotConfigInner,
+    args: MemoriesArgs,
+    older_than: Option<u32>,
+    all: bool,
+) -> anyhow::Result<()> {
+    let namespace = namespace(config, &args)?;
+    let storage = memory_storage(config).await?;
+
+    if all {
+        storage.delete_all(&namespace).await?;
+        log::info!("deleted every memory in {}", namespace.as_str());
+
+        return Ok(());
+    }
+
+    let Some(days) = older_than else {
+        bail!("either --older-than or --all is needed");
+    };
+
+    let cutoff = chrono::Utc::now() - chrono::Duration::days(days.into());
+    let ids = storage
+        .list(&namespace)
+        .await?
+        .into_iter()
+        .filter(|memory| memory.date < cutoff)
+        .map(|memory| memory.id)
+        .collect::<Vec<_>>();
+
+    let count = ids.len();
+    if count > 0 {
+        storage.delete_many(ids, &namespace).await?;
+    }
+
+    log::info!(
+        "deleted {count} memories older than {days} days in {}",
+        namespace.as_str()
+    );
+
+    Ok(())
+}
+
+pub async fn migrate(config: &ChatBotConfigInner, keep: bool) -> anyhow::Result<()> {
+    if config.context.store.unwrap_or_default() != ConversationStoreKind::Sqlite {
+        // the bot would no longer find the moved conversations
+        bail!("set context.store to \"sqlite\" first");
+    }
+
+    let moved = migrate_to_sqlite(&config.context, keep).await?;
+    log::info!("moved {moved} conversations into the SQLite store");
+
+    Ok(())
+}
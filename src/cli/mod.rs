@@ -0,0 +1,95 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use log::LevelFilter;
+
+use crate::chat::context::ConversationScope;
+
+mod data;
+
+pub use data::*;
+
+/// A Discord chatbot with long term memory.
+#[derive(Debug, Parser)]
+#[command(version)]
+pub struct Cli {
+    /// The config file, or the folder holding a `config.toml`
+    #[arg(short, long, global = true, default_value = "config.toml")]
+    pub config: PathBuf,
+
+    /// How much to log: off, error, warn, info, debug or trace
+    #[arg(short, long, global = true)]
+    pub log_level: Option<LevelFilter>,
+
+    /// Where to keep conversations, profiles and local memories, instead of the
+    /// configured `context.save_to_disk_folder`
+    #[arg(short, long, global = true)]
+    pub data_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Starts the bot, the default
+    Run,
+    /// Validates the config and prints it with its secrets masked
+    CheckConfig,
+    /// Writes the conversation, profile and memories of a scope to a JSON file
+    Export {
+        #[arg(value_parser = parse_scope)]
+        scope: ConversationScope,
+        /// Defaults to `export-<scope>.json`
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Restores a scope from a file written by `export`, replacing its conversation and
+    /// profile and adding its memories
+    Import { file: PathBuf },
+    /// Manages the long term memories of a scope
+    Memories {
+        #[command(subcommand)]
+        command: MemoriesCommand,
+    },
+    /// Moves every conversation saved to files into the SQLite store
+    Migrate {
+        /// Keeps the files of the moved conversations
+        #[arg(long)]
+        keep: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MemoriesCommand {
+    /// Lists the memories of a scope, newest first
+    List(MemoriesArgs),
+    /// Deletes the memories of a scope
+    Prune {
+        #[command(flatten)]
+        target: MemoriesArgs,
+        /// Only deletes memories older than this many days
+        #[arg(long, conflicts_with = "all", required_unless_present = "all")]
+        older_than: Option<u32>,
+        /// Deletes every memory
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Debug, Args)]
+pub struct MemoriesArgs {
+    /// A user id, `channel_<id>` or `thread_<id>`
+    #[arg(value_parser = parse_scope)]
+    pub scope: ConversationScope,
+    /// The persona whose memories to manage, the default one otherwise
+    #[arg(short, long)]
+    pub persona: Option<String>,
+}
+
+/// Parses a scope given as its key, the bare user id for direct messages.
+fn parse_scope(key: &str) -> Result<ConversationScope, String> {
+    ConversationScope::from_key(key).ok_or(format!(
+        "\"{key}\" is not a user id, channel_<id> or thread_<id>"
+    ))
+}
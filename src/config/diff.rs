@@ -0,0 +1,73 @@
+use std::fmt::Display;
+
+use super::structure::ChatBotConfigInner;
+
+/// The sections of the config that changed between two versions of it, deciding what
+/// has to be rebuilt.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ConfigChanges {
+    pub discord: bool,
+    /// `llm.completion` and every other `llm` setting besides the embedding ones
+    pub completion: bool,
+    /// `llm.embedding` and the similarity threshold, the memory storage depends on them
+    pub embedding: bool,
+    pub freewill: bool,
+    /// `context.system` and the personas
+    pub system: bool,
+    /// Every other `context` setting
+    pub context: bool,
+    /// `context.save_to_disk_folder` and `context.store`, the conversation store has to be
+    /// opened again
+    pub store: bool,
+}
+
+impl ConfigChanges {
+    pub fn between(old: &ChatBotConfigInner, new: &ChatBotConfigInner) -> Self {
+        let embedding = old.llm.embedding != new.llm.embedding
+            || old.llm.similarity_threshold != new.llm.similarity_threshold;
+
+        let mut old_llm = old.llm.clone();
+        old_llm.embedding = new.llm.embedding.clone();
+        old_llm.similarity_threshold = new.llm.similarity_threshold;
+
+        let mut old_context = old.context.clone();
+        old_context.system = new.context.system.clone();
+
+        Self {
+            discord: old.discord != new.discord,
+            completion: old_llm != new.llm,
+            embedding,
+            freewill: old.freewill != new.freewill,
+            system: old.context.system != new.context.system || old.personas != new.personas,
+            context: old_context != new.context,
+            store: old.context.save_to_disk_folder != new.context.save_to_disk_folder
+                || old.context.store != new.context.store,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl Display for ConfigChanges {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sections = [
+            (self.discord, "discord"),
+            (self.completion, "llm.completion"),
+            (self.embedding, "llm.embedding"),
+            (self.freewill, "freewill"),
+            (self.system, "context.system"),
+            (self.context, "context"),
+            (self.store, "context.store"),
+        ]
+        .into_iter()
+        .filter_map(|(changed, section)| changed.then_some(section))
+        .collect::<Vec<_>>();
+
+        match sections.is_empty() {
+            true => write!(f, "nothing"),
+            false => write!(f, "{}", sections.join(", ")),
+        }
+    }
+}
@@ -1,2 +1,5 @@
+pub mod diff;
+pub mod secrets;
 pub mod store;
 pub mod structure;
+pub mod validate;
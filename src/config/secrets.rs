@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, bail};
+
+use super::structure::ChatBotConfigInner;
+
+/// A secret given as a reference to where it is kept instead of in plain text, either
+/// `${ENV_VAR}` or `file:/run/secrets/...`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SecretRef {
+    pub reference: String,
+    resolved: String,
+}
+
+/// The references secrets were resolved from, keyed on their path in the config.
+pub type SecretRefs = HashMap<String, SecretRef>;
+
+/// Resolves a secret reference, returning [None] if the value is the secret itself.
+pub fn resolve(value: &str) -> anyhow::Result<Option<String>> {
+    let value = value.trim();
+
+    if let Some(name) = value
+        .strip_prefix("${")
+        .and_then(|rest| rest.strip_suffix('}'))
+    {
+        if name.is_empty() {
+            bail!("\"{value}\" does not name an environment variable");
+        }
+
+        return std::env::var(name)
+            .map(Some)
+            .with_context(|| format!("environment variable {name} is not set"));
+    }
+
+    if let Some(path) = value.strip_prefix("file:") {
+        let secret = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read the secret file {path}"))?;
+
+        // secret files usually end with a newline
+        return Ok(Some(secret.trim_end_matches(['\r', '\n']).to_string()));
+    }
+
+    Ok(None)
+}
+
+/// Every secret of the config along with its path.
+pub fn secrets_mut(config: &mut ChatBotConfigInner) -> Vec<(String, &mut String)> {
+    let mut secrets = vec![
+        ("discord.token".to_string(), &mut config.discord.token),
+        (
+            "llm.completion.api_key".to_string(),
+            &mut config.llm.completion.api_key,
+        ),
+    ];
+
+    if let Some(api_key) = config.llm.embedding.api_key.as_mut() {
+        secrets.push(("llm.embedding.api_key".to_string(), api_key));
+    }
+
+    for (i, fallback) in config
+        .llm
+        .completion
+        .fallbacks
+        .iter_mut()
+        .flatten()
+        .enumerate()
+    {
+        if let Some(api_key) = fallback.api_key.as_mut() {
+            secrets.push((format!("llm.completion.fallbacks[{i}].api_key"), api_key));
+        }
+    }
+
+    secrets
+}
+
+/// Replaces every secret reference of the config by the secret, returning the
+/// references so that they can be written back instead of the secrets.
+pub fn resolve_all(config: &mut ChatBotConfigInner) -> anyhow::Result<SecretRefs> {
+    let mut refs = SecretRefs::new();
+
+    for (path, value) in secrets_mut(config) {
+        if let Some(resolved) = resolve(value).with_context(|| format!("config.{path}"))? {
+            refs.insert(
+                path,
+                SecretRef {
+                    reference: std::mem::replace(value, resolved.clone()),
+                    resolved,
+                },
+            );
+        }
+    }
+
+    Ok(refs)
+}
+
+/// Puts the references back in place of the secrets they were resolved to, secrets that
+/// were changed since are kept as they are.
+pub fn restore(config: &mut ChatBotConfigInner, refs: &SecretRefs) {
+    for (path, value) in secrets_mut(config) {
+        if let Some(secret) = refs.get(&path).filter(|secret| *value == secret.resolved) {
+            *value = secret.reference.clone();
+        }
+    }
+}
+
+/// Whether the value points to where a secret is kept instead of being the secret.
+pub fn is_reference(value: &str) -> bool {
+    let value = value.trim();
+
+    (value.starts_with("${") && value.ends_with('}')) || value.starts_with("file:")
+}
+
+/// Sets a secret at runtime, which has to be given in plain text: references would let
+/// whoever sets it read any environment variable or file the bot can.
+pub fn set(refs: &mut SecretRefs, path: &str, value: &str) -> anyhow::Result<String> {
+    if is_reference(value) {
+        bail!("{path} can only be set to the secret itself, references go in the config file");
+    }
+
+    refs.remove(path);
+
+    Ok(value.to_string())
+}
@@ -1,7 +1,11 @@
 use anyhow::bail;
 use serenity::prelude::TypeMapKey;
 
-use super::structure::{ChatBotConfigInner, ChatBotConfigTOML};
+use super::{
+    diff::ConfigChanges,
+    secrets::{self, SecretRefs},
+    structure::{ChatBotConfigInner, ChatBotConfigTOML},
+};
 use std::{
     ops::{Deref, DerefMut},
     path::PathBuf,
@@ -11,6 +15,11 @@ use std::{
 pub struct ChatBotConfig {
     pub path: PathBuf,
     cached: ChatBotConfigTOML,
+    /// Where the secrets read from the file come from, they are saved as references
+    secrets: SecretRefs,
+    /// The data folder given on the command line, along with the one in the file which is
+    /// saved instead
+    data_dir: Option<(PathBuf, Option<PathBuf>)>,
 }
 
 impl ChatBotConfig {
@@ -21,7 +30,10 @@ impl ChatBotConfig {
         };
 
         if !path.exists() {
-            return Ok(Self::new(path)?);
+            bail!(
+                "{} does not exist, copy config.example.toml there and fill it in",
+                path.display()
+            );
         }
 
         if !path.is_file() {
@@ -32,49 +44,100 @@ impl ChatBotConfig {
 
         let config_str = std::fs::read_to_string(&path)?;
 
+        Self::parse(path, &config_str)
+    }
+
+    /// Reads a config that is not in a file, which is only ever saved to `config.toml`.
+    #[cfg(test)]
+    pub fn from_toml(config_str: &str) -> Result<Self, anyhow::Error> {
+        Self::parse(PathBuf::from("config.toml"), config_str)
+    }
+
+    fn parse(path: PathBuf, config_str: &str) -> Result<Self, anyhow::Error> {
+        let mut cached: ChatBotConfigTOML = toml::from_str(config_str)
+            .map_err(|why| anyhow::anyhow!("{} is not a valid config:\n{why}", path.display()))?;
+
+        let secrets = secrets::resolve_all(&mut cached.config)?;
+        cached.config.validate()?;
+
         Ok(Self {
             path,
-            cached: toml::from_str(&config_str)?,
+            cached,
+            secrets,
+            data_dir: None,
         })
     }
 
-    pub fn update(&mut self) -> bool {
-        let new = Self::read(self.path.clone()).unwrap();
-
-        match self.cached.config == new.cached.config {
-            true => false,
-            false => {
-                self.cached = new.cached;
-                true
-            }
+    /// Reads the file again, returning what changed. The current config is kept if the
+    /// file can no longer be read.
+    pub fn update(&mut self) -> Result<ConfigChanges, anyhow::Error> {
+        let mut new = Self::read(self.path.clone())?;
+        if let Some((data_dir, _)) = &self.data_dir {
+            new = new.with_data_dir(data_dir.clone());
         }
-    }
 
-    fn new(path: PathBuf) -> Result<Self, anyhow::Error> {
-        std::fs::create_dir_all(path.parent().unwrap())?;
+        let changes = ConfigChanges::between(&self.cached.config, &new.cached.config);
 
-        let config = Self {
-            path,
-            cached: ChatBotConfigTOML::default(),
-        };
+        self.data_dir = new.data_dir;
+        // a reference can change without the secret it resolves to changing
+        self.secrets = new.secrets;
+        if !changes.is_empty() {
+            self.cached = new.cached;
+        }
 
-        config.save()?;
+        Ok(changes)
+    }
 
-        Ok(config)
+    /// Keeps conversations, profiles and local memories in `data_dir` whatever the file
+    /// says, without ever saving it there.
+    pub fn with_data_dir(mut self, data_dir: PathBuf) -> Self {
+        let from_file = self
+            .cached
+            .config
+            .context
+            .save_to_disk_folder
+            .replace(data_dir.clone());
+        self.data_dir = Some((data_dir, from_file));
+
+        self
     }
 
     pub fn into_inner(self) -> ChatBotConfigInner {
         self.cached.config
     }
 
-    pub fn save(&self) -> Result<(), anyhow::Error> {
-        std::fs::write(&self.path, toml::to_string(&self.cached)?)?;
+    /// The config as TOML, with every secret masked.
+    pub fn to_masked_string(&self) -> Result<String, anyhow::Error> {
+        Ok(toml::to_string_pretty(&ChatBotConfigTOML {
+            config: self.cached.config.masked(),
+        })?)
+    }
 
-        Ok(())
+    /// Sets a secret, which has to be given in plain text, returning it.
+    pub fn set_secret(&mut self, path: &str, value: &str) -> Result<String, anyhow::Error> {
+        secrets::set(&mut self.secrets, path, value)
     }
 
+    /// The reference a secret was resolved from, if it was not given in plain text.
+    pub fn secret_ref(&self, path: &str) -> Option<&str> {
+        self.secrets
+            .get(path)
+            .map(|secret| secret.reference.as_str())
+    }
+
+    /// Writes the config back to its file, secrets that came from references are written
+    /// as those references.
     pub async fn async_save(&self) -> Result<(), anyhow::Error> {
-        tokio::fs::write(&self.path, toml::to_string(&self.cached)?).await?;
+        let mut saved = self.cached.clone();
+        secrets::restore(&mut saved.config, &self.secrets);
+        if let Some((data_dir, from_file)) = &self.data_dir {
+            let folder = &mut saved.config.context.save_to_disk_folder;
+            if folder.as_ref() == Some(data_dir) {
+                *folder = from_file.clone();
+            }
+        }
+
+        tokio::fs::write(&self.path, toml::to_string(&saved)?).await?;
 
         Ok(())
     }
@@ -16,19 +16,145 @@ pub struct ChatBotConfigInner {
     pub llm: LLMConfig,
     pub freewill: FreewillConfig,
     pub context: ContextConfig,
+    pub personas: Option<HashMap<String, PersonaConfig>>,
+}
+
+impl ChatBotConfigInner {
+    /// The config as seen by a persona, `None` being the default one (`context.system`).
+    pub fn with_persona(&self, persona: Option<&str>) -> anyhow::Result<Self> {
+        let mut config = self.clone();
+
+        let Some(name) = persona else {
+            return Ok(config);
+        };
+
+        let persona = self
+            .personas
+            .as_ref()
+            .and_then(|personas| personas.get(name))
+            .ok_or(anyhow::anyhow!("there is no persona named \"{name}\""))?;
+
+        config.context.system = persona.system.clone();
+
+        if let Some(model) = &persona.model {
+            config.llm.completion.model = model.clone();
+        }
+        if let Some(temperature) = persona.temperature {
+            config.llm.completion.temperature = Some(temperature);
+        }
+        if let Some(max_tokens) = persona.max_tokens {
+            config.llm.completion.max_tokens = Some(max_tokens);
+        }
+
+        Ok(config)
+    }
+
+    /// Names of the configured personas, sorted.
+    pub fn persona_names(&self) -> Vec<String> {
+        let mut names = self
+            .personas
+            .iter()
+            .flat_map(|personas| personas.keys().cloned())
+            .collect::<Vec<_>>();
+        names.sort();
+
+        names
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct PersonaConfig {
+    pub system: SystemPromptBuilder,
+
+    // LLM overrides
+    pub model: Option<String>,
+    pub temperature: Option<f64>,
+    pub max_tokens: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct ContextConfig {
     pub max_stm: usize,
+    pub token_budget: Option<TokenBudgetConfig>,
+    pub summary: Option<SummaryConfig>,
     pub save_to_disk_folder: Option<PathBuf>,
+    pub store: Option<ConversationStoreKind>,
+    pub autosave: Option<AutosaveConfig>,
+    pub residency: Option<ResidencyConfig>,
     pub stm_drain_percentage: Option<f64>,
     pub system: SystemPromptBuilder,
 }
 
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConversationStoreKind {
+    #[default]
+    File,
+    Sqlite,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AutosaveConfig {
+    pub after_turn: Option<bool>,
+    pub interval_secs: Option<u64>,
+    pub backups: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ResidencyConfig {
+    pub idle_secs: Option<u64>,
+    pub max_resident: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct SummaryConfig {
+    pub enabled: Option<bool>,
+    pub max_tokens: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TokenBudgetConfig {
+    pub max_tokens: usize,
+    pub system_share: Option<f64>,
+    pub memories_share: Option<f64>,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 pub struct DiscordConfig {
     pub token: String,
+
+    // Guilds
+    pub triggers: Option<TriggerConfig>,
+    pub channels: Option<HashMap<String, TriggerConfig>>,
+
+    pub attachments: Option<AttachmentConfig>,
+}
+
+impl DiscordConfig {
+    /// Trigger rules of a guild channel, falling back to the global ones.
+    pub fn triggers_for(&self, channel_id: u64) -> TriggerConfig {
+        self.channels
+            .as_ref()
+            .and_then(|channels| channels.get(&channel_id.to_string()))
+            .or(self.triggers.as_ref())
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct AttachmentConfig {
+    pub enabled: Option<bool>,
+    pub max_size_kb: Option<u64>,
+    pub max_text_chars: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TriggerConfig {
+    pub enabled: Option<bool>,
+    pub mention: Option<bool>,
+    pub reply: Option<bool>,
+    pub keywords: Option<Vec<String>>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
@@ -36,6 +162,29 @@ pub struct FreewillConfig {
     pub min_time_secs: u64,
     pub max_time_secs: u64,
     pub steepness: f64,
+
+    // Scheduling
+    pub quiet_hours: Option<QuietHours>,
+    pub learn_active_hours: Option<bool>,
+    pub max_unanswered: Option<usize>,
+}
+
+/// Hours of the day, in the user's timezone, the bot never reaches out in.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub struct QuietHours {
+    pub start: u32,
+    pub end: u32,
+}
+
+impl QuietHours {
+    /// Whether the hour is quiet, the range wrapping around midnight if it ends before it
+    /// starts.
+    pub fn contains(&self, hour: u32) -> bool {
+        match self.start <= self.end {
+            true => (self.start..self.end).contains(&hour),
+            false => hour >= self.start || hour < self.end,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
@@ -49,6 +198,8 @@ pub struct LLMConfig {
     pub additional_params: Option<HashMap<String, toml::Value>>,
 
     pub use_tools: Option<bool>,
+    pub max_tool_steps: Option<usize>,
+    pub retry: Option<RetryConfig>,
     pub force_lowercase: Option<bool>,
     pub similarity_threshold: Option<f64>,
 }
@@ -65,9 +216,37 @@ pub struct LLMCompletionConfig {
     pub reason: Option<bool>,
     pub fake_reason: Option<bool>,
 
+    // Streaming
+    pub stream: Option<bool>,
+
+    // Whether the model can be shown images
+    pub vision: Option<bool>,
+
     // Additional Parameters
     pub max_tokens: Option<u64>,
     pub temperature: Option<f64>,
+
+    // Tried in order whenever the model above fails
+    pub fallbacks: Option<Vec<FallbackConfig>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct FallbackConfig {
+    pub model: String,
+    pub provider: Provider,
+    pub api_key: Option<String>,
+    pub custom_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct RetryConfig {
+    pub max_attempts: Option<usize>,
+    pub base_delay_ms: Option<u64>,
+    pub max_delay_ms: Option<u64>,
+
+    // Circuit breaker
+    pub failure_threshold: Option<u32>,
+    pub cooldown_secs: Option<u64>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
@@ -81,7 +260,17 @@ pub struct LLMEmbeddingConfig {
     pub vector_size: Option<usize>,
 
     // Vector DB
+    pub backend: Option<MemoryBackendKind>,
+    #[serde(default)]
     pub qdrant_host: String,
     pub qdrant_port: Option<u16>,
     pub qdrant_https: Option<bool>,
 }
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MemoryBackendKind {
+    #[default]
+    Qdrant,
+    Local,
+}
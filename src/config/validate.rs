@@ -0,0 +1,280 @@
+use std::fmt::Display;
+
+use crate::chat::prompt::SystemPromptBuilder;
+
+use super::{
+    secrets::secrets_mut,
+    structure::{ChatBotConfigInner, MemoryBackendKind},
+};
+
+/// Replaces secrets when the config is shown.
+const MASK: &str = "********";
+
+/// Hides a secret, keeping whether it is set at all.
+pub fn mask(secret: &str) -> String {
+    match secret.is_empty() {
+        true => String::new(),
+        false => MASK.to_string(),
+    }
+}
+
+/// A setting that makes no sense, named by its path in the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigIssue {
+    pub path: String,
+    pub message: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "config.{}: {}", self.path, self.message)
+    }
+}
+
+/// Every issue found in a config, the config is only used if there are none.
+#[derive(Debug)]
+pub struct ValidationReport(pub Vec<ConfigIssue>);
+
+impl std::error::Error for ValidationReport {}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "found {} issue(s) in the config:", self.0.len())?;
+        for issue in &self.0 {
+            writeln!(f, "  - {issue}")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Issues(Vec<ConfigIssue>);
+
+impl Issues {
+    fn check(&mut self, ok: bool, path: impl Into<String>, message: impl Into<String>) {
+        if !ok {
+            self.0.push(ConfigIssue {
+                path: path.into(),
+                message: message.into(),
+            });
+        }
+    }
+
+    fn not_empty(&mut self, value: &str, path: impl Into<String>) {
+        self.check(!value.trim().is_empty(), path, "must not be empty");
+    }
+
+    fn fraction(&mut self, value: Option<f64>, path: impl Into<String>) {
+        if let Some(value) = value {
+            self.check(
+                (0.0..=1.0).contains(&value),
+                path,
+                format!("must be between 0.0 and 1.0, got {value}"),
+            );
+        }
+    }
+
+    fn url(&mut self, value: Option<&str>, path: impl Into<String>) {
+        let Some(value) = value else {
+            return;
+        };
+
+        let host = value
+            .strip_prefix("https://")
+            .or_else(|| value.strip_prefix("http://"))
+            .map(|rest| rest.split(['/', '?', '#']).next().unwrap_or_default());
+
+        self.check(
+            host.is_some_and(|host| !host.is_empty() && !host.contains(char::is_whitespace)),
+            path,
+            format!("\"{value}\" is not an http(s) url"),
+        );
+    }
+
+    fn system(&mut self, system: &SystemPromptBuilder, path: &str) {
+        self.not_empty(&system.chatbot_name, format!("{path}.chatbot_name"));
+        self.not_empty(&system.user_name, format!("{path}.user_name"));
+    }
+}
+
+impl ChatBotConfigInner {
+    /// Checks the settings that parse fine but cannot work, reporting all of them at once.
+    pub fn validate(&self) -> Result<(), ValidationReport> {
+        let mut issues = Issues::default();
+
+        // discord
+        issues.not_empty(&self.discord.token, "discord.token");
+        for channel in self
+            .discord
+            .channels
+            .iter()
+            .flat_map(|channels| channels.keys())
+        {
+            issues.check(
+                channel.parse::<u64>().is_ok(),
+                format!("discord.channels.\"{channel}\""),
+                "must be keyed on a channel id",
+            );
+        }
+        if let Some(attachments) = &self.discord.attachments {
+            issues.check(
+                attachments.max_size_kb != Some(0),
+                "discord.attachments.max_size_kb",
+                "must be over 0",
+            );
+        }
+
+        // llm
+        let completion = &self.llm.completion;
+        issues.not_empty(&completion.model, "llm.completion.model");
+        issues.url(
+            completion.custom_url.as_deref(),
+            "llm.completion.custom_url",
+        );
+        if let Some(temperature) = completion.temperature {
+            issues.check(
+                (0.0..=2.0).contains(&temperature),
+                "llm.completion.temperature",
+                format!("must be between 0.0 and 2.0, got {temperature}"),
+            );
+        }
+        for (i, fallback) in completion.fallbacks.iter().flatten().enumerate() {
+            issues.not_empty(
+                &fallback.model,
+                format!("llm.completion.fallbacks[{i}].model"),
+            );
+            issues.url(
+                fallback.custom_url.as_deref(),
+                format!("llm.completion.fallbacks[{i}].custom_url"),
+            );
+        }
+        if let Some(retry) = &self.llm.retry {
+            issues.check(
+                retry.max_attempts != Some(0),
+                "llm.retry.max_attempts",
+                "must be over 0",
+            );
+            if let (Some(base), Some(max)) = (retry.base_delay_ms, retry.max_delay_ms) {
+                issues.check(
+                    base <= max,
+                    "llm.retry.base_delay_ms",
+                    format!("must not be over max_delay_ms ({base} > {max})"),
+                );
+            }
+        }
+        issues.check(
+            self.llm.max_tool_steps != Some(0),
+            "llm.max_tool_steps",
+            "must be over 0",
+        );
+        issues.fraction(self.llm.similarity_threshold, "llm.similarity_threshold");
+
+        let embedding = &self.llm.embedding;
+        issues.not_empty(&embedding.model, "llm.embedding.model");
+        issues.url(embedding.custom_url.as_deref(), "llm.embedding.custom_url");
+        issues.check(
+            embedding.vector_size != Some(0),
+            "llm.embedding.vector_size",
+            "must be over 0",
+        );
+        if embedding.backend.unwrap_or_default() == MemoryBackendKind::Qdrant {
+            issues.not_empty(&embedding.qdrant_host, "llm.embedding.qdrant_host");
+        }
+
+        // freewill
+        issues.check(
+            self.freewill.min_time_secs < self.freewill.max_time_secs,
+            "freewill.min_time_secs",
+            format!(
+                "must be under max_time_secs ({} >= {})",
+                self.freewill.min_time_secs, self.freewill.max_time_secs
+            ),
+        );
+        issues.check(
+            self.freewill.steepness != 0.0,
+            "freewill.steepness",
+            "must not be 0",
+        );
+        if let Some(quiet_hours) = self.freewill.quiet_hours {
+            issues.check(
+                quiet_hours.start < 24,
+                "freewill.quiet_hours.start",
+                format!(
+                    "must be an hour between 0 and 23, got {}",
+                    quiet_hours.start
+                ),
+            );
+            issues.check(
+                quiet_hours.end < 24,
+                "freewill.quiet_hours.end",
+                format!("must be an hour between 0 and 23, got {}", quiet_hours.end),
+            );
+        }
+        issues.check(
+            self.freewill.max_unanswered != Some(0),
+            "freewill.max_unanswered",
+            "must be over 0",
+        );
+
+        // context
+        let context = &self.context;
+        if context.token_budget.is_none() {
+            issues.check(context.max_stm > 0, "context.max_stm", "must be over 0");
+        }
+        if let Some(drain) = context.stm_drain_percentage {
+            issues.check(
+                drain > 0.0 && drain <= 1.0,
+                "context.stm_drain_percentage",
+                format!("must be over 0.0 and at most 1.0, got {drain}"),
+            );
+        }
+        if let Some(budget) = &context.token_budget {
+            issues.check(
+                budget.max_tokens > 0,
+                "context.token_budget.max_tokens",
+                "must be over 0",
+            );
+            issues.fraction(budget.system_share, "context.token_budget.system_share");
+            issues.fraction(budget.memories_share, "context.token_budget.memories_share");
+            issues.check(
+                budget.system_share.unwrap_or(0.2) + budget.memories_share.unwrap_or(0.1) < 1.0,
+                "context.token_budget",
+                "system_share and memories_share leave no room for the history",
+            );
+        }
+        issues.system(&context.system, "context.system");
+
+        // personas
+        for (name, persona) in self.personas.iter().flatten() {
+            issues.check(
+                !name.trim().is_empty(),
+                "personas",
+                "persona names must not be empty",
+            );
+            issues.system(&persona.system, &format!("personas.{name}.system"));
+            if let Some(temperature) = persona.temperature {
+                issues.check(
+                    (0.0..=2.0).contains(&temperature),
+                    format!("personas.{name}.temperature"),
+                    format!("must be between 0.0 and 2.0, got {temperature}"),
+                );
+            }
+        }
+
+        match issues.0.is_empty() {
+            true => Ok(()),
+            false => Err(ValidationReport(issues.0)),
+        }
+    }
+
+    /// The config with every secret replaced, to be shown.
+    pub fn masked(&self) -> Self {
+        let mut config = self.clone();
+        for (_, secret) in secrets_mut(&mut config) {
+            *secret = mask(secret);
+        }
+
+        config
+    }
+}
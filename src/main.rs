@@ -1,22 +1,65 @@
-use std::path::PathBuf;
+use std::process::exit;
 
+use clap::Parser;
+use cli::{Cli, Command, MemoriesCommand};
 use config::store::ChatBotConfig;
 
 extern crate proc_macro;
 
 mod bot;
 mod chat;
+mod cli;
 mod config;
+#[cfg(test)]
+mod testing;
 mod utils;
 
 #[tokio::main]
 async fn main() {
-    utils::log::Logger::init(None);
-    log::info!("Starting ChatBot...");
+    let cli = Cli::parse();
 
-    let config = ChatBotConfig::read(PathBuf::from("config.toml")).unwrap();
+    utils::log::Logger::init(cli.log_level);
 
-    let bot = bot::ChatBot::new(config).await.unwrap();
+    let config = match ChatBotConfig::read(cli.config) {
+        Ok(config) => config,
+        Err(why) => {
+            log::error!("refusing to start, {why:#}");
+            exit(1);
+        }
+    };
+    let config = match cli.data_dir {
+        Some(data_dir) => config.with_data_dir(data_dir),
+        None => config,
+    };
 
-    bot.run().await;
+    let result = match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            log::info!("Starting ChatBot...");
+
+            let bot = bot::ChatBot::new(config).await.unwrap();
+
+            bot.run().await;
+            return;
+        }
+        Command::CheckConfig => config.to_masked_string().map(|effective| {
+            println!("{effective}");
+            log::info!("config is valid");
+        }),
+        Command::Export { scope, output } => cli::export(&config, scope, output).await,
+        Command::Import { file } => cli::import(&config, file).await,
+        Command::Memories { command } => match command {
+            MemoriesCommand::List(args) => cli::list_memories(&config, args).await,
+            MemoriesCommand::Prune {
+                target,
+                older_than,
+                all,
+            } => cli::prune_memories(&config, target, older_than, all).await,
+        },
+        Command::Migrate { keep } => cli::migrate(&config, keep).await,
+    };
+
+    if let Err(why) = result {
+        log::error!("{why:#}");
+        exit(1);
+    }
 }
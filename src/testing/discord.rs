@@ -0,0 +1,134 @@
+use std::sync::{
+    Mutex,
+    atomic::{AtomicU64, Ordering},
+};
+
+use async_trait::async_trait;
+use serenity::all::{ChannelId, CreateMessage, EditMessage, MessageId};
+
+use crate::bot::Outbound;
+
+/// A message the bot sent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sent {
+    pub channel: ChannelId,
+    pub id: MessageId,
+    pub content: String,
+    /// Whether it still has any buttons
+    pub buttons: bool,
+}
+
+/// Records whatever the bot does on Discord instead of doing it.
+pub struct MockDiscord {
+    /// Every message ever sent, as it was sent
+    sent: Mutex<Vec<Sent>>,
+    /// The messages that were not deleted, as they were last edited
+    visible: Mutex<Vec<Sent>>,
+    edited: Mutex<Vec<(ChannelId, MessageId)>>,
+    deleted: Mutex<Vec<(ChannelId, MessageId)>>,
+    next_id: AtomicU64,
+}
+
+impl Default for MockDiscord {
+    fn default() -> Self {
+        Self {
+            sent: Mutex::new(vec![]),
+            visible: Mutex::new(vec![]),
+            edited: Mutex::new(vec![]),
+            deleted: Mutex::new(vec![]),
+            // far away from the ids tests give their own messages
+            next_id: AtomicU64::new(1_000_000),
+        }
+    }
+}
+
+impl MockDiscord {
+    pub fn sent(&self) -> Vec<Sent> {
+        self.sent.lock().unwrap().clone()
+    }
+
+    /// What the channels show right now, oldest first.
+    pub fn visible(&self) -> Vec<Sent> {
+        self.visible.lock().unwrap().clone()
+    }
+
+    pub fn edited(&self) -> Vec<(ChannelId, MessageId)> {
+        self.edited.lock().unwrap().clone()
+    }
+
+    pub fn deleted(&self) -> Vec<(ChannelId, MessageId)> {
+        self.deleted.lock().unwrap().clone()
+    }
+}
+
+#[async_trait]
+impl Outbound for MockDiscord {
+    async fn send_message(
+        &self,
+        channel: ChannelId,
+        message: CreateMessage,
+    ) -> anyhow::Result<MessageId> {
+        let id = MessageId::new(self.next_id.fetch_add(1, Ordering::SeqCst));
+        let message = serde_json::to_value(&message)?;
+
+        let sent = Sent {
+            channel,
+            id,
+            content: message["content"].as_str().unwrap_or_default().to_string(),
+            buttons: has_buttons(&message),
+        };
+        self.sent.lock().unwrap().push(sent.clone());
+        self.visible.lock().unwrap().push(sent);
+
+        Ok(id)
+    }
+
+    async fn edit_message(
+        &self,
+        channel: ChannelId,
+        message: MessageId,
+        edit: EditMessage,
+    ) -> anyhow::Result<()> {
+        let edit = serde_json::to_value(&edit)?;
+
+        let mut visible = self.visible.lock().unwrap();
+        let shown = visible
+            .iter_mut()
+            .find(|sent| sent.channel == channel && sent.id == message)
+            .ok_or(anyhow::anyhow!("there is no message {message} to edit"))?;
+
+        if let Some(content) = edit["content"].as_str() {
+            shown.content = content.to_string();
+        }
+        if !edit["components"].is_null() {
+            shown.buttons = has_buttons(&edit);
+        }
+
+        self.edited.lock().unwrap().push((channel, message));
+
+        Ok(())
+    }
+
+    async fn delete_message(&self, channel: ChannelId, message: MessageId) -> anyhow::Result<()> {
+        let mut visible = self.visible.lock().unwrap();
+        let index = visible
+            .iter()
+            .position(|sent| sent.channel == channel && sent.id == message)
+            .ok_or(anyhow::anyhow!("there is no message {message} to delete"))?;
+        visible.remove(index);
+
+        self.deleted.lock().unwrap().push((channel, message));
+
+        Ok(())
+    }
+
+    async fn broadcast_typing(&self, _channel: ChannelId) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+fn has_buttons(message: &serde_json::Value) -> bool {
+    message["components"]
+        .as_array()
+        .is_some_and(|rows| !rows.is_empty())
+}
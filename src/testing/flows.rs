@@ -0,0 +1,116 @@
+use super::{CHANNEL, CONFIG, Harness, USER, wait_until};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn messages_are_answered() {
+    let harness = Harness::new(["hey there!", "still here"]).await;
+
+    let first = harness.send("hello").await.unwrap();
+
+    let shown = harness.shown();
+    assert_eq!(shown.id, first);
+    assert_eq!(shown.channel, CHANNEL);
+    assert_eq!(shown.content, "hey there!");
+    assert!(shown.buttons);
+
+    // the buttons of a response go away once anything else happens
+    harness.send("are you there?").await.unwrap();
+    wait_until(|| async { !harness.discord.visible()[0].buttons }).await;
+
+    let visible = harness.discord.visible();
+    assert_eq!(visible.len(), 2);
+    assert_eq!(visible[1].content, "still here");
+    assert!(visible[1].buttons);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn streamed_responses_are_answered() {
+    let config = CONFIG.replace("stream = false", "stream = true");
+    let harness = Harness::with_config(&config, ["one word at a time"]).await;
+
+    harness.send("hello").await.unwrap();
+
+    assert_eq!(harness.shown().content, "one word at a time");
+    assert_eq!(harness.discord.visible().len(), 1);
+    assert_eq!(harness.model.remaining(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn responses_are_regenerated_and_browsed() {
+    let harness = Harness::new(["first take", "second take"]).await;
+
+    let first = harness.send("tell me something").await.unwrap();
+    harness.regen(first).await.unwrap();
+
+    // the regenerated response replaces the old one
+    let second = harness.shown();
+    assert_eq!(second.content, "second take");
+    assert_eq!(harness.discord.visible().len(), 1);
+    assert_eq!(harness.discord.deleted(), vec![(CHANNEL, first)]);
+
+    harness.prev(second.id).await.unwrap();
+    let back = harness.shown();
+    assert_eq!(back.content, "first take");
+
+    // there is nothing before the first version
+    assert!(harness.prev(back.id).await.is_err());
+
+    harness.next(back.id).await.unwrap();
+    assert_eq!(harness.shown().content, "second take");
+    assert_eq!(harness.discord.visible().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn responses_are_edited_as_another_version() {
+    let harness = Harness::new(["i like cats"]).await;
+
+    let response = harness.send("what do you like?").await.unwrap();
+    harness.edit(response, "i like dogs").await.unwrap();
+
+    let edited = harness.shown();
+    assert_eq!(edited.content, "i like dogs");
+    assert_eq!(harness.discord.visible().len(), 1);
+
+    harness.prev(edited.id).await.unwrap();
+    assert_eq!(harness.shown().content, "i like cats");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn full_contexts_are_drained_and_summarized() {
+    let config = CONFIG.replace("max_stm = 50", "max_stm = 4\nstm_drain_percentage = 0.5");
+    let harness = Harness::with_config(
+        &config,
+        [
+            "<user> talked about their garden",
+            "- <user> grows tomatoes",
+            "how are the tomatoes?",
+        ],
+    )
+    .await;
+    harness
+        .exchange("i grow tomatoes", "that sounds lovely")
+        .await;
+    harness
+        .exchange("they are almost ripe", "can't wait to hear how they taste")
+        .await;
+
+    harness.send("guess what").await.unwrap();
+    assert_eq!(harness.shown().content, "how are the tomatoes?");
+
+    // the story so far is updated before the drained messages are stored as memories
+    let preambles = harness.model.preambles();
+    assert!(preambles[0].starts_with("# Story Summarization Assistant"));
+    assert!(preambles[1].starts_with("# Summarization Assistant"));
+    // and the response already knows the story so far
+    assert!(preambles[2].contains("## Story So Far"));
+    assert!(preambles[2].contains("<user> talked about their garden"));
+
+    assert_eq!(
+        harness.summary().await.as_deref(),
+        Some("<user> talked about their garden")
+    );
+
+    let memories = harness.memories().await;
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].content, "- <user> grows tomatoes");
+    assert_eq!(memories[0].user_id, Some(USER));
+}
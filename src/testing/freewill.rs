@@ -0,0 +1,136 @@
+use chrono::{Duration, TimeZone, Utc};
+
+use super::{CHANNEL, CONFIG, Harness, USER, wait_until};
+use crate::{bot::handler::Handler, utils::macros::config};
+
+#[tokio::test(flavor = "multi_thread")]
+async fn freewill_reaches_out_once_the_user_went_quiet() {
+    let harness = Harness::new([
+        "- <user> bought a sailboat",
+        "still thinking about that boat of yours?",
+    ])
+    .await;
+    harness
+        .exchange(
+            "i finally bought a sailboat",
+            "that's amazing, what's her name?",
+        )
+        .await;
+
+    harness.dispatch_freewill().await;
+
+    // a couple minutes of silence is too early to reach out
+    harness.advance(Duration::minutes(2)).await;
+    assert!(harness.discord.sent().is_empty());
+    assert!(!harness.freewill_stopped().await);
+
+    // past max_time_secs reaching out is certain
+    harness.advance(Duration::hours(1)).await;
+
+    let sent = harness.discord.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel, CHANNEL);
+    assert_eq!(sent[0].content, "still thinking about that boat of yours?");
+
+    // the conversation is stored as a memory before reaching out
+    let preambles = harness.model.preambles();
+    assert!(preambles[0].starts_with("# Summarization Assistant"));
+    let memories = harness.memories().await;
+    assert_eq!(memories.len(), 1);
+    assert_eq!(memories[0].content, "- <user> bought a sailboat");
+    assert_eq!(memories[0].user_id, Some(USER));
+
+    assert_eq!(harness.unanswered_freewills().await, 1);
+
+    // the buttons go away once anything else happens
+    let _ = harness.data().msg_channel.0.send("hello".to_string());
+    wait_until(|| async { !harness.discord.edited().is_empty() }).await;
+    assert_eq!(harness.discord.edited(), vec![(CHANNEL, sent[0].id)]);
+
+    // an unanswered freewill message is as far as it goes
+    harness.advance(Duration::hours(1)).await;
+    assert!(harness.freewill_stopped().await);
+    assert_eq!(harness.discord.sent().len(), 1);
+    assert_eq!(harness.model.remaining(), 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn freewill_decides_without_loading_the_conversation() {
+    let harness = Harness::new(Vec::<String>::new()).await;
+    harness.exchange("hi", "hello!").await;
+
+    // as if it was evicted
+    harness.data().user_map.write().await.clear();
+
+    harness.dispatch_freewill().await;
+    harness.advance(Duration::minutes(2)).await;
+
+    assert!(!harness.freewill_stopped().await);
+    assert!(harness.data().user_map.read().await.is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn freewill_waits_out_quiet_hours() {
+    // 12:30 in Berlin, quiet from 13:00 to 15:00 there
+    let start = Utc.with_ymd_and_hms(2025, 6, 1, 10, 30, 0).unwrap();
+    let config = format!(
+        "{}\n[config.freewill.quiet_hours]\nstart = 13\nend = 15\n",
+        CONFIG.replace("max_ltm = 10", "max_ltm = 10\ntimezone = \"Europe/Berlin\"")
+    );
+
+    let harness = Harness::with_start(&config, start, ["- <user> likes tea", "tea time?"]).await;
+    harness
+        .exchange("i could go for some tea", "green or black?")
+        .await;
+
+    harness.dispatch_freewill().await;
+
+    harness.advance(Duration::hours(1)).await;
+    harness.advance(Duration::hours(1)).await;
+    assert!(harness.discord.sent().is_empty());
+    assert!(!harness.freewill_stopped().await);
+
+    harness.advance(Duration::hours(1)).await;
+    assert_eq!(harness.discord.sent().len(), 1);
+    assert_eq!(harness.discord.sent()[0].content, "tea time?");
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn freewill_chance_follows_the_simulated_clock() {
+    // far from the wall clock, so that mixing the two would show
+    let start = Utc.with_ymd_and_hms(2025, 6, 1, 10, 0, 0).unwrap();
+    let harness = Harness::with_start(CONFIG, start, Vec::<String>::new()).await;
+    harness.exchange("hi", "hello!").await;
+
+    let last_message = harness.data().activities.get(USER).unwrap().last_message;
+    assert_eq!(last_message, Some(start));
+
+    let freewill = config!(harness.data()).freewill;
+    let chance_after = |minutes| {
+        harness.clock.advance(Duration::minutes(minutes));
+        let gap = harness.data().env.now() - last_message.unwrap();
+        Handler::freewill_chance(&freewill, gap)
+    };
+
+    // 5 minutes of silence, under min_time_secs
+    assert_eq!(chance_after(5), 0.0);
+    // 15 minutes, halfway between min_time_secs and max_time_secs
+    let expected = 5.0f64.exp_m1() / 10.0f64.exp_m1();
+    assert!((chance_after(10) - expected).abs() < 1e-9);
+    // 25 minutes, past max_time_secs
+    assert_eq!(chance_after(10), 1.0);
+}
+
+#[test]
+fn seeded_rng_replays_the_same_numbers() {
+    use crate::utils::rng::SharedRng;
+
+    let (first, second) = (SharedRng::seeded(7), SharedRng::seeded(7));
+    let rolls = |rng: &SharedRng| {
+        (0..16)
+            .map(|_| rng.random_range(60..120u64))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(rolls(&first), rolls(&second));
+}
@@ -0,0 +1,308 @@
+//! A bot running on a simulated clock, scripted models and a mock Discord, for tests to
+//! drive.
+
+use std::{
+    future::Future,
+    sync::{
+        Arc,
+        atomic::{AtomicU64, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use serenity::all::{ChannelId, MessageId, UserId};
+
+use crate::{
+    bot::{
+        Data,
+        handler::{Handler, framework::InnerData},
+    },
+    chat::{
+        ChatMessage,
+        archive::storage::{Memory, MemoryNamespace, MemoryStorage},
+        client::SharedMemory,
+        context::{ConversationScope, Speaker},
+        engine::EngineGuard,
+    },
+    config::store::ChatBotConfig,
+    utils::{Environment, clock::SimulatedClock, rng::SharedRng},
+};
+
+mod discord;
+mod flows;
+mod freewill;
+mod models;
+mod provider;
+mod reminder;
+mod store;
+
+pub use discord::*;
+pub use models::*;
+
+pub const USER: UserId = UserId::new(1);
+pub const CHANNEL: ChannelId = ChannelId::new(2);
+pub const SCOPE: ConversationScope = ConversationScope::Direct(USER);
+
+/// Every harness rolls the same numbers.
+const SEED: u64 = 42;
+
+/// Freewill becomes likely after 10 minutes of silence and certain after 20.
+pub const CONFIG: &str = r#"
+[config.discord]
+token = "test"
+
+[config.llm.completion]
+model = "scripted"
+provider = "openai"
+api_key = "test"
+stream = false
+
+[config.llm.retry]
+max_attempts = 1
+
+[config.llm.embedding]
+model = "fake"
+backend = "local"
+vector_size = 16
+
+[config.freewill]
+min_time_secs = 600
+max_time_secs = 1200
+steepness = 10.0
+
+[config.context]
+max_stm = 50
+
+[config.context.system]
+chatbot_name = "bot"
+user_name = "user"
+about = "A bot under test."
+max_ltm = 10
+"#;
+
+pub struct Harness {
+    pub handler: Arc<Handler>,
+    pub clock: Arc<SimulatedClock>,
+    pub discord: Arc<MockDiscord>,
+    pub model: Arc<ScriptedModel>,
+    pub memory: SharedMemory,
+    next_id: AtomicU64,
+}
+
+impl Harness {
+    /// A harness with the default test config, the model answering with `responses`.
+    pub async fn new<S: Into<String>>(responses: impl IntoIterator<Item = S>) -> Self {
+        Self::with_config(CONFIG, responses).await
+    }
+
+    pub async fn with_config<S: Into<String>>(
+        config: &str,
+        responses: impl IntoIterator<Item = S>,
+    ) -> Self {
+        Self::with_start(config, Utc::now(), responses).await
+    }
+
+    /// A harness whose clock starts at `start`, for tests that depend on the time of day.
+    pub async fn with_start<S: Into<String>>(
+        config: &str,
+        start: DateTime<Utc>,
+        responses: impl IntoIterator<Item = S>,
+    ) -> Self {
+        let config = ChatBotConfig::from_toml(config).expect("test config is invalid");
+
+        let clock = Arc::new(SimulatedClock::new(start));
+        let env = Environment::new(clock.clone(), SharedRng::seeded(SEED));
+        let data: Data = Arc::new(InnerData::new(config.clone(), env));
+
+        let model = Arc::new(ScriptedModel::new(responses));
+        let memory = SharedMemory {
+            embedding_model: Arc::new(FakeEmbedder),
+            storage: Arc::new(
+                MemoryStorage::new(&config.llm, None, DIMENSIONS as u64)
+                    .expect("memory storage could not be created"),
+            ),
+        };
+        data.models
+            .insert(&config.llm, None, model.clone(), memory.clone())
+            .await
+            .expect("models could not be registered");
+
+        Self {
+            handler: Arc::new(Handler { data }),
+            clock,
+            discord: Arc::new(MockDiscord::default()),
+            model,
+            memory,
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    pub fn data(&self) -> &Data {
+        &self.handler.data
+    }
+
+    /// Adds a message of the user and the answer of the bot to the conversation, as if
+    /// both were just sent.
+    pub async fn exchange(&self, user: &str, bot: &str) {
+        let guard = EngineGuard::lock(self.data(), SCOPE)
+            .await
+            .expect("engine could not be loaded");
+        let mut engine = guard.engine().await.write().await;
+
+        engine.add_message(
+            ChatMessage::user(user.to_string()),
+            (self.message_id(), CHANNEL),
+        );
+        engine.add_message(
+            ChatMessage::assistant(bot.to_string()),
+            (self.message_id(), CHANNEL),
+        );
+        engine.autosave().await;
+    }
+
+    /// Sends a message of the user and waits for the reply, returning the id of its last
+    /// message.
+    pub async fn send(&self, content: &str) -> anyhow::Result<MessageId> {
+        let _ = self.data().msg_channel.0.send(content.to_string());
+
+        let speaker = Speaker {
+            id: USER,
+            name: "user".to_string(),
+        };
+        self.handler
+            .reply(
+                SCOPE,
+                content.to_string(),
+                speaker,
+                vec![],
+                (self.message_id(), CHANNEL),
+                self.discord.clone(),
+            )
+            .await
+    }
+
+    /// Clicks the regen button of a response.
+    pub async fn regen(&self, response: MessageId) -> anyhow::Result<()> {
+        self.handler
+            .regen_response(SCOPE, (response, CHANNEL).into(), self.discord.clone())
+            .await
+    }
+
+    /// Clicks the prev button of a response.
+    pub async fn prev(&self, response: MessageId) -> anyhow::Result<()> {
+        self.handler
+            .prev_response(SCOPE, (response, CHANNEL).into(), self.discord.clone())
+            .await
+    }
+
+    /// Clicks the next button of a response.
+    pub async fn next(&self, response: MessageId) -> anyhow::Result<()> {
+        self.handler
+            .next_response(SCOPE, (response, CHANNEL).into(), self.discord.clone())
+            .await
+    }
+
+    /// Rewrites a response through the edit modal.
+    pub async fn edit(&self, response: MessageId, content: &str) -> anyhow::Result<()> {
+        self.handler
+            .edit_response(
+                SCOPE,
+                (response, CHANNEL).into(),
+                content.to_string(),
+                self.discord.clone(),
+            )
+            .await
+    }
+
+    /// The newest message still shown, panicking if there is none.
+    pub fn shown(&self) -> Sent {
+        self.discord.visible().pop().expect("no message is shown")
+    }
+
+    /// The story so far of the conversation.
+    pub async fn summary(&self) -> Option<String> {
+        let guard = EngineGuard::peek(self.data(), SCOPE)
+            .await
+            .expect("engine could not be loaded");
+        let engine = guard.engine().await.read().await;
+
+        engine.summary().map(str::to_string)
+    }
+
+    /// Starts the freewill task of the user, returning once it went to sleep.
+    pub async fn dispatch_freewill(&self) {
+        let naps = self.clock.naps();
+        self.handler
+            .freewill_dispatch(USER, self.discord.clone())
+            .await;
+
+        wait_until(|| async { self.clock.naps() > naps }).await;
+    }
+
+    /// Starts delivering reminders, returning once it went to sleep.
+    pub async fn dispatch_reminders(&self) {
+        let naps = self.clock.naps();
+        self.handler.reminder_dispatch(self.discord.clone()).await;
+
+        wait_until(|| async { self.clock.naps() > naps }).await;
+    }
+
+    /// Moves the clock forward, returning once whatever woke up went back to sleep or
+    /// stopped.
+    pub async fn advance(&self, duration: chrono::Duration) {
+        let naps = self.clock.naps();
+        self.clock.advance(duration);
+
+        wait_until(|| async { self.clock.naps() > naps || self.freewill_stopped().await }).await;
+    }
+
+    pub async fn freewill_stopped(&self) -> bool {
+        self.data()
+            .freewill_map
+            .read()
+            .await
+            .get(&USER)
+            .is_none_or(|handle| handle.is_finished())
+    }
+
+    /// How many freewill messages the user left unanswered.
+    pub async fn unanswered_freewills(&self) -> usize {
+        let guard = EngineGuard::peek(self.data(), SCOPE)
+            .await
+            .expect("engine could not be loaded");
+        let engine = guard.engine().await.read().await;
+
+        engine.activity().unanswered
+    }
+
+    /// The memories stored about the user with the default persona.
+    pub async fn memories(&self) -> Vec<Memory> {
+        self.memory
+            .storage
+            .list(&MemoryNamespace::new(SCOPE, None))
+            .await
+            .expect("memories could not be listed")
+    }
+
+    fn message_id(&self) -> MessageId {
+        MessageId::new(self.next_id.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+/// Waits for `condition` to hold, in real time as it depends on other tasks.
+pub async fn wait_until<F, Fut>(mut condition: F)
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    for _ in 0..1000 {
+        if condition().await {
+            return;
+        }
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+
+    panic!("gave up waiting after 5 seconds");
+}
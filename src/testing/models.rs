@@ -0,0 +1,104 @@
+use std::{collections::VecDeque, sync::Mutex};
+
+use async_trait::async_trait;
+use rig::{
+    OneOrMany,
+    completion::{CompletionError, CompletionRequest},
+    embeddings::Embedding,
+    message::AssistantContent,
+    streaming::{StreamingChoice, StreamingResult},
+};
+
+use crate::chat::client::{ChatModel, Embedder};
+
+/// How many dimensions [FakeEmbedder] embeds into.
+pub const DIMENSIONS: usize = 16;
+
+/// A completion model answering with scripted responses, in order.
+pub struct ScriptedModel {
+    responses: Mutex<VecDeque<String>>,
+    /// The system prompt of every request it answered
+    preambles: Mutex<Vec<String>>,
+}
+
+impl ScriptedModel {
+    pub fn new<S: Into<String>>(responses: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            responses: Mutex::new(responses.into_iter().map(Into::into).collect()),
+            preambles: Mutex::new(vec![]),
+        }
+    }
+
+    pub fn preambles(&self) -> Vec<String> {
+        self.preambles.lock().unwrap().clone()
+    }
+
+    /// How many responses were not asked for yet.
+    pub fn remaining(&self) -> usize {
+        self.responses.lock().unwrap().len()
+    }
+
+    /// Takes the next response, remembering what it answered.
+    fn respond(&self, request: CompletionRequest) -> anyhow::Result<String> {
+        let response = self
+            .responses
+            .lock()
+            .unwrap()
+            .pop_front()
+            .ok_or(anyhow::anyhow!("no scripted response left"))?;
+
+        self.preambles
+            .lock()
+            .unwrap()
+            .push(request.preamble.unwrap_or_default());
+
+        Ok(response)
+    }
+}
+
+#[async_trait]
+impl ChatModel for ScriptedModel {
+    async fn completion(
+        &self,
+        request: CompletionRequest,
+    ) -> anyhow::Result<OneOrMany<AssistantContent>> {
+        Ok(OneOrMany::one(AssistantContent::text(
+            self.respond(request)?,
+        )))
+    }
+
+    /// Streams the response a word at a time.
+    async fn stream(&self, request: CompletionRequest) -> anyhow::Result<StreamingResult> {
+        let deltas = self
+            .respond(request)?
+            .split_inclusive(' ')
+            .map(|delta| Ok::<_, CompletionError>(StreamingChoice::Message(delta.to_string())))
+            .collect::<Vec<_>>();
+
+        Ok(Box::pin(futures::stream::iter(deltas)))
+    }
+}
+
+/// Embeds text as a bag of words, so that texts sharing words are similar.
+pub struct FakeEmbedder;
+
+#[async_trait]
+impl Embedder for FakeEmbedder {
+    async fn embed_text(&self, text: &str) -> anyhow::Result<Embedding> {
+        // never all zeroes, which has no direction to compare
+        let mut vec = vec![0.0; DIMENSIONS];
+        vec[0] = 1.0;
+
+        for word in text.split_whitespace() {
+            let hash = word.to_lowercase().bytes().fold(7usize, |hash, byte| {
+                hash.wrapping_mul(31).wrapping_add(byte.into())
+            });
+            vec[1 + hash % (DIMENSIONS - 1)] += 1.0;
+        }
+
+        Ok(Embedding {
+            document: text.to_string(),
+            vec,
+        })
+    }
+}
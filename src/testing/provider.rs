@@ -0,0 +1,218 @@
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
+    time::Duration,
+};
+
+use chrono::Utc;
+use rig::completion::CompletionError;
+
+use super::{CONFIG, SEED, ScriptedModel};
+use crate::{
+    chat::client::{FailureKind, ProviderChain, ProviderFailure},
+    config::store::ChatBotConfig,
+    utils::{Environment, clock::SimulatedClock, rng::SharedRng},
+};
+
+/// A chain of the scripted model alone, retrying as configured.
+async fn chain(retry: &str) -> ProviderChain {
+    chain_on(retry, Arc::new(SimulatedClock::new(Utc::now()))).await
+}
+
+/// A chain like [`chain`] whose waits and breakers run on `clock`.
+async fn chain_on(retry: &str, clock: Arc<SimulatedClock>) -> ProviderChain {
+    let config = CONFIG.replace("max_attempts = 1", retry);
+    let config = ChatBotConfig::from_toml(&config).expect("test config is invalid");
+    let env = Environment::new(clock, SharedRng::seeded(SEED));
+
+    ProviderChain::new(
+        Arc::new(ScriptedModel::new(Vec::<String>::new())),
+        &config.llm,
+        &env,
+    )
+    .await
+    .expect("provider chain could not be created")
+}
+
+/// Error bodies as providers answer with them.
+const UNAVAILABLE: &str = "upstream connect error";
+const UNAUTHORIZED: &str = r#"{"type":"error","error":{"type":"authentication_error"}}"#;
+const BAD_REQUEST: &str = r#"{"error":{"code":400,"status":"INVALID_ARGUMENT"}}"#;
+
+/// Runs the chain with every attempt failing with the provider answering `body`,
+/// returning how many attempts were made and what the chain failed with.
+async fn fail(chain: &ProviderChain, body: &str) -> (usize, anyhow::Error) {
+    let attempts = AtomicUsize::new(0);
+    let why = chain
+        .run(|_| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            async move { Err::<(), _>(CompletionError::ProviderError(body.to_string()).into()) }
+        })
+        .await
+        .unwrap_err();
+
+    (attempts.into_inner(), why)
+}
+
+async fn succeed(chain: &ProviderChain) -> anyhow::Result<()> {
+    chain.run(|_| async { Ok(()) }).await
+}
+
+#[test]
+fn provider_errors_are_classified_by_what_the_provider_answered() {
+    let classify = |body: &str| {
+        FailureKind::classify(&CompletionError::ProviderError(body.to_string()).into())
+    };
+
+    // OpenAI
+    assert_eq!(
+        classify(r#"{"error":{"type":"requests","code":"rate_limit_exceeded"}}"#),
+        FailureKind::RateLimited
+    );
+    assert_eq!(
+        classify(r#"{"error":{"type":"invalid_request_error","code":"invalid_api_key"}}"#),
+        FailureKind::Unauthorized
+    );
+    assert_eq!(
+        classify(r#"{"error":{"type":"invalid_request_error","code":null}}"#),
+        FailureKind::BadRequest
+    );
+
+    // Anthropic
+    assert_eq!(classify(UNAUTHORIZED), FailureKind::Unauthorized);
+    assert_eq!(
+        classify(r#"{"type":"error","error":{"type":"overloaded_error"}}"#),
+        FailureKind::Unavailable
+    );
+
+    // Google, by its status code
+    assert_eq!(
+        classify(r#"{"error":{"code":429,"status":"RESOURCE_EXHAUSTED"}}"#),
+        FailureKind::RateLimited
+    );
+    assert_eq!(classify(BAD_REQUEST), FailureKind::BadRequest);
+
+    // the text of an error is never guessed at
+    assert_eq!(
+        classify("Invalid API key provided"),
+        FailureKind::Unavailable
+    );
+    assert_eq!(
+        FailureKind::classify(&anyhow::anyhow!(UNAUTHORIZED)),
+        FailureKind::Unavailable
+    );
+    assert_eq!(classify(UNAVAILABLE), FailureKind::Unavailable);
+
+    // requests that could not be built are not worth sending again
+    let why = CompletionError::RequestError("unsupported attachment".into());
+    assert_eq!(FailureKind::classify(&why.into()), FailureKind::BadRequest);
+
+    // the context of an error counts as well
+    let why = anyhow::Error::from(CompletionError::ProviderError(UNAUTHORIZED.to_string()))
+        .context("completion failed");
+    assert_eq!(FailureKind::classify(&why), FailureKind::Unauthorized);
+}
+
+#[tokio::test]
+async fn breaker_opens_after_repeated_failures() {
+    let chain = chain("max_attempts = 1\nbase_delay_ms = 0\nfailure_threshold = 2").await;
+
+    let (attempts, why) = fail(&chain, UNAVAILABLE).await;
+    assert_eq!(attempts, 1);
+    let failure = why.downcast_ref::<ProviderFailure>().unwrap();
+    assert_eq!(failure.kind, FailureKind::Unavailable);
+
+    // still tried, reaching the threshold
+    let (attempts, _) = fail(&chain, UNAVAILABLE).await;
+    assert_eq!(attempts, 1);
+
+    // open, not even tried until the cooldown is over
+    let (attempts, why) = fail(&chain, UNAVAILABLE).await;
+    assert_eq!(attempts, 0);
+    assert!(why.to_string().contains("cooling down"));
+    assert!(succeed(&chain).await.is_err());
+}
+
+#[tokio::test]
+async fn breaker_resets_on_success() {
+    let chain = chain("max_attempts = 1\nbase_delay_ms = 0\nfailure_threshold = 2").await;
+
+    fail(&chain, UNAVAILABLE).await;
+    succeed(&chain).await.unwrap();
+
+    // the earlier failure no longer counts
+    fail(&chain, UNAVAILABLE).await;
+    succeed(&chain).await.unwrap();
+}
+
+#[tokio::test]
+async fn breaker_closes_after_the_cooldown() {
+    let clock = Arc::new(SimulatedClock::new(Utc::now()));
+    let chain = chain_on(
+        "max_attempts = 1\nbase_delay_ms = 0\nfailure_threshold = 1\ncooldown_secs = 60",
+        clock.clone(),
+    )
+    .await;
+
+    fail(&chain, UNAVAILABLE).await;
+    assert!(succeed(&chain).await.is_err());
+
+    clock.advance(chrono::Duration::seconds(59));
+    assert!(succeed(&chain).await.is_err());
+
+    clock.advance(chrono::Duration::seconds(2));
+    succeed(&chain).await.unwrap();
+}
+
+#[tokio::test]
+async fn unauthorized_providers_are_skipped_right_away() {
+    let chain = chain("max_attempts = 3\nbase_delay_ms = 0\nfailure_threshold = 5").await;
+
+    // no other provider is left to try, so it does not wait for another round
+    let (attempts, why) = fail(&chain, UNAUTHORIZED).await;
+    assert_eq!(attempts, 1);
+    let failure = why.downcast_ref::<ProviderFailure>().unwrap();
+    assert_eq!(failure.kind, FailureKind::Unauthorized);
+
+    assert!(succeed(&chain).await.is_err());
+}
+
+#[tokio::test]
+async fn bad_requests_are_not_retried() {
+    let chain = chain("max_attempts = 3\nbase_delay_ms = 0\nfailure_threshold = 1").await;
+
+    let (attempts, _) = fail(&chain, BAD_REQUEST).await;
+    assert_eq!(attempts, 1);
+
+    // nor held against the provider
+    succeed(&chain).await.unwrap();
+}
+
+#[tokio::test]
+async fn failures_are_retried_up_to_max_attempts() {
+    let chain = chain("max_attempts = 3\nbase_delay_ms = 0\nfailure_threshold = 10").await;
+
+    let (attempts, _) = fail(&chain, UNAVAILABLE).await;
+    assert_eq!(attempts, 3);
+}
+
+#[tokio::test]
+async fn backoff_grows_up_to_the_max_delay() {
+    let chain = chain("max_attempts = 3\nbase_delay_ms = 100\nmax_delay_ms = 1000").await;
+
+    for _ in 0..100 {
+        assert!(chain.backoff(0) <= Duration::from_millis(100));
+        assert!(chain.backoff(2) <= Duration::from_millis(400));
+        assert!(chain.backoff(10) <= Duration::from_millis(1000));
+        // far past the point where doubling overflows
+        assert!(chain.backoff(64) <= Duration::from_millis(1000));
+    }
+
+    // jittered, not always the cap
+    let delays = (0..100)
+        .map(|_| chain.backoff(10))
+        .collect::<std::collections::HashSet<_>>();
+    assert!(delays.len() > 1);
+}
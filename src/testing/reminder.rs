@@ -0,0 +1,137 @@
+use chrono::{Duration, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use super::{CHANNEL, Harness, SCOPE, USER, wait_until};
+use crate::chat::reminder::parse_when;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reminders_are_brought_up_once_due() {
+    let harness = Harness::new(["don't forget to water the plants!"]).await;
+    harness
+        .exchange("remind me to water the plants in 2 hours", "will do!")
+        .await;
+
+    let reminders = &harness.data().reminders;
+    let due = parse_when("2h", reminders.now(), Tz::UTC).unwrap();
+    let reminder = reminders
+        .add(SCOPE, Some(USER), due, "water the plants".to_string())
+        .await
+        .unwrap();
+
+    harness.dispatch_reminders().await;
+
+    harness.advance(Duration::hours(1)).await;
+    assert!(harness.discord.sent().is_empty());
+    assert_eq!(reminders.list(SCOPE).unwrap(), vec![reminder]);
+
+    harness.advance(Duration::hours(1)).await;
+    wait_until(|| async { !harness.discord.sent().is_empty() }).await;
+
+    let sent = harness.discord.sent();
+    assert_eq!(sent.len(), 1);
+    assert_eq!(sent[0].channel, CHANNEL);
+    assert_eq!(sent[0].content, "don't forget to water the plants!");
+
+    wait_until(|| async { reminders.list(SCOPE).unwrap().is_empty() }).await;
+    assert_eq!(harness.model.remaining(), 0);
+    // the user was not left hanging the way freewill would
+    assert_eq!(harness.unanswered_freewills().await, 0);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reminders_are_kept_until_they_are_brought_up() {
+    // no response to bring the reminder up with
+    let harness = Harness::new(Vec::<String>::new()).await;
+
+    let reminders = &harness.data().reminders;
+    let due = parse_when("2h", reminders.now(), Tz::UTC).unwrap();
+    let reminder = reminders
+        .add(SCOPE, Some(USER), due, "water the plants".to_string())
+        .await
+        .unwrap();
+
+    harness.dispatch_reminders().await;
+    harness.advance(Duration::hours(2)).await;
+    wait_until(|| async { reminders.list(SCOPE).unwrap()[0].attempts == 1 }).await;
+
+    let pending = reminders.list(SCOPE).unwrap();
+    assert_eq!(pending.len(), 1);
+    assert_eq!(pending[0].id, reminder.id);
+    assert!(pending[0].due > reminder.due);
+    assert!(harness.discord.sent().is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn reminders_are_cancelled_by_whoever_asked() {
+    let harness = Harness::new(Vec::<String>::new()).await;
+    let reminders = &harness.data().reminders;
+    let due = reminders.now() + Duration::minutes(30);
+
+    let reminder = reminders
+        .add(SCOPE, Some(USER), due, "stretch".to_string())
+        .await
+        .unwrap();
+    assert!(
+        reminders
+            .add(SCOPE, Some(USER), reminders.now(), "too late".to_string())
+            .await
+            .is_err()
+    );
+
+    let stranger = serenity::all::UserId::new(99);
+    assert!(
+        reminders
+            .cancel(SCOPE, reminder.id, Some(stranger))
+            .await
+            .is_err()
+    );
+    assert_eq!(
+        reminders
+            .cancel(SCOPE, reminder.id, Some(USER))
+            .await
+            .unwrap(),
+        Some(reminder.clone())
+    );
+    assert_eq!(reminders.cancel(SCOPE, 1, Some(USER)).await.unwrap(), None);
+
+    // the id of a cancelled reminder is not given out again
+    let next = reminders
+        .add(SCOPE, Some(USER), due, "stretch again".to_string())
+        .await
+        .unwrap();
+    assert!(next.id > reminder.id);
+}
+
+#[test]
+fn reminder_times_are_parsed_in_the_users_timezone() {
+    let tokyo: Tz = "Asia/Tokyo".parse().unwrap();
+    // 20:00 in Tokyo
+    let now = Utc.with_ymd_and_hms(2025, 6, 1, 11, 0, 0).unwrap();
+
+    assert_eq!(
+        parse_when("30m", now, tokyo).unwrap(),
+        now + Duration::minutes(30)
+    );
+    assert_eq!(
+        parse_when("in 1h 30m", now, tokyo).unwrap(),
+        now + Duration::minutes(90)
+    );
+    assert_eq!(
+        parse_when("21:15", now, tokyo).unwrap(),
+        Utc.with_ymd_and_hms(2025, 6, 1, 12, 15, 0).unwrap()
+    );
+    // already past today
+    assert_eq!(
+        parse_when("08:00", now, tokyo).unwrap(),
+        Utc.with_ymd_and_hms(2025, 6, 1, 23, 0, 0).unwrap()
+    );
+    assert_eq!(
+        parse_when("2025-06-03 09:00", now, tokyo).unwrap(),
+        Utc.with_ymd_and_hms(2025, 6, 3, 0, 0, 0).unwrap()
+    );
+    assert!(parse_when("soon", now, tokyo).is_err());
+    assert!(parse_when("0m", now, tokyo).is_err());
+    assert!(parse_when("367d", now, tokyo).is_err());
+    assert!(parse_when("9223372036854775807w", now, tokyo).is_err());
+    assert!(parse_when("99999999999999999999m", now, tokyo).is_err());
+}
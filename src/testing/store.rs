@@ -0,0 +1,126 @@
+use branch_context::Messages;
+use indexmap::IndexMap;
+use serenity::all::MessageId;
+
+use super::{CHANNEL, SCOPE};
+use crate::{
+    chat::{
+        ChatMessage,
+        archive::storage::MemoryNamespace,
+        context::{MessageIdentifier, SavedContext, open_store},
+    },
+    config::structure::{ContextConfig, ConversationStoreKind},
+};
+
+/// Every branch of every message, along with the selected one.
+fn branches(saved: &SavedContext) -> Vec<(MessageIdentifier, Vec<Option<String>>, usize)> {
+    saved
+        .messages
+        .iter()
+        .map(|(identifier, messages)| {
+            let mut cursor = messages.clone();
+            let mut selected = 0;
+            while cursor.backward {
+                cursor.backward();
+                selected += 1;
+            }
+
+            let mut contents = vec![cursor.selected().content()];
+            while cursor.forward {
+                cursor.forward();
+                contents.push(cursor.selected().content());
+            }
+
+            (identifier.clone(), contents, selected)
+        })
+        .collect()
+}
+
+fn message(
+    id: u64,
+    branches: &[&str],
+    selected: usize,
+) -> (MessageIdentifier, Messages<ChatMessage>) {
+    let mut messages = Messages::new(ChatMessage::assistant(branches[0].to_string()).into());
+    for branch in &branches[1..] {
+        messages.push(ChatMessage::assistant(branch.to_string()));
+    }
+    for _ in selected + 1..branches.len() {
+        messages.backward();
+    }
+
+    ((MessageId::new(id), CHANNEL).into(), messages)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn sqlite_keeps_every_branch_across_saves() {
+    let folder = std::env::temp_dir().join(format!("store-roundtrip-{}", std::process::id()));
+    let config = ContextConfig {
+        save_to_disk_folder: Some(folder.clone()),
+        store: Some(ConversationStoreKind::Sqlite),
+        ..Default::default()
+    };
+    let store = open_store(&config).unwrap().unwrap();
+
+    let mut saved = SavedContext {
+        messages: IndexMap::from([
+            message(1, &["hi"], 0),
+            message(2, &["hello", "hey", "howdy"], 1),
+            message(3, &["bye", "see you"], 1),
+        ]),
+        summary: Some("they greeted".to_string()),
+        persona: None,
+        channel: Some(CHANNEL),
+    };
+    store.save(SCOPE, &saved.as_ref()).await.unwrap();
+
+    let loaded = store.load(SCOPE).await.unwrap().unwrap();
+    assert_eq!(branches(&loaded), branches(&saved));
+    assert_eq!(loaded.summary, saved.summary);
+    assert_eq!(loaded.channel, saved.channel);
+
+    // saving again only touches what changed, which still has to come back as it was
+    saved.messages.pop();
+    let (_, messages) = saved.messages.get_index_mut(1).unwrap();
+    messages.push(ChatMessage::assistant("greetings".to_string()));
+    store.save(SCOPE, &saved.as_ref()).await.unwrap();
+
+    let loaded = store.load(SCOPE).await.unwrap().unwrap();
+    assert_eq!(branches(&loaded), branches(&saved));
+    assert_eq!(
+        branches(&loaded)[1],
+        (
+            (MessageId::new(2), CHANNEL).into(),
+            vec![
+                Some("hello".to_string()),
+                Some("hey".to_string()),
+                Some("howdy".to_string()),
+                Some("greetings".to_string()),
+            ],
+            3,
+        )
+    );
+
+    std::fs::remove_dir_all(folder).ok();
+}
+
+#[test]
+fn persona_namespaces_stay_apart_after_sanitizing() {
+    let namespace = |persona: Option<&str>| MemoryNamespace::new(SCOPE, persona);
+
+    assert_eq!(namespace(None).as_str(), SCOPE.key());
+    assert_eq!(
+        namespace(Some("owl")).as_str(),
+        format!("{}_persona_owl", SCOPE.key())
+    );
+
+    let names = ["Night Owl", "night_owl", "night owl", "Night-Owl"];
+    let namespaces = names
+        .map(|name| namespace(Some(name)))
+        .into_iter()
+        .collect::<std::collections::HashSet<_>>();
+    assert_eq!(namespaces.len(), names.len());
+
+    // the same name always ends up in the same place
+    assert_eq!(namespace(Some("Night Owl")), namespace(Some("Night Owl")));
+}
@@ -0,0 +1,105 @@
+use serenity::all::{Attachment as DiscordAttachment, Embed, Message};
+
+use crate::{
+    chat::context::{Attachment, image_media_type},
+    config::structure::AttachmentConfig,
+};
+
+const DEFAULT_MAX_SIZE_KB: u64 = 8 * 1024;
+const DEFAULT_MAX_TEXT_CHARS: usize = 8000;
+
+/// Extensions read as text even when Discord does not say so.
+const TEXT_EXTENSIONS: [&str; 24] = [
+    "txt", "md", "log", "csv", "json", "toml", "yaml", "yml", "xml", "html", "css", "ini", "rs",
+    "py", "js", "ts", "c", "h", "cpp", "java", "go", "sh", "lua", "sql",
+];
+
+/// Downloads whatever was attached to a message, within the configured limits, and turns
+/// its embeds into text.
+pub async fn collect(msg: &Message, config: &AttachmentConfig, vision: bool) -> Vec<Attachment> {
+    if !config.enabled.unwrap_or(true) {
+        return vec![];
+    }
+
+    let max_size = config.max_size_kb.unwrap_or(DEFAULT_MAX_SIZE_KB) * 1024;
+    let max_chars = config.max_text_chars.unwrap_or(DEFAULT_MAX_TEXT_CHARS);
+
+    let mut attachments = Vec::with_capacity(msg.attachments.len());
+    for file in &msg.attachments {
+        attachments.push(collect_file(file, max_size, max_chars, vision).await);
+    }
+
+    attachments.extend(
+        msg.embeds
+            .iter()
+            .filter_map(|embed| collect_embed(embed, max_chars)),
+    );
+
+    attachments
+}
+
+async fn collect_file(
+    file: &DiscordAttachment,
+    max_size: u64,
+    max_chars: usize,
+    vision: bool,
+) -> Attachment {
+    let name = file.filename.clone();
+    let mime = file.content_type.clone().unwrap_or_default();
+
+    let image = image_media_type(&mime).is_some();
+    let text = mime.starts_with("text/")
+        || mime.contains("json")
+        || mime.contains("xml")
+        || name.rsplit_once('.').is_some_and(|(_, extension)| {
+            TEXT_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+        });
+
+    if image && !vision {
+        return Attachment::omitted(name, "the model cannot see images");
+    }
+    if !image && !text {
+        return Attachment::omitted(name, "unsupported file type");
+    }
+    if file.size as u64 > max_size {
+        return Attachment::omitted(name, format!("larger than {}KB", max_size / 1024));
+    }
+
+    match file.download().await {
+        Ok(bytes) if image => Attachment::image(name, mime, file.url.clone(), &bytes),
+        Ok(bytes) => Attachment::text(name, &bytes, max_chars),
+        Err(why) => {
+            log::warn!("failed to download attachment {name}: {why:?}");
+            Attachment::omitted(name, "failed to download")
+        }
+    }
+}
+
+fn collect_embed(embed: &Embed, max_chars: usize) -> Option<Attachment> {
+    let mut lines = vec![];
+
+    if let Some(title) = &embed.title {
+        lines.push(title.clone());
+    }
+    if let Some(description) = &embed.description {
+        lines.push(description.clone());
+    }
+    for field in &embed.fields {
+        lines.push(format!("{}: {}", field.name, field.value));
+    }
+    if let Some(footer) = &embed.footer {
+        lines.push(footer.text.clone());
+    }
+
+    if lines.is_empty() {
+        return None;
+    }
+
+    let name = embed.url.clone().unwrap_or_else(|| "embed".to_string());
+
+    Some(Attachment::text(
+        name,
+        lines.join("\n").as_bytes(),
+        max_chars,
+    ))
+}
@@ -0,0 +1,71 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+
+/// Where the current time comes from and how waiting works, so that both can be
+/// simulated.
+#[async_trait]
+pub trait Clock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+
+    /// Waits until `duration` went by on this clock.
+    async fn sleep(&self, duration: Duration);
+}
+
+/// The wall clock.
+pub struct SystemClock;
+
+#[async_trait]
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        tokio::time::sleep(duration).await
+    }
+}
+
+/// A clock that only moves when told to, waking whoever slept past the new time.
+#[cfg(test)]
+pub struct SimulatedClock {
+    now: tokio::sync::watch::Sender<DateTime<Utc>>,
+    /// How many times anyone went to sleep on this clock
+    naps: std::sync::atomic::AtomicUsize,
+}
+
+#[cfg(test)]
+impl SimulatedClock {
+    pub fn new(start: DateTime<Utc>) -> Self {
+        Self {
+            now: tokio::sync::watch::channel(start).0,
+            naps: Default::default(),
+        }
+    }
+
+    pub fn advance(&self, duration: chrono::Duration) {
+        self.now.send_modify(|now| *now += duration);
+    }
+
+    pub fn naps(&self) -> usize {
+        self.naps.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl Clock for SimulatedClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.borrow()
+    }
+
+    async fn sleep(&self, duration: Duration) {
+        let deadline =
+            self.now() + chrono::Duration::from_std(duration).unwrap_or(chrono::Duration::zero());
+        let mut now = self.now.subscribe();
+
+        self.naps.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        let _ = now.wait_for(|now| *now >= deadline).await;
+    }
+}
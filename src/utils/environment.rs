@@ -0,0 +1,35 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+
+use super::{
+    clock::{Clock, SystemClock},
+    rng::SharedRng,
+};
+
+/// The clock and random numbers that engines and background tasks run on, the real ones
+/// unless tests simulate them.
+#[derive(Clone)]
+pub struct Environment {
+    pub clock: Arc<dyn Clock>,
+    pub rng: Arc<SharedRng>,
+}
+
+impl Environment {
+    pub fn new(clock: Arc<dyn Clock>, rng: SharedRng) -> Self {
+        Self {
+            clock,
+            rng: Arc::new(rng),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+}
+
+impl Default for Environment {
+    fn default() -> Self {
+        Self::new(Arc::new(SystemClock), SharedRng::from_os_rng())
+    }
+}
@@ -1,5 +1,7 @@
 use futures::StreamExt;
-use serenity::all::{ChannelId, CreateButton, CreateMessage, Http, MessageId};
+use serenity::all::{ChannelId, CreateActionRow, CreateButton, CreateMessage, MessageId};
+
+use crate::bot::Outbound;
 
 pub fn time_to_string(time: chrono::Duration) -> String {
     match time.num_seconds() {
@@ -65,6 +67,31 @@ pub enum RegenOrNext {
     Next,
 }
 
+/// The prev, regen/next and edit buttons of the last message of a response.
+pub fn action_row(state: ButtonStates) -> CreateActionRow {
+    let (regen_or_next_id, regen_or_next_emoji) = match state.regen_or_next {
+        RegenOrNext::Next => ("next", '⏩'),
+        RegenOrNext::Regen => ("regen", '♻'),
+    };
+
+    CreateActionRow::Buttons(vec![
+        CreateButton::new("prev")
+            .label("")
+            .emoji('⏪')
+            .style(serenity::all::ButtonStyle::Secondary)
+            .disabled(state.prev_disabled),
+        CreateButton::new(regen_or_next_id)
+            .label("")
+            .emoji(regen_or_next_emoji)
+            .style(serenity::all::ButtonStyle::Secondary),
+        CreateButton::new("edit")
+            .label("")
+            .emoji('✏')
+            .style(serenity::all::ButtonStyle::Secondary)
+            .disabled(false),
+    ])
+}
+
 pub fn chunk_message(message: &str, state: ButtonStates) -> anyhow::Result<Vec<CreateMessage>> {
     let mut chunks = chunk_string(message);
     let last = chunks.pop().ok_or(anyhow::anyhow!("no chunks"))?;
@@ -74,33 +101,9 @@ pub fn chunk_message(message: &str, state: ButtonStates) -> anyhow::Result<Vec<C
         .map(|chunk| CreateMessage::new().content(chunk))
         .collect::<Vec<_>>();
 
-    let (regen_or_next_id, regen_or_next_emoji) = match state.regen_or_next {
-        RegenOrNext::Next => ("next", '⏩'),
-        RegenOrNext::Regen => ("regen", '♻'),
-    };
-
     let message = CreateMessage::new()
         .content(last)
-        .button(
-            CreateButton::new("prev")
-                .label("")
-                .emoji('⏪')
-                .style(serenity::all::ButtonStyle::Secondary)
-                .disabled(state.prev_disabled),
-        )
-        .button(
-            CreateButton::new(regen_or_next_id)
-                .label("")
-                .emoji(regen_or_next_emoji)
-                .style(serenity::all::ButtonStyle::Secondary),
-        )
-        .button(
-            CreateButton::new("edit")
-                .label("")
-                .emoji('✏')
-                .style(serenity::all::ButtonStyle::Secondary)
-                .disabled(false),
-        );
+        .components(vec![action_row(state)]);
 
     messages.push(message);
 
@@ -109,17 +112,11 @@ pub fn chunk_message(message: &str, state: ButtonStates) -> anyhow::Result<Vec<C
 
 pub async fn send_message_batch(
     channel: ChannelId,
-    http: &Http,
+    outbound: &dyn Outbound,
     messages: Vec<CreateMessage>,
 ) -> anyhow::Result<Vec<MessageId>> {
     futures::stream::iter(messages)
-        .then(|message| async {
-            channel
-                .send_message(http, message)
-                .await
-                .map(|msg| msg.id)
-                .map_err(anyhow::Error::from)
-        })
+        .then(|message| outbound.send_message(channel, message))
         .collect::<Vec<_>>()
         .await
         .into_iter()
@@ -128,17 +125,11 @@ pub async fn send_message_batch(
 
 pub async fn delete_message_batch(
     channel: ChannelId,
-    http: &Http,
+    outbound: &dyn Outbound,
     message_ids: Vec<MessageId>,
 ) -> anyhow::Result<()> {
     futures::stream::iter(message_ids)
-        .then(async |message_id| {
-            channel
-                .delete_message(http, message_id)
-                .await
-                .map(|_| ())
-                .map_err(anyhow::Error::from)
-        })
+        .then(|message_id| outbound.delete_message(channel, message_id))
         .collect::<Vec<_>>()
         .await
         .into_iter()
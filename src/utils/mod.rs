@@ -1,5 +1,11 @@
+pub mod attachments;
+pub mod clock;
+pub mod environment;
 pub mod log;
 pub mod macros;
 pub mod misc;
+pub mod rng;
+pub mod stream;
 
+pub use environment::Environment;
 pub use misc::time_to_string;
@@ -0,0 +1,50 @@
+use std::sync::{Mutex, PoisonError};
+
+use rand::{
+    Rng, SeedableRng,
+    distr::{
+        Distribution, StandardUniform,
+        uniform::{SampleRange, SampleUniform},
+    },
+    rngs::StdRng,
+};
+
+/// A random number generator shared across tasks, which can be seeded to replay the
+/// same numbers.
+pub struct SharedRng(Mutex<StdRng>);
+
+impl SharedRng {
+    pub fn from_os_rng() -> Self {
+        Self(Mutex::new(StdRng::from_os_rng()))
+    }
+
+    pub fn seeded(seed: u64) -> Self {
+        Self(Mutex::new(StdRng::seed_from_u64(seed)))
+    }
+
+    pub fn random<T>(&self) -> T
+    where
+        StandardUniform: Distribution<T>,
+    {
+        self.with(|rng| rng.random())
+    }
+
+    pub fn random_range<T, R>(&self, range: R) -> T
+    where
+        T: SampleUniform,
+        R: SampleRange<T>,
+    {
+        self.with(|rng| rng.random_range(range))
+    }
+
+    /// `true` with a probability of `p`, which has to be between 0 and 1.
+    pub fn random_bool(&self, p: f64) -> bool {
+        self.with(|rng| rng.random_bool(p))
+    }
+
+    fn with<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        // a panic elsewhere cannot leave the generator itself in a broken state
+        let mut rng = self.0.lock().unwrap_or_else(PoisonError::into_inner);
+        f(&mut rng)
+    }
+}
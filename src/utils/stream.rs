@@ -0,0 +1,119 @@
+use std::{sync::Arc, time::Duration};
+
+use serenity::all::{ChannelId, CreateMessage, EditMessage, MessageId};
+use tokio::{sync::mpsc::UnboundedReceiver, time::Instant};
+
+use crate::{bot::Outbound, chat::client::StreamEvent};
+
+use super::misc::{self, ButtonStates};
+
+/// Discord rate limits message edits, so streamed text is flushed at most this often.
+const EDIT_INTERVAL: Duration = Duration::from_millis(1200);
+
+/// Progressively renders a streamed response into Discord messages, rolling over to new
+/// messages at the same boundaries [misc::chunk_string] splits on.
+pub struct MessageStreamer {
+    channel: ChannelId,
+    outbound: Arc<dyn Outbound>,
+    content: String,
+    /// The messages rendered so far and what each of them says
+    messages: Vec<(MessageId, String)>,
+    last_edit: Instant,
+}
+
+impl MessageStreamer {
+    pub fn new(channel: ChannelId, outbound: Arc<dyn Outbound>) -> Self {
+        Self {
+            channel,
+            outbound,
+            content: String::new(),
+            messages: vec![],
+            last_edit: Instant::now(),
+        }
+    }
+
+    /// Renders stream events until every sender is dropped.
+    pub async fn consume(&mut self, mut events: UnboundedReceiver<StreamEvent>) {
+        while let Some(event) = events.recv().await {
+            match event {
+                StreamEvent::Delta(delta) => self.content.push_str(&delta),
+                StreamEvent::Reset => self.content.clear(),
+            }
+
+            if self.last_edit.elapsed() >= EDIT_INTERVAL {
+                if let Err(why) = self.render(&self.content.clone(), None).await {
+                    log::warn!("failed to render streamed response: {why:?}");
+                }
+            }
+        }
+    }
+
+    /// Replaces whatever was streamed with the final response, adding the buttons to its
+    /// last message. Returns the ids of every message the response spans.
+    pub async fn finish(
+        mut self,
+        content: &str,
+        state: ButtonStates,
+    ) -> anyhow::Result<Vec<MessageId>> {
+        if content.is_empty() {
+            anyhow::bail!("no chunks");
+        }
+
+        self.render(content, Some(state)).await?;
+
+        Ok(self.messages.iter().map(|(id, _)| *id).collect())
+    }
+
+    /// Deletes every message streamed so far, used when the response failed.
+    pub async fn discard(mut self) {
+        if let Err(why) = self.render("", None).await {
+            log::warn!("failed to delete streamed response: {why:?}");
+        }
+    }
+
+    async fn render(&mut self, content: &str, state: Option<ButtonStates>) -> anyhow::Result<()> {
+        let chunks = misc::chunk_string(content);
+        let mut state = state;
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let components = match i + 1 == chunks.len() {
+                true => state.take().map(|state| vec![misc::action_row(state)]),
+                false => None,
+            };
+
+            match self.messages.get_mut(i) {
+                Some((id, content)) => {
+                    if *content == *chunk && components.is_none() {
+                        continue;
+                    }
+
+                    let mut edit = EditMessage::new().content(chunk);
+                    if let Some(components) = components {
+                        edit = edit.components(components);
+                    }
+
+                    self.outbound.edit_message(self.channel, *id, edit).await?;
+                    *content = chunk.clone();
+                }
+                None => {
+                    let mut create = CreateMessage::new().content(chunk);
+                    if let Some(components) = components {
+                        create = create.components(components);
+                    }
+
+                    let id = self.outbound.send_message(self.channel, create).await?;
+                    self.messages.push((id, chunk.clone()));
+                }
+            }
+        }
+
+        // the response got shorter, either because it was reset or cleaned up at the end
+        for (id, _) in self.messages.split_off(chunks.len()) {
+            self.outbound.delete_message(self.channel, id).await?;
+        }
+
+        self.last_edit = Instant::now();
+
+        Ok(())
+    }
+}